@@ -265,11 +265,37 @@ impl ParsingRules {
 
     fn compile_regex(name: &'static str, regex_rules: Vec<String>) -> io::Result<RegexSet> {
         const REGEX_SIZE_LIMIT: usize = usize::MAX;
-        RegexSetBuilder::new(regex_rules)
-            .size_limit(REGEX_SIZE_LIMIT)
-            .unicode(false)
-            .build()
-            .map_err(|err| Error::new(ErrorKind::Other, format!("{name} regex error: {err}")))
+
+        let build = |rules: &[String]| {
+            RegexSetBuilder::new(rules)
+                .size_limit(REGEX_SIZE_LIMIT)
+                .unicode(false)
+                .build()
+        };
+
+        if let Ok(set) = build(&regex_rules) {
+            return Ok(set);
+        }
+
+        // Find out and skip malformed rules, then try again
+        let regex_rules: Vec<String> = regex_rules
+            .into_iter()
+            .filter(|rule| {
+                match RegexBuilder::new(rule)
+                    .size_limit(REGEX_SIZE_LIMIT)
+                    .unicode(false)
+                    .build()
+                {
+                    Ok(..) => true,
+                    Err(err) => {
+                        warn!("ACL {} regex rule {} skipped, error: {}", name, rule, err);
+                        false
+                    }
+                }
+            })
+            .collect();
+
+        build(&regex_rules).map_err(|err| Error::new(ErrorKind::Other, format!("{name} regex error: {err}")))
     }
 
     fn into_rules(self) -> io::Result<Rules> {
@@ -343,6 +369,8 @@ pub struct AccessControl {
 
 impl AccessControl {
     /// Load ACL rules from a file
+    ///
+    /// Malformed rules are skipped with warnings, errors are only returned for failures of reading the file.
     pub fn load_from_file<P: AsRef<Path>>(p: P) -> io::Result<AccessControl> {
         trace!("ACL loading from {:?}", p.as_ref());
 
@@ -363,6 +391,7 @@ impl AccessControl {
 
         for line in r.lines() {
             let line = line?;
+            let line = line.trim();
             if line.is_empty() {
                 continue;
             }
@@ -372,20 +401,22 @@ impl AccessControl {
                 continue;
             }
 
-            let line = line.trim();
-
             if !line.is_ascii() {
                 warn!("ACL rule {} containing non-ASCII characters, skipped", line);
                 continue;
             }
 
             if let Some(rule) = line.strip_prefix("||") {
-                curr.add_tree_rule(rule)?;
+                if let Err(err) = curr.add_tree_rule(rule) {
+                    warn!("ACL rule {} skipped, error: {}", line, err);
+                }
                 continue;
             }
 
             if let Some(rule) = line.strip_prefix('|') {
-                curr.add_set_rule(rule)?;
+                if let Err(err) = curr.add_set_rule(rule) {
+                    warn!("ACL rule {} skipped, error: {}", line, err);
+                }
                 continue;
             }
 
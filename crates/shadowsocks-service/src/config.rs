@@ -43,39 +43,46 @@
 
 use std::{
     borrow::Cow,
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
     convert::{From, Infallible},
     default::Default,
     env,
     fmt::{self, Debug, Display, Formatter},
-    fs::OpenOptions,
-    io::Read,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Write},
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    ops::RangeInclusive,
     option::Option,
     path::{Path, PathBuf},
     str::FromStr,
     string::ToString,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
+use base64::Engine as _;
 use cfg_if::cfg_if;
 #[cfg(feature = "hickory-dns")]
 use hickory_resolver::config::{NameServerConfig, ResolverConfig};
-#[cfg(feature = "local-tun")]
 use ipnet::IpNet;
 #[cfg(feature = "local-fake-dns")]
 use ipnet::{Ipv4Net, Ipv6Net};
-use log::warn;
-use serde::{Deserialize, Serialize};
-#[cfg(any(feature = "local-tunnel", feature = "local-dns"))]
-use shadowsocks::relay::socks5::Address;
+use log::{debug, warn};
+use regex::Regex;
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
 use shadowsocks::{
     config::{
-        ManagerAddr, Mode, ReplayAttackPolicy, ServerAddr, ServerConfig, ServerSource, ServerUser, ServerUserManager,
-        ServerWeight,
+        parse_method, KdfConfig, ManagerAddr, Mode, ReplayAttackPolicy, ServerAddr, ServerConfig, ServerSource, ServerUser,
+        ServerUserManager, ServerWeight,
     },
     crypto::CipherKind,
     plugin::PluginConfig,
+    relay::{socks5::Address, tcprelay::utils::request_probe_len},
 };
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
 use crate::acl::AccessControl;
 #[cfg(feature = "local-dns")]
@@ -87,6 +94,7 @@ use crate::local::socks::config::Socks5AuthConfig;
 #[serde(untagged)]
 enum SSDnsConfig {
     Simple(String),
+    List(Vec<String>),
     #[cfg(feature = "hickory-dns")]
     HickoryDns(ResolverConfig),
 }
@@ -103,6 +111,19 @@ struct SSSecurityReplayAttackConfig {
     policy: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+enum SSOutboundSocks5Config {
+    Simple(String),
+    Detailed {
+        address: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        password: Option<String>,
+    },
+}
+
 #[derive(Serialize, Deserialize, Debug, Default)]
 struct SSBalancerConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -113,15 +134,34 @@ struct SSBalancerConfig {
     check_best_interval: Option<u64>,
 }
 
+#[cfg(feature = "local")]
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct SSLocalClientAclConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allow: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deny: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct SSHealthCheckConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeout: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Default)]
 struct SSConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     server: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    server_port: Option<u16>,
+    server_port: Option<SSServerPortConfig>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    local_address: Option<String>,
+    local_address: Option<SSLocalAddressConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     local_port: Option<u16>,
 
@@ -145,6 +185,20 @@ struct SSConfig {
     password: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     method: Option<String>,
+    /// Method tried by servers if requests couldn't be decrypted with `method`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fallback_method: Option<String>,
+    /// Key derivation parameters of `password`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kdf: Option<SSKdfConfig>,
+    /// Validity window of servers, RFC3339 timestamps
+    #[serde(skip_serializing_if = "Option::is_none", alias = "start_time")]
+    valid_from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "end_time")]
+    valid_until: Option<String>,
+    /// Candidates of `"method": "auto"`, in priority order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auto_methods: Option<Vec<String>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     plugin: Option<String>,
@@ -157,6 +211,21 @@ struct SSConfig {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     timeout: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    connect_timeout: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    idle_timeout: Option<u64>,
+    /// Parsed as `i64` for validating, json5 saturates out of range integers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_connections: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_retries: Option<i64>,
+    /// In milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_backoff: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shutdown_timeout: Option<u64>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     udp_timeout: Option<u64>,
@@ -164,9 +233,16 @@ struct SSConfig {
     udp_max_associations: Option<usize>,
     #[serde(skip_serializing_if = "Option::is_none")]
     udp_mtu: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    udp_bind_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    udp_max_payload: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit_rate: Option<SSRateLimitConfig>,
 
     #[serde(skip_serializing_if = "Option::is_none", alias = "shadowsocks")]
-    servers: Option<Vec<SSServerExtConfig>>,
+    servers: Option<Vec<SSServerEntryConfig>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     locals: Option<Vec<SSLocalExtConfig>>,
@@ -174,11 +250,16 @@ struct SSConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     dns: Option<SSDnsConfig>,
 
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "Option::is_none", alias = "dns_cache_capacity")]
     dns_cache_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dns_cache: Option<bool>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     mode: Option<String>,
+    /// Shorthand of `mode`, ignored if `mode` is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    enable_udp: Option<bool>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     no_delay: Option<bool>,
@@ -193,7 +274,13 @@ struct SSConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     ipv6_first: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    disable_ipv6: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     ipv6_only: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reuse_port: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reuse_address: Option<bool>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     fast_open: Option<bool>,
@@ -201,9 +288,9 @@ struct SSConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     mptcp: Option<bool>,
 
+    /// Parsed as `i64` for validating, json5 saturates out of range integers
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[cfg(any(target_os = "linux", target_os = "android"))]
-    outbound_fwmark: Option<u32>,
+    outbound_fwmark: Option<i64>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     #[cfg(target_os = "freebsd")]
@@ -215,25 +302,64 @@ struct SSConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     outbound_bind_interface: Option<String>,
 
+    /// Parsed as `i64` for validating, json5 saturates out of range integers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outbound_ttl: Option<i64>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     outbound_udp_allow_fragmentation: Option<bool>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outbound_socks5: Option<SSOutboundSocks5Config>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    forbidden_ip: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    forbidden_ip_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    forbidden_domain_regex: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    udp_allowed_ports: Option<Vec<i64>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bypass_ip: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proxy_ip: Option<Vec<String>>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     security: Option<SSSecurityConfig>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     balancer: Option<SSBalancerConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    health_check: Option<SSHealthCheckConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preferred_server: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    log_level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    log_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    access_log: Option<bool>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     acl: Option<String>,
 
-    #[cfg(feature = "local-online-config")]
     #[serde(skip_serializing_if = "Option::is_none")]
     version: Option<u32>,
 
+    /// Configuration fragments, merged in `Config::load_from_file`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    include: Option<Vec<String>>,
+
     #[cfg(feature = "local-online-config")]
     #[serde(skip_serializing_if = "Option::is_none")]
     online_config: Option<SSOnlineConfig>,
+
+    /// Unknown keys
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
@@ -323,6 +449,18 @@ struct SSLocalExtConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     socks5_auth_config_path: Option<String>,
 
+    #[cfg(feature = "local")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    local_client_acl: Option<SSLocalClientAclConfig>,
+
+    /// HTTP
+    #[cfg(feature = "local-http")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    local_http_username: Option<String>,
+    #[cfg(feature = "local-http")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    local_http_password: Option<String>,
+
     /// Fake DNS
     #[cfg(feature = "local-fake-dns")]
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -337,10 +475,111 @@ struct SSLocalExtConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fake_dns_database_path: Option<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    servers: Option<Vec<usize>>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     acl: Option<String>,
 }
 
+/// Rate limit, could be bytes per second or a string with unit, like `"10mbit"`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+enum SSRateLimitConfig {
+    BytesPerSec(u64),
+    Rate(String),
+}
+
+/// Local address, could be an address or a list of addresses sharing the same `local_port`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+enum SSLocalAddressConfig {
+    Address(String),
+    Addresses(Vec<String>),
+}
+
+impl SSLocalAddressConfig {
+    /// Expand into all addresses
+    fn into_addresses(self) -> Result<Vec<String>, Error> {
+        match self {
+            SSLocalAddressConfig::Address(addr) => Ok(vec![addr]),
+            SSLocalAddressConfig::Addresses(addrs) => {
+                if addrs.is_empty() {
+                    let err = Error::new(ErrorKind::Malformed, "`local_address` cannot be an empty array", None);
+                    return Err(err);
+                }
+                Ok(addrs)
+            }
+        }
+    }
+}
+
+/// Server port, could be a port number or a range of ports like `"40000-40010"`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+enum SSServerPortConfig {
+    Port(u16),
+    Range(String),
+}
+
+impl SSServerPortConfig {
+    /// Expand into all ports
+    fn to_ports(&self) -> Result<Vec<u16>, Error> {
+        let range = match *self {
+            SSServerPortConfig::Port(port) => return Ok(vec![port]),
+            SSServerPortConfig::Range(ref range) => range,
+        };
+
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (start.trim(), end.trim()),
+            None => (range.trim(), range.trim()),
+        };
+
+        match (start.parse::<u16>(), end.parse::<u16>()) {
+            (Ok(start), Ok(end)) if start <= end && usize::from(end - start) < MAX_SERVER_PORT_RANGE => {
+                Ok((start..=end).collect())
+            }
+            (Ok(start), Ok(end)) if start <= end => {
+                let err = Error::new(
+                    ErrorKind::Invalid,
+                    "too many ports in `server_port` range",
+                    Some(format!("{range}, at most {MAX_SERVER_PORT_RANGE} ports are allowed")),
+                );
+                Err(err)
+            }
+            (Ok(..), Ok(..)) => {
+                let err = Error::new(
+                    ErrorKind::Invalid,
+                    "invalid `server_port` range, start port must be less than or equal to end port",
+                    Some(range.clone()),
+                );
+                Err(err)
+            }
+            _ => {
+                let err = Error::new(
+                    ErrorKind::Malformed,
+                    "malformed `server_port`, should be a port or a range of ports like \"40000-40010\"",
+                    Some(range.clone()),
+                );
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Server in `servers`, could be a SIP002 URL or an object
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+enum SSServerEntryConfig {
+    Url(String),
+    Detailed(Box<SSServerExtConfig>),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct SSKdfConfig {
+    iterations: u32,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct SSServerUserConfig {
     name: String,
@@ -355,11 +594,20 @@ struct SSServerExtConfig {
     #[serde(alias = "address")]
     server: String,
     #[serde(alias = "port")]
-    server_port: u16,
+    server_port: SSServerPortConfig,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     password: Option<String>,
-    method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fallback_method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kdf: Option<SSKdfConfig>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "start_time")]
+    valid_from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "end_time")]
+    valid_until: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     users: Option<Vec<SSServerUserConfig>>,
@@ -378,11 +626,25 @@ struct SSServerExtConfig {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     timeout: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    connect_timeout: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    idle_timeout: Option<u64>,
+    /// Parsed as `i64` for validating, json5 saturates out of range integers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_connections: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_retries: Option<i64>,
+    /// In milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_backoff: Option<i64>,
 
     #[serde(skip_serializing_if = "Option::is_none", alias = "name")]
     remarks: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     mode: Option<String>,
@@ -395,9 +657,9 @@ struct SSServerExtConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     acl: Option<String>,
 
+    /// Parsed as `i64` for validating, json5 saturates out of range integers
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[cfg(any(target_os = "linux", target_os = "android"))]
-    outbound_fwmark: Option<u32>,
+    outbound_fwmark: Option<i64>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     outbound_bind_addr: Option<IpAddr>,
@@ -407,6 +669,9 @@ struct SSServerExtConfig {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     outbound_udp_allow_fragmentation: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outbound_socks5: Option<SSOutboundSocks5Config>,
 }
 
 #[cfg(feature = "local-online-config")]
@@ -873,6 +1138,8 @@ impl ProtocolType {
             "socks",
             #[cfg(feature = "local-http")]
             "http",
+            #[cfg(feature = "local-http")]
+            "auto",
             #[cfg(feature = "local-tunnel")]
             "tunnel",
             #[cfg(feature = "local-redir")]
@@ -905,6 +1172,9 @@ impl FromStr for ProtocolType {
             "socks" => Ok(ProtocolType::Socks),
             #[cfg(feature = "local-http")]
             "http" => Ok(ProtocolType::Http),
+            // SOCKS server detects HTTP requests by the first byte, see `SocksTcpHandler::handle_tcp_client`
+            #[cfg(feature = "local-http")]
+            "auto" => Ok(ProtocolType::Socks),
             #[cfg(feature = "local-tunnel")]
             "tunnel" => Ok(ProtocolType::Tunnel),
             #[cfg(feature = "local-redir")]
@@ -920,11 +1190,39 @@ impl FromStr for ProtocolType {
     }
 }
 
+/// Source IP access control of a local server's listener
+#[cfg(feature = "local")]
+#[derive(Clone, Debug, Default)]
+pub struct LocalClientAclConfig {
+    /// Clients in these networks are allowed, all clients are allowed if it is empty
+    pub allow: Vec<IpNet>,
+    /// Clients in these networks are denied, takes precedence over `allow`
+    pub deny: Vec<IpNet>,
+}
+
+#[cfg(feature = "local")]
+impl LocalClientAclConfig {
+    /// Check if a client from `ip` is allowed
+    pub fn is_allowed(&self, ip: &IpAddr) -> bool {
+        // IPv4 clients of dual-stack listeners are IPv4-mapped IPv6 addresses
+        let ip = ip.to_canonical();
+        if self.deny.iter().any(|net| net.contains(&ip)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|net| net.contains(&ip))
+    }
+}
+
 /// Local server configuration
 #[derive(Clone, Debug)]
 pub struct LocalConfig {
     /// Listen address for local servers
     pub addr: Option<ServerAddr>,
+    /// Listen on this Unix Domain Socket path instead of `addr`, set by `local_address` `unix:/path/to/socket`
+    ///
+    /// Only SOCKS5 in `tcp_only` mode is supported
+    #[cfg(unix)]
+    pub unix_addr: Option<PathBuf>,
 
     /// Local Protocol
     pub protocol: ProtocolType,
@@ -1031,6 +1329,14 @@ pub struct LocalConfig {
     #[cfg(feature = "local")]
     pub socks5_auth: Socks5AuthConfig,
 
+    /// Source IP access control of listener, allows all clients if not set
+    #[cfg(feature = "local")]
+    pub client_acl: Option<LocalClientAclConfig>,
+
+    /// HTTP proxy Basic authentication, `(username, password)`
+    #[cfg(feature = "local-http")]
+    pub http_proxy_auth: Option<(String, String)>,
+
     /// Fake DNS record expire seconds
     #[cfg(feature = "local-fake-dns")]
     pub fake_dns_record_expire_duration: Option<Duration>,
@@ -1058,6 +1364,8 @@ impl LocalConfig {
 
         LocalConfig {
             addr: None,
+            #[cfg(unix)]
+            unix_addr: None,
 
             protocol,
 
@@ -1100,6 +1408,11 @@ impl LocalConfig {
 
             #[cfg(feature = "local")]
             socks5_auth: Socks5AuthConfig::default(),
+            #[cfg(feature = "local")]
+            client_acl: None,
+
+            #[cfg(feature = "local-http")]
+            http_proxy_auth: None,
 
             #[cfg(feature = "local-fake-dns")]
             fake_dns_record_expire_duration: None,
@@ -1125,13 +1438,42 @@ impl LocalConfig {
             ProtocolType::Tun => {}
 
             _ => {
-                if self.addr.is_none() {
-                    let err = Error::new(ErrorKind::MissingField, "missing `addr` in configuration", None);
+                #[cfg(unix)]
+                let has_addr = self.addr.is_some() || self.unix_addr.is_some();
+                #[cfg(not(unix))]
+                let has_addr = self.addr.is_some();
+
+                if !has_addr {
+                    let err = Error::new(
+                        ErrorKind::MissingField,
+                        "missing `local_address` and `local_port` for local server",
+                        Some(format!("protocol {}", self.protocol.as_str())),
+                    );
                     return Err(err);
                 }
             }
         }
 
+        #[cfg(unix)]
+        if self.unix_addr.is_some() {
+            if self.protocol != ProtocolType::Socks {
+                let err = Error::new(
+                    ErrorKind::Invalid,
+                    "unix domain socket `local_address` only supports socks protocol",
+                    Some(format!("protocol {}", self.protocol.as_str())),
+                );
+                return Err(err);
+            }
+            if self.mode.enable_udp() {
+                let err = Error::new(
+                    ErrorKind::Invalid,
+                    "unix domain socket `local_address` only supports `tcp_only` mode",
+                    None,
+                );
+                return Err(err);
+            }
+        }
+
         match self.protocol {
             #[cfg(feature = "local-dns")]
             ProtocolType::Dns => {
@@ -1163,6 +1505,17 @@ impl LocalConfig {
             _ => {}
         }
 
+        // HTTP requests are served by `http` locals, and `socks` (`auto`) locals sniffing HTTP requests
+        #[cfg(feature = "local-http")]
+        if self.http_proxy_auth.is_some() && !matches!(self.protocol, ProtocolType::Http | ProtocolType::Socks) {
+            let err = Error::new(
+                ErrorKind::Invalid,
+                "`local_http_username` and `local_http_password` are only supported by http and socks protocols",
+                Some(format!("protocol {}", self.protocol.as_str())),
+            );
+            return Err(err);
+        }
+
         Ok(())
     }
 
@@ -1172,6 +1525,11 @@ impl LocalConfig {
             return false;
         }
 
+        #[cfg(unix)]
+        if self.unix_addr.is_some() {
+            return false;
+        }
+
         #[cfg(feature = "local-tunnel")]
         if self.forward_addr.is_some() {
             return false;
@@ -1223,6 +1581,105 @@ pub struct BalancerConfig {
     pub check_best_interval: Option<Duration>,
 }
 
+/// Active health checks of the balancer
+///
+/// Values set here take precedence over `check_interval` and `max_server_rtt` of [`BalancerConfig`].
+#[derive(Clone, Debug, Default)]
+pub struct HealthCheckConfig {
+    /// Interval between each checking
+    pub interval: Option<Duration>,
+    /// Timeout of each check request
+    pub timeout: Option<Duration>,
+    /// HTTP server probed through each server, the builtin connectivity check is used if not set
+    pub target: Option<HealthCheckTarget>,
+}
+
+/// HTTP server probed by health checks, in form of `http://host[:port][/path]` or `host:port`
+///
+/// A check succeeds if it gets any valid HTTP response of `GET path`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HealthCheckTarget {
+    /// Address of the HTTP server
+    pub addr: Address,
+    /// Path of the request, `/` by default
+    pub path: String,
+}
+
+/// Parsing HealthCheckTarget error
+#[derive(Debug, Clone, Copy)]
+pub struct HealthCheckTargetError;
+
+impl Display for HealthCheckTargetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid HealthCheckTarget")
+    }
+}
+
+impl FromStr for HealthCheckTarget {
+    type Err = HealthCheckTargetError;
+
+    fn from_str(s: &str) -> Result<HealthCheckTarget, Self::Err> {
+        let target = match s.split_once("://") {
+            Some(("http", target)) => target,
+            Some(..) => return Err(HealthCheckTargetError),
+            None => s,
+        };
+
+        let (host, path) = match target.find('/') {
+            Some(pos) => target.split_at(pos),
+            None => (target, "/"),
+        };
+        if host.is_empty() {
+            return Err(HealthCheckTargetError);
+        }
+
+        // Port is 80 if it is omitted
+        let addr = match host.parse::<Address>() {
+            Ok(Address::DomainNameAddress(dn, port)) => match dn.parse::<IpAddr>() {
+                Ok(ip) => Address::SocketAddress(SocketAddr::new(ip, port)),
+                Err(..) => Address::DomainNameAddress(dn, port),
+            },
+            Ok(addr) => addr,
+            Err(..) => return Err(HealthCheckTargetError),
+        };
+
+        Ok(HealthCheckTarget {
+            addr,
+            path: path.to_owned(),
+        })
+    }
+}
+
+impl Display for HealthCheckTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "http://{}{}", self.addr, self.path)
+    }
+}
+
+/// Outbound SOCKS5 proxy configuration
+///
+/// Servers' outbound TCP connections will be sent through this proxy (proxy chaining)
+#[derive(Clone, Debug)]
+pub struct OutboundProxyConfig {
+    /// SOCKS5 proxy's address
+    pub addr: ServerAddr,
+    /// Username for Username/Password Authentication (RFC1929)
+    pub username: Option<String>,
+    /// Password for Username/Password Authentication (RFC1929)
+    pub password: Option<String>,
+}
+
+impl OutboundProxyConfig {
+    /// Create a new `OutboundProxyConfig` without authentication
+    pub fn new(addr: ServerAddr) -> OutboundProxyConfig {
+        OutboundProxyConfig {
+            addr,
+            username: None,
+            password: None,
+        }
+    }
+}
+
 /// Address for local to report flow statistic data
 #[cfg(feature = "local-flow-stat")]
 #[derive(Debug, Clone)]
@@ -1247,6 +1704,8 @@ pub struct ServerInstanceConfig {
     pub outbound_bind_addr: Option<IpAddr>,
     pub outbound_bind_interface: Option<String>,
     pub outbound_udp_allow_fragmentation: Option<bool>,
+    /// Server's private outbound SOCKS5 proxy, set to `None` will use the global `outbound_proxy`
+    pub outbound_proxy: Option<OutboundProxyConfig>,
 }
 
 impl ServerInstanceConfig {
@@ -1260,8 +1719,17 @@ impl ServerInstanceConfig {
             outbound_bind_addr: None,
             outbound_bind_interface: None,
             outbound_udp_allow_fragmentation: None,
+            outbound_proxy: None,
         }
     }
+
+    /// Outbound SOCKS5 proxy of this server, the private one takes precedence over the `global` one
+    pub fn effective_outbound_proxy<'a>(
+        &'a self,
+        global: Option<&'a OutboundProxyConfig>,
+    ) -> Option<&'a OutboundProxyConfig> {
+        self.outbound_proxy.as_ref().or(global)
+    }
 }
 
 /// Local instance config
@@ -1271,12 +1739,18 @@ pub struct LocalInstanceConfig {
     pub config: LocalConfig,
     /// Server's private ACL, set to `None` will use the global `AccessControl`
     pub acl: Option<AccessControl>,
+    /// Indices of `Config::server` that this local server is allowed to use, set to `None` will use all servers
+    pub servers: Option<Vec<usize>>,
 }
 
 impl LocalInstanceConfig {
     /// Create with `LocalConfig`
     pub fn with_local_config(config: LocalConfig) -> LocalInstanceConfig {
-        LocalInstanceConfig { config, acl: None }
+        LocalInstanceConfig {
+            config,
+            acl: None,
+            servers: None,
+        }
     }
 }
 
@@ -1291,6 +1765,49 @@ pub struct OnlineConfig {
     pub update_interval: Option<Duration>,
 }
 
+/// Maximum value of `dns_cache_size`, larger values will be clamped to it
+pub const MAX_DNS_CACHE_SIZE: usize = 65536;
+
+/// Range of `udp_max_payload`, from the minimum DNS over UDP message size to the maximum IPv4 UDP payload size
+pub const UDP_MAX_PAYLOAD_RANGE: RangeInclusive<usize> = 512..=65507;
+
+/// Default `timeout` of servers if neither the server nor the top-level configuration sets one
+///
+/// Applied by [`ServerConfig::connect_timeout`] and [`ServerConfig::idle_timeout`], so it also covers servers that are
+/// not loaded from configuration files. Set `timeout` to `0` explicitly for no timeout.
+pub use shadowsocks::config::DEFAULT_TIMEOUT;
+
+/// Maximum number of ports in a `server_port` range, each port is a separate server instance
+pub const MAX_SERVER_PORT_RANGE: usize = 1024;
+
+/// Default `shutdown_timeout`
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Default `local_port` if only `local_address` is set
+pub const DEFAULT_LOCAL_PORT: u16 = 1080;
+
+/// Latest format version of configuration files, set by `version`
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Maximum size of configuration files accepted by [`Config::load_from_file`], in bytes
+pub const MAX_CONFIG_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Integer weight of a server with `tcp_weight` 1.0, used by [`Config::total_weight`] and [`Config::pick_weighted`]
+pub const SERVER_WEIGHT_SCALE: u64 = 1000;
+
+/// Method name that is resolved into a concrete method by `auto_methods` while loading local configurations
+pub const AUTO_METHOD: &str = "auto";
+
+/// Default `auto_methods`, strongest first. Methods that are not enabled by features are skipped
+pub const DEFAULT_AUTO_METHODS: &[&str] = &[
+    "2022-blake3-aes-256-gcm",
+    "2022-blake3-chacha20-poly1305",
+    "2022-blake3-aes-128-gcm",
+    "aes-256-gcm",
+    "chacha20-ietf-poly1305",
+    "aes-128-gcm",
+];
+
 /// Configuration
 #[derive(Clone, Debug)]
 pub struct Config {
@@ -1309,13 +1826,26 @@ pub struct Config {
     /// - `cloudflare`, `cloudflare_tls`, `cloudflare_https`
     /// - `quad9`, `quad9_tls`
     pub dns: DnsConfig,
+    /// Name servers of `dns` are tried one by one in the configured order, set by the list form of `dns`
+    pub dns_in_order: bool,
+    /// DNS cache size, `0` to disable DNS cache, at most `MAX_DNS_CACHE_SIZE`
     pub dns_cache_size: Option<usize>,
     /// Uses IPv6 addresses first
     ///
     /// Set to `true` if you want to query IPv6 addresses before IPv4
     pub ipv6_first: bool,
+    /// Never use IPv6
+    ///
+    /// IPv6 addresses resolved from hostnames are dropped, and listening on IPv6 addresses is not allowed
+    pub disable_ipv6: bool,
     /// Set `IPV6_V6ONLY` for listener sockets
     pub ipv6_only: bool,
+    /// Set `SO_REUSEPORT` for servers' listener sockets
+    pub reuse_port: bool,
+    /// Set `SO_REUSEADDR` for TCP listener sockets, `true` by default
+    ///
+    /// Disable it to fail binding while the address is still held by sockets of a previous instance
+    pub reuse_address: bool,
 
     /// Set `TCP_NODELAY` socket option
     pub no_delay: bool,
@@ -1344,8 +1874,22 @@ pub struct Config {
     pub outbound_bind_interface: Option<String>,
     /// Outbound sockets will `bind` to this address
     pub outbound_bind_addr: Option<IpAddr>,
+    /// Set `IP_TTL` (IPv4) or `IPV6_UNICAST_HOPS` (IPv6) socket option for outbound sockets
+    pub outbound_ttl: Option<u32>,
     /// Outbound UDP sockets allow IP fragmentation
     pub outbound_udp_allow_fragmentation: bool,
+    /// Outbound SOCKS5 proxy for servers' TCP connections
+    pub outbound_proxy: Option<OutboundProxyConfig>,
+    /// Servers are not allowed to connect to these IPs or networks
+    pub forbidden_ip: Vec<IpNet>,
+    /// Servers are not allowed to connect to domain names matching these patterns, see [`Config::domain_matches_forbidden_regex`]
+    pub forbidden_domain_regex: Vec<Regex>,
+    /// Servers' UDP relay only forwards packets to these destination ports, all ports are allowed if `None`
+    pub udp_allowed_ports: Option<Vec<u16>>,
+    /// Destinations in these IPs or networks should be connected directly, see [`Config::route_for`]
+    pub bypass_ip: Vec<IpNet>,
+    /// Destinations in these IPs or networks should be connected through proxy, see [`Config::route_for`]
+    pub proxy_ip: Vec<IpNet>,
     /// Path to protect callback unix address, only for Android
     #[cfg(target_os = "android")]
     pub outbound_vpn_protect_path: Option<PathBuf>,
@@ -1365,6 +1909,11 @@ pub struct Config {
     /// Config is for Client or Server
     pub config_type: ConfigType,
 
+    /// Time to wait for active TCP connections to finish when shutting down, default is [`DEFAULT_SHUTDOWN_TIMEOUT`]
+    ///
+    /// Zero closes connections immediately
+    pub shutdown_timeout: Option<Duration>,
+
     /// Timeout for UDP Associations, default is 5 minutes
     pub udp_timeout: Option<Duration>,
     /// Maximum number of UDP Associations, default is unconfigured
@@ -1373,6 +1922,22 @@ pub struct Config {
     /// 65535 by default. Suggestion: 1500
     /// NOTE: mtu includes IP header, UDP header, UDP payload
     pub udp_mtu: Option<usize>,
+    /// Address advertised in SOCKS5 UDP ASSOCIATE replies, derived from the local listen address if not set
+    ///
+    /// Overridden by `udp_associate_addr` of the local instance
+    pub udp_bind_addr: Option<SocketAddr>,
+    /// Size of buffers receiving UDP packets from targets in relays, in [`UDP_MAX_PAYLOAD_RANGE`]
+    ///
+    /// [`MAXIMUM_UDP_PAYLOAD_SIZE`](shadowsocks::relay::udprelay::MAXIMUM_UDP_PAYLOAD_SIZE) if not set, which already
+    /// fits any UDP packet. Setting it only saves memory, packets larger than it are truncated
+    pub udp_max_payload: Option<usize>,
+
+    /// Maximum throughput of TCP relays in bytes per second, set by `limit_rate`
+    ///
+    /// This is an aggregate limit shared by all TCP connections of the process, not per-connection.
+    /// Upload and download draw from the same budget, so it limits their sum, not outbound traffic alone.
+    /// UDP associations are not limited. `0` means unlimited, same as `None`.
+    pub max_rate_bytes_per_sec: Option<u64>,
 
     /// ACL configuration (Global)
     ///
@@ -1388,10 +1953,31 @@ pub struct Config {
 
     /// Balancer config of local server
     pub balancer: BalancerConfig,
+    /// Active health checks of the balancer
+    pub health_check: Option<HealthCheckConfig>,
+    /// Index of the server in `server` that balancers always choose while it is passing health checks
+    pub preferred_server: Option<usize>,
 
-    /// Configuration file path, the actual path of the configuration.
-    /// This is normally for auto-reloading if implementation supports.
-    pub config_path: Option<PathBuf>,
+    /// Log level, one of `off`, `error`, `warn`, `info`, `debug` and `trace`
+    ///
+    /// Applied by the binaries' logger, `-v` and `RUST_LOG` take precedence
+    pub log_level: Option<String>,
+    /// Log output file path, logs are written to stdout if not set
+    pub log_file: Option<String>,
+    /// Log a summary of every completed TCP connection of local servers, disabled by default
+    pub access_log: bool,
+
+    /// Format version of the configuration, [`CONFIG_VERSION`] if `version` is not set
+    pub version: u32,
+
+    /// Unknown top-level keys, kept as is for saving the configuration without losing them
+    ///
+    /// Values are opaque and not validated.
+    pub extra: serde_json::Map<String, serde_json::Value>,
+
+    /// Configuration file path, the actual path of the configuration.
+    /// This is normally for auto-reloading if implementation supports.
+    pub config_path: Option<PathBuf>,
 
     /// OnlineConfiguration (SIP008)
     /// https://shadowsocks.org/doc/sip008.html
@@ -1400,7 +1986,7 @@ pub struct Config {
 }
 
 /// Configuration parsing error kind
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ErrorKind {
     /// Missing required fields in JSON configuration
     MissingField,
@@ -1444,7 +2030,20 @@ macro_rules! impl_from {
 }
 
 impl_from!(::std::io::Error, ErrorKind::IoError, "error while reading file");
-impl_from!(json5::Error, ErrorKind::JsonParsingError, "json parse error");
+
+impl From<json5::Error> for Error {
+    fn from(err: json5::Error) -> Self {
+        // Syntax errors have locations, show them first for locating quickly in large files
+        let detail = match err {
+            json5::Error::Message {
+                msg,
+                location: Some(location),
+            } => format!("line {}, column {}: {}", location.line, location.column, msg),
+            json5::Error::Message { msg, location: None } => msg,
+        };
+        Error::new(ErrorKind::JsonParsingError, "json parse error", Some(detail))
+    }
+}
 
 impl Debug for Error {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
@@ -1464,6 +2063,9 @@ impl Display for Error {
     }
 }
 
+/// Result of configuration operations, with [`Error`] by default
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
 impl Config {
     /// Creates an empty configuration
     pub fn new(config_type: ConfigType) -> Config {
@@ -1472,9 +2074,13 @@ impl Config {
             local: Vec::new(),
 
             dns: DnsConfig::default(),
+            dns_in_order: false,
             dns_cache_size: None,
             ipv6_first: false,
+            disable_ipv6: false,
             ipv6_only: false,
+            reuse_port: false,
+            reuse_address: true,
 
             no_delay: false,
             fast_open: false,
@@ -1490,7 +2096,14 @@ impl Config {
             outbound_user_cookie: None,
             outbound_bind_interface: None,
             outbound_bind_addr: None,
+            outbound_ttl: None,
             outbound_udp_allow_fragmentation: false,
+            outbound_proxy: None,
+            forbidden_ip: Vec::new(),
+            forbidden_domain_regex: Vec::new(),
+            udp_allowed_ports: None,
+            bypass_ip: Vec::new(),
+            proxy_ip: Vec::new(),
             #[cfg(target_os = "android")]
             outbound_vpn_protect_path: None,
 
@@ -1503,9 +2116,13 @@ impl Config {
 
             config_type,
 
+            shutdown_timeout: None,
             udp_timeout: None,
             udp_max_associations: None,
             udp_mtu: None,
+            udp_bind_addr: None,
+            udp_max_payload: None,
+            max_rate_bytes_per_sec: None,
 
             acl: None,
 
@@ -1515,6 +2132,14 @@ impl Config {
             security: SecurityConfig::default(),
 
             balancer: BalancerConfig::default(),
+            health_check: None,
+            preferred_server: None,
+            log_level: None,
+            log_file: None,
+            access_log: false,
+
+            version: CONFIG_VERSION,
+            extra: serde_json::Map::new(),
 
             config_path: None,
 
@@ -1526,9 +2151,86 @@ impl Config {
     fn load_from_ssconfig(config: SSConfig, config_type: ConfigType) -> Result<Config, Error> {
         let mut nconfig = Config::new(config_type);
 
+        // Includes are resolved in `Config::load_from_file`
+        if config.include.is_some() {
+            let err = Error::new(
+                ErrorKind::Invalid,
+                "`include` is only supported in configuration files",
+                None,
+            );
+            return Err(err);
+        }
+
+        // Format version
+        match config.version {
+            Some(0) => {
+                let err = Error::new(
+                    ErrorKind::Invalid,
+                    "invalid `version`",
+                    Some(format!(
+                        "version 0 is not a valid version, remove it or set it to {CONFIG_VERSION}"
+                    )),
+                );
+                return Err(err);
+            }
+            Some(v) if v > CONFIG_VERSION => {
+                warn!(
+                    "configuration version {} is newer than the supported version {}, some keys may not be recognized",
+                    v, CONFIG_VERSION
+                );
+                nconfig.version = v;
+            }
+            Some(v) => nconfig.version = v,
+            None => {}
+        }
+
+        for key in config.extra.keys() {
+            debug!("unknown key `{}` in configuration", key);
+        }
+        nconfig.extra = config.extra;
+
+        // SO_MARK, servers inherit it
+        let outbound_fwmark = match config.outbound_fwmark {
+            Some(fwmark) => Some(check_outbound_fwmark(fwmark)?),
+            None => None,
+        };
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        if let Some(fwmark) = outbound_fwmark {
+            warn!(
+                "`outbound_fwmark` {} is only supported on Linux and Android, ignored",
+                fwmark
+            );
+        }
+
+        // Candidates of `"method": "auto"`
+        let auto_methods = match config.auto_methods {
+            Some(ref methods) => {
+                let mut auto_methods = Vec::with_capacity(methods.len());
+                for m in methods {
+                    match parse_method(m) {
+                        Ok(method) => auto_methods.push(method),
+                        Err(..) => {
+                            let err = Error::new(
+                                ErrorKind::Invalid,
+                                "unsupported method in `auto_methods`",
+                                Some(format!("`{m}` is not a supported method")),
+                            );
+                            return Err(err);
+                        }
+                    }
+                }
+                auto_methods
+            }
+            None => DEFAULT_AUTO_METHODS
+                .iter()
+                .filter_map(|m| m.parse::<CipherKind>().ok())
+                .collect(),
+        };
+
         // Client
         //
         // local_address is allowed to be NULL, which means to bind to ::1 or 127.0.0.1
+        // local_port is allowed to be NULL if local_address is set, which means to bind to DEFAULT_LOCAL_PORT
         //
         // https://shadowsocks.org/en/config/quick-guide.html
         #[inline]
@@ -1557,8 +2259,16 @@ impl Config {
             }
         }
 
+        // Default local address is IPv6 loopback only if IPv6 is preferred and not disabled
+        let local_ipv6_first = config.ipv6_first.unwrap_or(false) && !config.disable_ipv6.unwrap_or(false);
+
         // Mode
-        let mut global_mode = Mode::TcpOnly;
+        //
+        // `mode` always wins, `enable_udp` is only a shorthand of `tcp_and_udp` (true) or `tcp_only` (false)
+        let mut global_mode = match config.enable_udp {
+            Some(true) => Mode::TcpAndUdp,
+            Some(false) | None => Mode::TcpOnly,
+        };
         if let Some(m) = config.mode {
             match m.parse::<Mode>() {
                 Ok(xm) => global_mode = xm,
@@ -1576,25 +2286,25 @@ impl Config {
         match config_type {
             ConfigType::Local => {
                 // Standard config
-                if config.local_address.is_some() && config.local_port.unwrap_or(0) == 0 {
-                    let err = Error::new(ErrorKind::MissingField, "missing `local_port`", None);
-                    return Err(err);
-                }
-
-                if let Some(local_port) = config.local_port {
-                    // local_port won't be 0, it was checked above
-                    assert_ne!(local_port, 0);
+                //
+                // `local_port` 0 binds a port assigned by the system, see `local::Server::bound_local_addr`
+                let local_port = match config.local_port {
+                    Some(port) => Some(port),
+                    None if config.local_address.is_some() => Some(DEFAULT_LOCAL_PORT),
+                    None => None,
+                };
 
-                    let local_addr =
-                        get_local_address(config.local_address, local_port, config.ipv6_first.unwrap_or(false));
+                // Multiple `local_address` binds the same `local_port`
+                let local_addresses = match config.local_address {
+                    Some(addr) => addr.into_addresses()?.into_iter().map(Some).collect(),
+                    None => vec![None],
+                };
 
+                if let Some(local_port) = local_port {
                     // shadowsocks uses SOCKS5 by default
-                    let mut local_config = LocalConfig::new(ProtocolType::Socks);
-                    local_config.addr = Some(local_addr);
-                    local_config.mode = global_mode;
-                    local_config.protocol = match config.protocol {
+                    let protocol = match config.protocol {
                         None => ProtocolType::Socks,
-                        Some(p) => match p.parse::<ProtocolType>() {
+                        Some(ref p) => match p.parse::<ProtocolType>() {
                             Ok(p) => p,
                             Err(..) => {
                                 let err = Error::new(
@@ -1606,22 +2316,38 @@ impl Config {
                             }
                         },
                     };
-                    #[cfg(target_os = "macos")]
-                    {
-                        local_config
-                            .launchd_tcp_socket_name
-                            .clone_from(&config.launchd_tcp_socket_name);
-                        local_config
-                            .launchd_udp_socket_name
-                            .clone_from(&config.launchd_udp_socket_name);
-                    }
 
-                    let local_instance = LocalInstanceConfig {
-                        config: local_config,
-                        acl: None,
-                    };
+                    for local_address in local_addresses {
+                        let mut local_config = LocalConfig::new(protocol);
+
+                        // `unix:/path/to/socket` listens on a Unix Domain Socket
+                        match local_address.as_deref().and_then(|a| a.strip_prefix("unix:")) {
+                            Some(path) => set_local_unix_addr(&mut local_config, path, config.local_port)?,
+                            None => {
+                                let local_addr = get_local_address(local_address, local_port, local_ipv6_first);
+                                local_config.addr = Some(local_addr);
+                            }
+                        }
+
+                        local_config.mode = global_mode;
+                        #[cfg(target_os = "macos")]
+                        {
+                            local_config
+                                .launchd_tcp_socket_name
+                                .clone_from(&config.launchd_tcp_socket_name);
+                            local_config
+                                .launchd_udp_socket_name
+                                .clone_from(&config.launchd_udp_socket_name);
+                        }
+
+                        let local_instance = LocalInstanceConfig {
+                            config: local_config,
+                            acl: None,
+                            servers: None,
+                        };
 
-                    nconfig.local.push(local_instance);
+                        nconfig.local.push(local_instance);
+                    }
                 }
 
                 // Ext locals
@@ -1649,31 +2375,26 @@ impl Config {
 
                         let mut local_config = LocalConfig::new(protocol);
 
-                        if let Some(local_port) = local.local_port {
-                            if local_port == 0 {
-                                let err = Error::new(ErrorKind::Malformed, "`local_port` cannot be 0", None);
-                                return Err(err);
-                            }
+                        // `unix:/path/to/socket` listens on a Unix Domain Socket
+                        let unix_addr = local.local_address.as_deref().and_then(|a| a.strip_prefix("unix:"));
+                        if let Some(path) = unix_addr {
+                            set_local_unix_addr(&mut local_config, path, local.local_port)?;
+                        }
 
-                            let local_addr =
-                                get_local_address(local.local_address, local_port, config.ipv6_first.unwrap_or(false));
+                        let local_port = match local.local_port {
+                            Some(port) => Some(port),
+                            None if local.local_address.is_some() && unix_addr.is_none() => Some(DEFAULT_LOCAL_PORT),
+                            None => None,
+                        };
+
+                        if let Some(local_port) = local_port {
+                            let local_addr = get_local_address(local.local_address, local_port, local_ipv6_first);
                             local_config.addr = Some(local_addr);
-                        } else if local.local_address.is_some() {
-                            let err = Error::new(ErrorKind::Malformed, "missing `local_port`", None);
-                            return Err(err);
                         }
 
                         if let Some(local_udp_port) = local.local_udp_port {
-                            if local_udp_port == 0 {
-                                let err = Error::new(ErrorKind::Malformed, "`local_udp_port` cannot be 0", None);
-                                return Err(err);
-                            }
-
-                            let local_udp_addr = get_local_address(
-                                local.local_udp_address,
-                                local_udp_port,
-                                config.ipv6_first.unwrap_or(false),
-                            );
+                            let local_udp_addr =
+                                get_local_address(local.local_udp_address, local_udp_port, local_ipv6_first);
 
                             local_config.udp_addr = Some(local_udp_addr);
                         }
@@ -1724,10 +2445,31 @@ impl Config {
 
                         #[cfg(feature = "local-redir")]
                         if let Some(tcp_redir) = local.tcp_redir {
+                            // Types valid on this platform may not be available for TCP
                             match tcp_redir.parse::<RedirType>() {
-                                Ok(r) => local_config.tcp_redir = r,
+                                Ok(r) if RedirType::tcp_available_types().contains(&r.name()) => {
+                                    local_config.tcp_redir = r
+                                }
+                                Ok(..) => {
+                                    let err = Error::new(
+                                        ErrorKind::Invalid,
+                                        "`tcp_redir` is not available for TCP",
+                                        Some(format!(
+                                            "{tcp_redir}, available types: {:?}",
+                                            RedirType::tcp_available_types()
+                                        )),
+                                    );
+                                    return Err(err);
+                                }
                                 Err(..) => {
-                                    let err = Error::new(ErrorKind::Malformed, "`tcp_redir` invalid", None);
+                                    let err = Error::new(
+                                        ErrorKind::Malformed,
+                                        "`tcp_redir` invalid",
+                                        Some(format!(
+                                            "{tcp_redir}, available types: {:?}",
+                                            RedirType::tcp_available_types()
+                                        )),
+                                    );
                                     return Err(err);
                                 }
                             }
@@ -1735,10 +2477,31 @@ impl Config {
 
                         #[cfg(feature = "local-redir")]
                         if let Some(udp_redir) = local.udp_redir {
+                            // Types valid on this platform may not be available for UDP, like `redirect` for UDP
                             match udp_redir.parse::<RedirType>() {
-                                Ok(r) => local_config.udp_redir = r,
+                                Ok(r) if RedirType::udp_available_types().contains(&r.name()) => {
+                                    local_config.udp_redir = r
+                                }
+                                Ok(..) => {
+                                    let err = Error::new(
+                                        ErrorKind::Invalid,
+                                        "`udp_redir` is not available for UDP",
+                                        Some(format!(
+                                            "{udp_redir}, available types: {:?}",
+                                            RedirType::udp_available_types()
+                                        )),
+                                    );
+                                    return Err(err);
+                                }
                                 Err(..) => {
-                                    let err = Error::new(ErrorKind::Malformed, "`udp_redir` invalid", None);
+                                    let err = Error::new(
+                                        ErrorKind::Malformed,
+                                        "`udp_redir` invalid",
+                                        Some(format!(
+                                            "{udp_redir}, available types: {:?}",
+                                            RedirType::udp_available_types()
+                                        )),
+                                    );
                                     return Err(err);
                                 }
                             }
@@ -1818,6 +2581,48 @@ impl Config {
                             local_config.socks5_auth = Socks5AuthConfig::load_from_file(&socks5_auth_config_path)?;
                         }
 
+                        #[cfg(feature = "local")]
+                        if let Some(client_acl) = local.local_client_acl {
+                            let parse_nets = |key: &str, nets: Option<Vec<String>>| -> Result<Vec<IpNet>> {
+                                let mut parsed = Vec::new();
+                                for net in nets.unwrap_or_default() {
+                                    match parse_ip_net(&net) {
+                                        Some(n) => parsed.push(n),
+                                        None => {
+                                            let err = Error::new(
+                                                ErrorKind::Malformed,
+                                                "invalid `local_client_acl`, should be a list of IPs or CIDRs",
+                                                Some(format!("{key}: {net}")),
+                                            );
+                                            return Err(err);
+                                        }
+                                    }
+                                }
+                                Ok(parsed)
+                            };
+
+                            local_config.client_acl = Some(LocalClientAclConfig {
+                                allow: parse_nets("allow", client_acl.allow)?,
+                                deny: parse_nets("deny", client_acl.deny)?,
+                            });
+                        }
+
+                        #[cfg(feature = "local-http")]
+                        match (local.local_http_username, local.local_http_password) {
+                            (Some(username), Some(password)) => {
+                                local_config.http_proxy_auth = Some((username, password));
+                            }
+                            (None, None) => {}
+                            _ => {
+                                let err = Error::new(
+                                    ErrorKind::MissingField,
+                                    "`local_http_username` and `local_http_password` must be set together",
+                                    None,
+                                );
+                                return Err(err);
+                            }
+                        }
+
                         #[cfg(feature = "local-fake-dns")]
                         {
                             if let Some(d) = local.fake_dns_record_expire_duration {
@@ -1848,9 +2653,13 @@ impl Config {
                             }
                         }
 
+                        // Fail early instead of failing while binding local servers
+                        local_config.check_integrity()?;
+
                         let mut local_instance = LocalInstanceConfig {
                             config: local_config,
                             acl: None,
+                            servers: local.servers,
                         };
 
                         if let Some(acl_path) = local.acl {
@@ -1858,7 +2667,7 @@ impl Config {
                                 Ok(acl) => acl,
                                 Err(err) => {
                                     let err = Error::new(
-                                        ErrorKind::Invalid,
+                                        ErrorKind::IoError,
                                         "acl loading failed",
                                         Some(format!("file {acl_path}, error: {err}")),
                                     );
@@ -1905,10 +2714,32 @@ impl Config {
             ConfigType::OnlineConfig => ServerSource::OnlineConfig,
         };
 
+        // Global timeouts of servers, `timeout`, `connect_timeout` and `idle_timeout`
+        let global_timeouts = [config.timeout, config.connect_timeout, config.idle_timeout];
+        // Global `max_connections` of servers
+        let global_max_connections = match config.max_connections {
+            Some(n) => Some(check_max_connections(n)?),
+            None => None,
+        };
+        // Global `max_retries` and `retry_backoff` of servers
+        let global_retries = [config.max_retries, config.retry_backoff];
+
         // Standard config
         // Server
-        match (config.server, config.server_port, config.password, &config.method) {
+        let implied_method = match config.server {
+            Some(..) => implied_plugin_method(config.plugin.as_deref()),
+            None => None,
+        };
+        match (
+            config.server,
+            config.server_port,
+            config.password.clone(),
+            config.method.as_deref().or(implied_method),
+        ) {
             (Some(address), Some(port), pwd_opt, Some(m)) => {
+                let ports = port.to_ports()?;
+                let port = ports[0];
+
                 let addr = match address.parse::<Ipv4Addr>() {
                     Ok(v4) => ServerAddr::SocketAddr(SocketAddr::V4(SocketAddrV4::new(v4, port))),
                     Err(..) => match address.parse::<Ipv6Addr>() {
@@ -1917,17 +2748,12 @@ impl Config {
                     },
                 };
 
-                let method = match m.parse::<CipherKind>() {
-                    Ok(m) => m,
-                    Err(..) => {
-                        let err = Error::new(
-                            ErrorKind::Invalid,
-                            "unsupported method",
-                            Some(format!("`{m}` is not a supported method")),
-                        );
-                        return Err(err);
-                    }
-                };
+                let method = parse_server_method(
+                    m,
+                    pwd_opt.as_deref().map(read_variable_field_value).as_deref(),
+                    config_type,
+                    &auto_methods,
+                )?;
 
                 // Only "password" support getting from environment variable.
                 let password = match pwd_opt {
@@ -1949,8 +2775,9 @@ impl Config {
                 let mut nsvr = match ServerConfig::new(addr, password, method) {
                     Ok(svr) => svr,
                     Err(serr) => {
+                        // Errors are all about keys, for example, AEAD-2022 keys with wrong length
                         let err = Error::new(
-                            ErrorKind::Malformed,
+                            ErrorKind::Invalid,
                             "server config create failed",
                             Some(format!("{}", serr)),
                         );
@@ -1987,10 +2814,22 @@ impl Config {
                     }
                 }
 
-                if let Some(timeout) = config.timeout.map(Duration::from_secs) {
-                    nsvr.set_timeout(timeout);
+                set_server_timeouts(&mut nsvr, [None; 3], global_timeouts);
+                if let Some(n) = global_max_connections {
+                    nsvr.set_max_connections(n);
+                }
+                set_server_retries(&mut nsvr, [None; 2], global_retries)?;
+
+                if let Some(ref m) = config.fallback_method {
+                    set_server_fallback_method(&mut nsvr, m)?;
+                }
+
+                if let Some(ref kdf) = config.kdf {
+                    set_server_kdf(&mut nsvr, kdf)?;
                 }
 
+                set_server_validity(&mut nsvr, config.valid_from.as_deref(), config.valid_until.as_deref())?;
+
                 let mut outbound_bind_addr: Option<IpAddr> = None;
 
                 if let Some(ref bind_addr) = config.outbound_bind_addr {
@@ -2007,17 +2846,21 @@ impl Config {
                     config: nsvr,
                     acl: None,
                     #[cfg(any(target_os = "linux", target_os = "android"))]
-                    outbound_fwmark: config.outbound_fwmark,
+                    outbound_fwmark,
                     outbound_bind_addr,
                     outbound_bind_interface: config.outbound_bind_interface.clone(),
                     outbound_udp_allow_fragmentation: config.outbound_udp_allow_fragmentation,
+                    outbound_proxy: None,
                 };
 
-                nconfig.server.push(server_instance);
+                push_server_instance_ports(&mut nconfig.server, server_instance, &ports);
             }
             (None, None, None, Some(_)) if config_type.is_manager() => {
                 // Set the default method for manager
             }
+            (None, None, ..) if config.servers.is_some() => {
+                // Default `method` and `password` for `servers`
+            }
             (None, None, None, None) => (),
             _ => {
                 let err = Error::new(
@@ -2030,264 +2873,96 @@ impl Config {
         }
 
         // Ext servers
+        //
+        // Root-level `method`, `password` and `timeout` are defaults of entries that omit them
         if let Some(servers) = config.servers {
-            for svr in servers {
-                // Skip if server is disabled
-                if svr.disabled.unwrap_or(false) {
-                    continue;
-                }
+            let mut outbound_bind_addr: Option<IpAddr> = None;
 
-                let address = svr.server;
-                let port = svr.server_port;
-
-                let addr = match address.parse::<Ipv4Addr>() {
-                    Ok(v4) => ServerAddr::SocketAddr(SocketAddr::V4(SocketAddrV4::new(v4, port))),
-                    Err(..) => match address.parse::<Ipv6Addr>() {
-                        Ok(v6) => ServerAddr::SocketAddr(SocketAddr::V6(SocketAddrV6::new(v6, port, 0, 0))),
-                        Err(..) => ServerAddr::DomainName(address, port),
-                    },
-                };
-
-                let method = match svr.method.parse::<CipherKind>() {
-                    Ok(m) => m,
+            if let Some(ref bind_addr) = config.outbound_bind_addr {
+                match bind_addr.parse::<IpAddr>() {
+                    Ok(b) => outbound_bind_addr = Some(b),
                     Err(..) => {
-                        let err = Error::new(
-                            ErrorKind::Invalid,
-                            "unsupported method",
-                            Some(format!("`{}` is not a supported method", svr.method)),
-                        );
-                        return Err(err);
-                    }
-                };
-
-                // Only "password" support getting from environment variable.
-                let password = match svr.password {
-                    Some(ref pwd) => read_variable_field_value(pwd),
-                    None => {
-                        if method.is_none() {
-                            String::new().into()
-                        } else {
-                            let err = Error::new(
-                                ErrorKind::MissingField,
-                                "`password` is required",
-                                Some(format!("`password` is required for method {method}")),
-                            );
-                            return Err(err);
-                        }
-                    }
-                };
-
-                let mut nsvr = match ServerConfig::new(addr, password, method) {
-                    Ok(svr) => svr,
-                    Err(serr) => {
-                        let err = Error::new(
-                            ErrorKind::Malformed,
-                            "server config create failed",
-                            Some(format!("{}", serr)),
-                        );
+                        let err = Error::new(ErrorKind::Invalid, "invalid outbound_bind_addr", None);
                         return Err(err);
                     }
-                };
-                nsvr.set_source(server_source);
-
-                // Extensible Identity Header, Users
-                if let Some(users) = svr.users {
-                    let mut user_manager = ServerUserManager::new();
+                }
+            }
 
-                    for user in users {
-                        let user = match ServerUser::with_encoded_key(user.name, &user.password) {
-                            Ok(u) => u,
-                            Err(..) => {
-                                let err = Error::new(
-                                    ErrorKind::Malformed,
-                                    "`users[].password` should be base64 encoded",
-                                    None,
-                                );
-                                return Err(err);
-                            }
-                        };
+            let defaults = ServerEntryDefaults {
+                config_type,
+                server_source,
+                global_method: config.method.as_deref(),
+                global_password: config.password.as_ref(),
+                global_fallback_method: config.fallback_method.as_ref(),
+                global_kdf: config.kdf.as_ref(),
+                global_valid_from: config.valid_from.as_deref(),
+                global_valid_until: config.valid_until.as_deref(),
+                global_timeouts,
+                global_max_connections,
+                global_retries,
+                global_mode,
+                auto_methods: &auto_methods,
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                outbound_fwmark,
+                outbound_bind_addr,
+                outbound_bind_interface: config.outbound_bind_interface.as_ref(),
+                outbound_udp_allow_fragmentation: config.outbound_udp_allow_fragmentation,
+            };
 
-                        user_manager.add_user(user);
-                    }
+            for (idx, svr) in servers.into_iter().enumerate() {
+                // Errors are tagged with index of the entry, for locating it quickly in large `servers`
+                load_server_entry(svr, &defaults, &mut nconfig.server).map_err(|mut err| {
+                    err.detail = Some(match err.detail {
+                        Some(detail) => format!("servers[{idx}]: {detail}"),
+                        None => format!("servers[{idx}]"),
+                    });
+                    err
+                })?;
+            }
+        }
 
-                    nsvr.set_user_manager(user_manager);
+        // Manager Address
+        if let Some(ma) = config.manager_address {
+            let manager = match config.manager_port {
+                Some(..) if ma.trim().is_empty() => {
+                    let e = Error::new(ErrorKind::Invalid, "`manager_address` is empty", None);
+                    return Err(e);
                 }
-
-                match svr.mode {
-                    Some(mode) => match mode.parse::<Mode>() {
-                        Ok(mode) => nsvr.set_mode(mode),
+                Some(port) => {
+                    match ma.parse::<IpAddr>() {
+                        Ok(ip) => ManagerAddr::from(SocketAddr::new(ip, port)),
                         Err(..) => {
-                            let err = Error::new(ErrorKind::Invalid, "invalid `mode`", None);
-                            return Err(err);
-                        }
-                    },
-                    None => {
-                        // Server will derive mode from the global scope
-                        if matches!(config_type, ConfigType::Server | ConfigType::Manager) {
-                            nsvr.set_mode(global_mode);
+                            // treated as domain
+                            ManagerAddr::from((ma, port))
                         }
                     }
                 }
-
-                if let Some(p) = svr.plugin {
-                    // SIP008 allows "plugin" to be an empty string
-                    // Empty string implies "no plugin"
-                    if !p.is_empty() {
-                        let plugin = PluginConfig {
-                            plugin: p,
-                            plugin_opts: svr.plugin_opts,
-                            plugin_args: svr.plugin_args.unwrap_or_default(),
-                            plugin_mode: match svr.plugin_mode {
-                                None => Mode::TcpOnly,
-                                Some(ref mode) => match mode.parse::<Mode>() {
-                                    Ok(m) => m,
-                                    Err(..) => {
-                                        let e = Error::new(
-                                            ErrorKind::Malformed,
-                                            "malformed `plugin_mode`, must be one of `tcp_only`, `udp_only` and `tcp_and_udp`",
-                                            None,
-                                        );
-                                        return Err(e);
-                                    }
-                                },
-                            },
-                        };
-                        nsvr.set_plugin(plugin);
+                // "host:port", or UNIX socket path
+                None => match ma.parse::<ManagerAddr>() {
+                    Ok(addr) => addr,
+                    Err(..) => {
+                        #[cfg(unix)]
+                        let desc = "invalid `manager_address`, should be \"host:port\" or a UNIX socket path if `manager_port` is not set";
+                        #[cfg(not(unix))]
+                        let desc = "invalid `manager_address`, should be \"host:port\" if `manager_port` is not set";
+                        let e = Error::new(ErrorKind::Invalid, desc, Some(ma));
+                        return Err(e);
                     }
-                }
-
-                if let Some(timeout) = config.timeout.map(Duration::from_secs) {
-                    nsvr.set_timeout(timeout);
-                }
-
-                if let Some(remarks) = svr.remarks {
-                    nsvr.set_remarks(remarks);
-                }
+                },
+            };
 
-                if let Some(id) = svr.id {
-                    nsvr.set_id(id);
-                }
+            let mut manager_config = ManagerConfig::new(manager);
+            manager_config.mode = global_mode;
 
-                if svr.tcp_weight.is_some() || svr.udp_weight.is_some() {
-                    let tcp_weight = svr.tcp_weight.unwrap_or(1.0);
-                    if !(0.0..=1.0).contains(&tcp_weight) {
-                        let err = Error::new(ErrorKind::Invalid, "invalid `tcp_weight`, must be in [0, 1]", None);
-                        return Err(err);
-                    }
-                    let udp_weight = svr.udp_weight.unwrap_or(1.0);
-                    if !(0.0..=1.0).contains(&udp_weight) {
-                        let err = Error::new(ErrorKind::Invalid, "invalid `udp_weight`, must be in [0, 1]", None);
-                        return Err(err);
-                    }
-                    let mut weight = ServerWeight::new();
-                    weight.set_tcp_weight(tcp_weight);
-                    weight.set_udp_weight(udp_weight);
-                    nsvr.set_weight(weight);
-                }
-
-                let mut outbound_bind_addr: Option<IpAddr> = None;
-
-                if let Some(ref bind_addr) = config.outbound_bind_addr {
-                    match bind_addr.parse::<IpAddr>() {
-                        Ok(b) => outbound_bind_addr = Some(b),
-                        Err(..) => {
-                            let err = Error::new(ErrorKind::Invalid, "invalid outbound_bind_addr", None);
-                            return Err(err);
-                        }
-                    }
-                }
-
-                let mut server_instance = ServerInstanceConfig {
-                    config: nsvr,
-                    acl: None,
-                    #[cfg(any(target_os = "linux", target_os = "android"))]
-                    outbound_fwmark: config.outbound_fwmark,
-                    outbound_bind_addr,
-                    outbound_bind_interface: config.outbound_bind_interface.clone(),
-                    outbound_udp_allow_fragmentation: config.outbound_udp_allow_fragmentation,
-                };
-
-                if let Some(acl_path) = svr.acl {
-                    let acl = match AccessControl::load_from_file(&acl_path) {
-                        Ok(acl) => acl,
-                        Err(err) => {
-                            let err = Error::new(
-                                ErrorKind::Invalid,
-                                "acl loading failed",
-                                Some(format!("file {acl_path}, error: {err}")),
-                            );
-                            return Err(err);
-                        }
-                    };
-                    server_instance.acl = Some(acl);
-                }
-
-                #[cfg(any(target_os = "linux", target_os = "android"))]
-                if let Some(outbound_fwmark) = svr.outbound_fwmark {
-                    server_instance.outbound_fwmark = Some(outbound_fwmark);
-                }
-
-                if let Some(outbound_bind_addr) = svr.outbound_bind_addr {
-                    server_instance.outbound_bind_addr = Some(outbound_bind_addr);
-                }
-
-                if let Some(ref outbound_bind_interface) = svr.outbound_bind_interface {
-                    server_instance.outbound_bind_interface = Some(outbound_bind_interface.clone());
-                }
-
-                if let Some(outbound_udp_allow_fragmentation) = svr.outbound_udp_allow_fragmentation {
-                    server_instance.outbound_udp_allow_fragmentation = Some(outbound_udp_allow_fragmentation);
-                }
-
-                nconfig.server.push(server_instance);
-            }
-        }
-
-        // Set timeout globally
-        if let Some(timeout) = config.timeout {
-            let timeout = Duration::from_secs(timeout);
-            // Set as a default timeout
-            for inst in &mut nconfig.server {
-                let svr = &mut inst.config;
-                if svr.timeout().is_none() {
-                    svr.set_timeout(timeout);
-                }
-            }
-        }
-
-        // Manager Address
-        if let Some(ma) = config.manager_address {
-            let manager = match config.manager_port {
-                Some(port) => {
-                    match ma.parse::<IpAddr>() {
-                        Ok(ip) => ManagerAddr::from(SocketAddr::new(ip, port)),
-                        Err(..) => {
-                            // treated as domain
-                            ManagerAddr::from((ma, port))
-                        }
-                    }
-                }
-                #[cfg(unix)]
-                None => ManagerAddr::from(PathBuf::from(ma)),
-                #[cfg(not(unix))]
-                None => {
-                    let e = Error::new(ErrorKind::MissingField, "missing `manager_port`", None);
-                    return Err(e);
-                }
-            };
-
-            let mut manager_config = ManagerConfig::new(manager);
-            manager_config.mode = global_mode;
-
-            if let Some(ref m) = config.method {
-                match m.parse::<CipherKind>() {
-                    Ok(method) => manager_config.method = Some(method),
-                    Err(..) => {
-                        let err = Error::new(
-                            ErrorKind::Invalid,
-                            "unsupported method",
-                            Some(format!("`{m}` is not a supported method")),
-                        );
+            if let Some(ref m) = config.method {
+                match parse_method(m) {
+                    Ok(method) => manager_config.method = Some(method),
+                    Err(..) => {
+                        let err = Error::new(
+                            ErrorKind::Invalid,
+                            "unsupported method",
+                            Some(format!("`{m}` is not a supported method")),
+                        );
                         return Err(err);
                     }
                 }
@@ -2326,11 +3001,29 @@ impl Config {
         {
             match config.dns {
                 Some(SSDnsConfig::Simple(ds)) => nconfig.set_dns_formatted(&ds)?,
+                Some(SSDnsConfig::List(ds)) => nconfig.set_dns_formatted_list(&ds)?,
                 #[cfg(feature = "hickory-dns")]
                 Some(SSDnsConfig::HickoryDns(c)) => nconfig.dns = DnsConfig::HickoryDns(c),
                 None => nconfig.dns = DnsConfig::System,
             }
-            nconfig.dns_cache_size = config.dns_cache_size;
+            nconfig.dns_cache_size = config.dns_cache_size.map(|size| {
+                if size > MAX_DNS_CACHE_SIZE {
+                    warn!(
+                        "dns_cache_size {} is too large, clamped to {}",
+                        size, MAX_DNS_CACHE_SIZE
+                    );
+                    MAX_DNS_CACHE_SIZE
+                } else {
+                    size
+                }
+            });
+            // `"dns_cache": false` disables DNS cache, even if `dns_cache_size` is set
+            if config.dns_cache == Some(false) {
+                if let Some(size) = nconfig.dns_cache_size.filter(|size| *size > 0) {
+                    warn!("dns_cache_size {} is ignored because dns_cache is disabled", size);
+                }
+                nconfig.dns_cache_size = Some(0);
+            }
         }
 
         // TCP nodelay
@@ -2353,6 +3046,9 @@ impl Config {
             nconfig.mptcp = b;
         }
 
+        // Graceful shutdown
+        nconfig.shutdown_timeout = config.shutdown_timeout.map(Duration::from_secs);
+
         // UDP
         nconfig.udp_timeout = config.udp_timeout.map(Duration::from_secs);
 
@@ -2362,6 +3058,55 @@ impl Config {
         // MTU for UDP
         nconfig.udp_mtu = config.udp_mtu;
 
+        // Receive buffer size of UDP relays
+        if let Some(size) = config.udp_max_payload {
+            if !UDP_MAX_PAYLOAD_RANGE.contains(&size) {
+                let err = Error::new(
+                    ErrorKind::Invalid,
+                    "`udp_max_payload` is out of range",
+                    Some(format!(
+                        "{} is not in [{}, {}]",
+                        size,
+                        UDP_MAX_PAYLOAD_RANGE.start(),
+                        UDP_MAX_PAYLOAD_RANGE.end()
+                    )),
+                );
+                return Err(err);
+            }
+            nconfig.udp_max_payload = Some(size);
+        }
+
+        // Advertised UDP ASSOCIATE address
+        if let Some(udp_bind_address) = config.udp_bind_address {
+            match udp_bind_address.parse::<SocketAddr>() {
+                Ok(addr) => nconfig.udp_bind_addr = Some(addr),
+                Err(..) => {
+                    let err = Error::new(ErrorKind::Invalid, "invalid udp_bind_address", None);
+                    return Err(err);
+                }
+            }
+        }
+
+        // Rate limit
+        if let Some(limit_rate) = config.limit_rate {
+            let rate = match limit_rate {
+                SSRateLimitConfig::BytesPerSec(r) => Some(r),
+                SSRateLimitConfig::Rate(ref r) => parse_rate_limit(r),
+            };
+
+            match rate {
+                Some(r) if r > 0 => nconfig.max_rate_bytes_per_sec = Some(r),
+                _ => {
+                    let err = Error::new(
+                        ErrorKind::Invalid,
+                        "invalid `limit_rate`, should be a positive number of bytes per second, or with units like `10mbit` and `1MB`",
+                        Some(format!("{limit_rate:?}")),
+                    );
+                    return Err(err);
+                }
+            }
+        }
+
         // RLIMIT_NOFILE
         #[cfg(all(unix, not(target_os = "android")))]
         {
@@ -2373,15 +3118,30 @@ impl Config {
             nconfig.ipv6_first = f;
         }
 
+        // Disables IPv6
+        if let Some(d) = config.disable_ipv6 {
+            nconfig.disable_ipv6 = d;
+        }
+
         // IPV6_V6ONLY
         if let Some(o) = config.ipv6_only {
             nconfig.ipv6_only = o;
         }
 
+        // SO_REUSEPORT
+        if let Some(o) = config.reuse_port {
+            nconfig.reuse_port = o;
+        }
+
+        // SO_REUSEADDR
+        if let Some(o) = config.reuse_address {
+            nconfig.reuse_address = o;
+        }
+
         // SO_MARK
         #[cfg(any(target_os = "linux", target_os = "android"))]
-        if let Some(fwmark) = config.outbound_fwmark {
-            nconfig.outbound_fwmark = Some(fwmark);
+        {
+            nconfig.outbound_fwmark = outbound_fwmark;
         }
 
         // SO_USER_COOKIE
@@ -2402,12 +3162,164 @@ impl Config {
         }
 
         // Bind device / interface
+        if let Some(ref iface) = config.outbound_bind_interface {
+            check_outbound_bind_interface(iface)?;
+        }
         nconfig.outbound_bind_interface = config.outbound_bind_interface;
 
+        // IP_TTL / IPV6_UNICAST_HOPS
+        if let Some(ttl) = config.outbound_ttl {
+            match u8::try_from(ttl) {
+                Ok(ttl) if ttl > 0 => nconfig.outbound_ttl = Some(u32::from(ttl)),
+                _ => {
+                    let err = Error::new(
+                        ErrorKind::Invalid,
+                        "invalid `outbound_ttl`, must be in [1, 255]",
+                        Some(ttl.to_string()),
+                    );
+                    return Err(err);
+                }
+            }
+        }
+
         if let Some(b) = config.outbound_udp_allow_fragmentation {
             nconfig.outbound_udp_allow_fragmentation = b;
         }
 
+        // Outbound SOCKS5 proxy
+        if let Some(outbound_socks5) = config.outbound_socks5 {
+            nconfig.outbound_proxy = Some(parse_outbound_socks5(outbound_socks5)?);
+        }
+
+        // Forbidden IPs
+        if let Some(forbidden_ip) = config.forbidden_ip {
+            for ip in forbidden_ip {
+                match parse_ip_net(&ip) {
+                    Some(net) => nconfig.forbidden_ip.push(net),
+                    None => {
+                        let err = Error::new(
+                            ErrorKind::Malformed,
+                            "invalid `forbidden_ip`, should be an IP or CIDR",
+                            Some(ip),
+                        );
+                        return Err(err);
+                    }
+                }
+            }
+        }
+
+        if let Some(forbidden_ip_file) = config.forbidden_ip_file {
+            let file = match File::open(&forbidden_ip_file) {
+                Ok(f) => f,
+                Err(err) => {
+                    let err = Error::new(
+                        ErrorKind::IoError,
+                        "failed to open `forbidden_ip_file`",
+                        Some(format!("file {forbidden_ip_file}, error: {err}")),
+                    );
+                    return Err(err);
+                }
+            };
+
+            for (idx, line) in BufReader::new(file).lines().enumerate() {
+                let line = match line {
+                    Ok(l) => l,
+                    Err(err) => {
+                        let err = Error::new(
+                            ErrorKind::IoError,
+                            "failed to read `forbidden_ip_file`",
+                            Some(format!("file {forbidden_ip_file}, error: {err}")),
+                        );
+                        return Err(err);
+                    }
+                };
+
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+
+                match parse_ip_net(line) {
+                    Some(net) => nconfig.forbidden_ip.push(net),
+                    None => {
+                        let err = Error::new(
+                            ErrorKind::Malformed,
+                            "invalid IP or CIDR in `forbidden_ip_file`",
+                            Some(format!("file {forbidden_ip_file}, line {}: {line}", idx + 1)),
+                        );
+                        return Err(err);
+                    }
+                }
+            }
+        }
+
+        if let Some(forbidden_domain_regex) = config.forbidden_domain_regex {
+            for pattern in forbidden_domain_regex {
+                match Regex::new(&pattern) {
+                    Ok(re) => nconfig.forbidden_domain_regex.push(re),
+                    Err(err) => {
+                        let err = Error::new(
+                            ErrorKind::Malformed,
+                            "invalid regular expression in `forbidden_domain_regex`",
+                            Some(format!("pattern {pattern}, error: {err}")),
+                        );
+                        return Err(err);
+                    }
+                }
+            }
+        }
+
+        if let Some(allowed_ports) = config.udp_allowed_ports {
+            if allowed_ports.is_empty() {
+                let err = Error::new(
+                    ErrorKind::Invalid,
+                    "`udp_allowed_ports` shouldn't be empty",
+                    Some("remove it to allow all ports".to_owned()),
+                );
+                return Err(err);
+            }
+
+            // Out of range integers are saturated by json5, check them here
+            let mut ports = Vec::with_capacity(allowed_ports.len());
+            for port in allowed_ports {
+                match u16::try_from(port) {
+                    Ok(p) if p != 0 => ports.push(p),
+                    _ => {
+                        let err = Error::new(
+                            ErrorKind::Invalid,
+                            "invalid port in `udp_allowed_ports`, must be in [1, 65535]",
+                            Some(port.to_string()),
+                        );
+                        return Err(err);
+                    }
+                }
+            }
+
+            ports.sort_unstable();
+            ports.dedup();
+            nconfig.udp_allowed_ports = Some(ports);
+        }
+
+        // Routing rules
+        for (key, ips, nets) in [
+            ("bypass_ip", config.bypass_ip, &mut nconfig.bypass_ip),
+            ("proxy_ip", config.proxy_ip, &mut nconfig.proxy_ip),
+        ] {
+            for ip in ips.unwrap_or_default() {
+                match parse_ip_net(&ip) {
+                    Some(net) => nets.push(net),
+                    None => {
+                        let err = Error::new(
+                            ErrorKind::Malformed,
+                            "invalid IP or CIDR in routing rules",
+                            Some(format!("`{key}`: {ip}")),
+                        );
+                        return Err(err);
+                    }
+                }
+            }
+        }
+
         // Security
         if let Some(sec) = config.security {
             if let Some(replay_attack) = sec.replay_attack {
@@ -2423,6 +3335,32 @@ impl Config {
             }
         }
 
+        if let Some(level) = config.log_level {
+            match level.parse::<log::LevelFilter>() {
+                Ok(l) => nconfig.log_level = Some(l.as_str().to_ascii_lowercase()),
+                Err(..) => {
+                    let err = Error::new(
+                        ErrorKind::Invalid,
+                        "invalid `log_level`, must be one of `off`, `error`, `warn`, `info`, `debug` and `trace`",
+                        Some(level),
+                    );
+                    return Err(err);
+                }
+            }
+        }
+
+        if let Some(log_file) = config.log_file {
+            if log_file.is_empty() {
+                let err = Error::new(ErrorKind::Invalid, "`log_file` shouldn't be an empty string", None);
+                return Err(err);
+            }
+            nconfig.log_file = Some(log_file);
+        }
+
+        if let Some(access_log) = config.access_log {
+            nconfig.access_log = access_log;
+        }
+
         if let Some(balancer) = config.balancer {
             nconfig.balancer = BalancerConfig {
                 max_server_rtt: balancer.max_server_rtt.map(Duration::from_secs),
@@ -2431,12 +3369,47 @@ impl Config {
             };
         }
 
+        if let Some(health_check) = config.health_check {
+            let target = match health_check.target {
+                None => None,
+                Some(target) => match target.parse::<HealthCheckTarget>() {
+                    Ok(t) => Some(t),
+                    Err(..) => {
+                        let err = Error::new(
+                            ErrorKind::Malformed,
+                            "malformed `health_check.target`, must be `http://host[:port][/path]` or `host:port`",
+                            Some(target),
+                        );
+                        return Err(err);
+                    }
+                },
+            };
+
+            nconfig.health_check = Some(HealthCheckConfig {
+                interval: health_check.interval.map(Duration::from_secs),
+                timeout: health_check.timeout.map(Duration::from_secs),
+                target,
+            });
+        }
+
+        if let Some(idx) = config.preferred_server {
+            if idx >= nconfig.server.len() {
+                let err = Error::new(
+                    ErrorKind::Invalid,
+                    "`preferred_server` is out of range",
+                    Some(format!("index {idx}, but there are {} servers", nconfig.server.len())),
+                );
+                return Err(err);
+            }
+            nconfig.preferred_server = Some(idx);
+        }
+
         if let Some(acl_path) = config.acl {
             let acl = match AccessControl::load_from_file(&acl_path) {
                 Ok(acl) => acl,
                 Err(err) => {
                     let err = Error::new(
-                        ErrorKind::Invalid,
+                        ErrorKind::IoError,
                         "acl loading failed",
                         Some(format!("file {acl_path}, error: {err}")),
                     );
@@ -2461,7 +3434,8 @@ impl Config {
     ///
     /// 1. `[(unix|tcp|udp)://]host[:port][,host[:port]]...`
     /// 2. Pre-defined. Like `google`, `cloudflare`
-    pub fn set_dns_formatted(&mut self, dns: &str) -> Result<(), Error> {
+    pub fn set_dns_formatted(&mut self, dns: &str) -> Result<()> {
+        self.dns_in_order = false;
         self.dns = match dns {
             "system" => DnsConfig::System,
 
@@ -2503,28 +3477,75 @@ impl Config {
         Ok(())
     }
 
-    #[cfg(any(feature = "hickory-dns", feature = "local-dns"))]
-    fn parse_dns_nameservers(&mut self, nameservers: &str) -> Result<DnsConfig, Error> {
-        use hickory_resolver::proto::xfer::Protocol;
-
-        #[cfg(all(unix, feature = "local-dns"))]
-        if let Some(nameservers) = nameservers.strip_prefix("unix://") {
-            // A special DNS server only for shadowsocks-android
-            // It serves like a TCP DNS server but using unix domain sockets
-
-            return Ok(DnsConfig::LocalDns(NameServerAddr::UnixSocketAddr(PathBuf::from(
-                nameservers,
-            ))));
-        }
-
-        enum DnsProtocol {
-            Tcp,
-            Udp,
-            Both,
+    /// Set DNS configuration with a list of resolvers in string format, see [`Config::set_dns_formatted`]
+    ///
+    /// Name servers of all resolvers are tried in the order of the list.
+    pub fn set_dns_formatted_list<S: AsRef<str>>(&mut self, dns_list: &[S]) -> Result<()> {
+        if dns_list.is_empty() {
+            let err = Error::new(ErrorKind::Invalid, "`dns` list is empty", None);
+            return Err(err);
         }
 
-        impl DnsProtocol {
-            fn enable_tcp(&self) -> bool {
+        #[cfg(feature = "hickory-dns")]
+        {
+            let mut c = ResolverConfig::new();
+            for dns in dns_list {
+                let dns = dns.as_ref();
+                self.set_dns_formatted(dns)?;
+                match std::mem::take(&mut self.dns) {
+                    DnsConfig::HickoryDns(rc) => {
+                        for ns in rc.name_servers() {
+                            c.add_name_server(ns.clone());
+                        }
+                    }
+                    _ => {
+                        let err = Error::new(
+                            ErrorKind::Invalid,
+                            "`dns` list can only contain name servers",
+                            Some(format!("{dns:?}")),
+                        );
+                        return Err(err);
+                    }
+                }
+            }
+            self.dns = DnsConfig::HickoryDns(c);
+            self.dns_in_order = true;
+            Ok(())
+        }
+
+        #[cfg(not(feature = "hickory-dns"))]
+        {
+            let err = Error::new(
+                ErrorKind::Invalid,
+                "`dns` list requires the `hickory-dns` feature",
+                None,
+            );
+            Err(err)
+        }
+    }
+
+    #[cfg(any(feature = "hickory-dns", feature = "local-dns"))]
+    fn parse_dns_nameservers(&mut self, nameservers: &str) -> Result<DnsConfig, Error> {
+        use hickory_resolver::proto::xfer::Protocol;
+
+        #[cfg(all(unix, feature = "local-dns"))]
+        if let Some(nameservers) = nameservers.strip_prefix("unix://") {
+            // A special DNS server only for shadowsocks-android
+            // It serves like a TCP DNS server but using unix domain sockets
+
+            return Ok(DnsConfig::LocalDns(NameServerAddr::UnixSocketAddr(PathBuf::from(
+                nameservers,
+            ))));
+        }
+
+        enum DnsProtocol {
+            Tcp,
+            Udp,
+            Both,
+        }
+
+        impl DnsProtocol {
+            fn enable_tcp(&self) -> bool {
                 matches!(*self, DnsProtocol::Tcp | DnsProtocol::Both)
             }
 
@@ -2593,27 +3614,173 @@ impl Config {
     }
 
     /// Load Config from a `str`
-    pub fn load_from_str(s: &str, config_type: ConfigType) -> Result<Config, Error> {
+    pub fn load_from_str(s: &str, config_type: ConfigType) -> Result<Config> {
+        check_duplicate_keys(s)?;
         let c = json5::from_str::<SSConfig>(s)?;
         Config::load_from_ssconfig(c, config_type)
     }
 
+    /// Load Config from one of the `profiles` in a `str`
+    ///
+    /// `profiles` is an object that maps profile names to normal configuration objects, for example
+    /// `{"profiles": {"dev": {...}, "prod": {...}}}`.
+    pub fn load_profile_from_str(s: &str, profile_name: &str, config_type: ConfigType) -> Result<Config> {
+        check_duplicate_keys(s)?;
+        let mut root = json5::from_str::<serde_json::Value>(s)?;
+
+        let profiles = match root.get_mut("profiles") {
+            Some(serde_json::Value::Object(profiles)) => profiles,
+            Some(..) => {
+                let err = Error::new(ErrorKind::Malformed, "`profiles` should be an object", None);
+                return Err(err);
+            }
+            None => {
+                let err = Error::new(ErrorKind::MissingField, "missing `profiles` in configuration", None);
+                return Err(err);
+            }
+        };
+
+        let profile = match profiles.remove(profile_name) {
+            Some(p) => p,
+            None => {
+                let err = Error::new(
+                    ErrorKind::Invalid,
+                    "unknown profile",
+                    Some(format!("profile {profile_name} is not in `profiles`")),
+                );
+                return Err(err);
+            }
+        };
+
+        let c = match serde_json::from_value::<SSConfig>(profile) {
+            Ok(c) => c,
+            Err(err) => {
+                let err = Error::new(
+                    ErrorKind::JsonParsingError,
+                    "profile parse error",
+                    Some(format!("profile {profile_name}, error: {err}")),
+                );
+                return Err(err);
+            }
+        };
+        Config::load_from_ssconfig(c, config_type)
+    }
+
     /// Load Config from a File
-    pub fn load_from_file<P: AsRef<Path>>(filename: P, config_type: ConfigType) -> Result<Config, Error> {
-        let filename = filename.as_ref();
+    ///
+    /// A leading `~` or `~user` will be expanded to the home directory, and relative paths are resolved against the current directory.
+    ///
+    /// The format is chosen by the file extension, `.json` (and `.json5`, `.jsonc`) or `.yaml` (`.yml`, requires the
+    /// `config-yaml` feature). Files with other extensions are parsed as JSON if possible, otherwise as YAML.
+    ///
+    /// Files listed in the root `include` array are merged into the configuration, relative paths are resolved against
+    /// the directory of the including file, and `*` or `?` in file names match multiple files. Arrays (like `servers`)
+    /// are concatenated, other keys that are already set are not overridden by the included files.
+    pub fn load_from_file<P: AsRef<Path>>(filename: P, config_type: ConfigType) -> Result<Config> {
+        let filename = match expand_config_path(filename.as_ref()) {
+            Ok(p) => p,
+            Err(err) => {
+                let err = Error::new(
+                    ErrorKind::IoError,
+                    "failed to resolve configuration path",
+                    Some(format!("path {}, error: {err}", filename.as_ref().display())),
+                );
+                return Err(err);
+            }
+        };
 
-        let mut reader = OpenOptions::new().read(true).open(filename)?;
-        let mut content = String::new();
-        reader.read_to_string(&mut content)?;
+        let content = read_config_content(&filename)?;
+        check_duplicate_keys(&content)?;
+        let mut jconf = match json5::from_str::<SSConfig>(&content) {
+            Ok(c) => c,
+            Err(err) => return Err(json5_error_with_offset(err, &content, &filename)),
+        };
+
+        if jconf.include.is_some() {
+            let mut root = match json5::from_str::<serde_json::Value>(&content) {
+                Ok(v) => v,
+                Err(err) => return Err(json5_error_with_offset(err, &content, &filename)),
+            };
+
+            let mut including = vec![fs::canonicalize(&filename)?];
+            merge_config_includes(&mut root, &filename, &mut including)?;
+
+            jconf = match serde_json::from_value::<SSConfig>(root) {
+                Ok(c) => c,
+                Err(err) => {
+                    let err = Error::new(
+                        ErrorKind::JsonParsingError,
+                        "included configuration parse error",
+                        Some(format!("path {}, error: {err}", filename.display())),
+                    );
+                    return Err(err);
+                }
+            };
+        }
 
-        let mut config = Config::load_from_str(&content[..], config_type)?;
+        let mut config = Config::load_from_ssconfig(jconf, config_type)?;
 
         // Record the path of the configuration for auto-reloading
-        config.config_path = Some(filename.to_owned());
+        config.config_path = Some(filename);
 
         Ok(config)
     }
 
+    /// Load servers from a file of `ss://` URLs, one per line
+    ///
+    /// Blank lines and lines starting with `#` are skipped. The whole file could also be base64 encoded, which is the
+    /// format of most subscriptions. All invalid lines are reported in one error with their line numbers.
+    pub fn load_servers_from_url_file<P: AsRef<Path>>(path: P) -> Result<Vec<ServerConfig>> {
+        let path = match expand_config_path(path.as_ref()) {
+            Ok(p) => p,
+            Err(err) => {
+                let err = Error::new(
+                    ErrorKind::IoError,
+                    "failed to resolve server URL file path",
+                    Some(format!("path {}, error: {err}", path.as_ref().display())),
+                );
+                return Err(err);
+            }
+        };
+
+        let content = decode_base64_subscription(read_config_file(&path)?);
+
+        let mut servers = Vec::new();
+        let mut errors = Vec::new();
+        for (idx, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match ServerConfig::from_url(line) {
+                Ok(svr) => servers.push(svr),
+                Err(err) => errors.push(format!("line {}: {err}", idx + 1)),
+            }
+        }
+
+        if !errors.is_empty() {
+            let err = Error::new(
+                ErrorKind::Malformed,
+                "invalid server URLs in file",
+                Some(format!("file {}, {}", path.display(), errors.join(", "))),
+            );
+            return Err(err);
+        }
+
+        Ok(servers)
+    }
+
+    /// Check if this configuration was loaded for local servers, see [`ConfigType::is_local`]
+    pub fn is_local(&self) -> bool {
+        self.config_type.is_local()
+    }
+
+    /// Check if this configuration was loaded for remote servers, see [`ConfigType::is_server`]
+    pub fn is_server(&self) -> bool {
+        self.config_type.is_server()
+    }
+
     /// Check if there are any plugin are enabled with servers
     pub fn has_server_plugins(&self) -> bool {
         for inst in &self.server {
@@ -2626,13 +3793,52 @@ impl Config {
         false
     }
 
+    /// Check if `ip` is in any networks of `forbidden_ip`
+    fn is_forbidden_ip(&self, ip: &IpAddr) -> bool {
+        self.forbidden_ip.iter().any(|net| net.contains(ip))
+    }
+
+    /// Listen addresses of servers that are in `forbidden_ip`
+    ///
+    /// `forbidden_ip` only applies to outbound connections of `ssserver`, so these servers can't be used to connect to
+    /// themselves, like other services on the same host. Domain names are resolved later, only IP literals are checked.
+    fn forbidden_server_addrs(&self, config_type: ConfigType) -> Vec<SocketAddr> {
+        if !config_type.is_server() {
+            return Vec::new();
+        }
+
+        self.server
+            .iter()
+            .filter_map(|inst| match inst.config.addr() {
+                ServerAddr::SocketAddr(sa) if !sa.ip().is_unspecified() && self.is_forbidden_ip(&sa.ip()) => Some(*sa),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Check if `domain` matches any patterns of `forbidden_domain_regex`
+    ///
+    /// Patterns are matched against the lowercase `domain` without the trailing dot, and they are not anchored,
+    /// so `^` and `$` are required for matching the whole domain name.
+    pub fn domain_matches_forbidden_regex(&self, domain: &str) -> bool {
+        matches_forbidden_domain_regex(&self.forbidden_domain_regex, domain)
+    }
+
     /// Check if all required fields are already set
-    pub fn check_integrity(&self) -> Result<(), Error> {
-        if self.config_type.is_local() {
+    pub fn check_integrity(&self) -> Result<()> {
+        self.validate_as(self.config_type)
+    }
+
+    /// Check if all fields required by `config_type` are set, and the values are usable by that role
+    ///
+    /// A configuration loaded for one role could be checked against another, for example,
+    /// a server configuration doesn't have any local listeners, which are required by [`ConfigType::Local`].
+    pub fn validate_as(&self, config_type: ConfigType) -> Result<()> {
+        if config_type.is_local() {
             if self.local.is_empty() {
                 let err = Error::new(
                     ErrorKind::MissingField,
-                    "missing `locals` for client configuration",
+                    "missing `local_address` and `local_port` or `locals` for client configuration",
                     None,
                 );
                 return Err(err);
@@ -2640,6 +3846,24 @@ impl Config {
 
             for local_config in &self.local {
                 local_config.config.check_integrity()?;
+
+                if let Some(ref servers) = local_config.servers {
+                    if servers.is_empty() {
+                        let err = Error::new(ErrorKind::Invalid, "`servers` of local shouldn't be empty", None);
+                        return Err(err);
+                    }
+
+                    for &idx in servers {
+                        if idx >= self.server.len() {
+                            let err = Error::new(
+                                ErrorKind::Invalid,
+                                "`servers` of local contains an out of range server index",
+                                Some(format!("index {idx}, but only {} servers", self.server.len())),
+                            );
+                            return Err(err);
+                        }
+                    }
+                }
             }
 
             // Balancer related checks
@@ -2656,9 +3880,25 @@ impl Config {
                     return Err(err);
                 }
             }
+
+            if let Some(ref health_check) = self.health_check {
+                if let Some(intv) = health_check.interval {
+                    if intv.as_secs() == 0 {
+                        let err = Error::new(ErrorKind::Invalid, "health_check.interval must be > 0", None);
+                        return Err(err);
+                    }
+                }
+
+                if let Some(timeout) = health_check.timeout {
+                    if timeout.as_secs() == 0 {
+                        let err = Error::new(ErrorKind::Invalid, "health_check.timeout must be > 0", None);
+                        return Err(err);
+                    }
+                }
+            }
         }
 
-        if self.config_type.is_server() && self.server.is_empty() {
+        if config_type.is_server() && self.server.is_empty() {
             let err = Error::new(
                 ErrorKind::MissingField,
                 "missing any valid servers in configuration",
@@ -2668,7 +3908,7 @@ impl Config {
         }
 
         #[cfg(feature = "local-online-config")]
-        if self.config_type.is_online_config() && self.server.is_empty() {
+        if config_type.is_online_config() && self.server.is_empty() {
             let err = Error::new(
                 ErrorKind::MissingField,
                 "missing any valid servers in configuration",
@@ -2677,7 +3917,7 @@ impl Config {
             return Err(err);
         }
 
-        if self.config_type.is_manager() && self.manager.is_none() {
+        if config_type.is_manager() && self.manager.is_none() {
             let err = Error::new(
                 ErrorKind::MissingField,
                 "missing `manager_addr` and `manager_port` in configuration",
@@ -2686,6 +3926,35 @@ impl Config {
             return Err(err);
         }
 
+        if self.disable_ipv6 {
+            let mut listen_addrs = Vec::new();
+            for local_instance in &self.local {
+                listen_addrs.extend(local_instance.config.addr.iter());
+                listen_addrs.extend(local_instance.config.udp_addr.iter());
+            }
+            if config_type.is_server() {
+                listen_addrs.extend(self.server.iter().map(|inst| inst.config.addr()));
+            }
+
+            for addr in listen_addrs {
+                if let Some(SocketAddr::V6(..)) = addr.as_socket_addr() {
+                    let err = Error::new(
+                        ErrorKind::Invalid,
+                        "couldn't listen on IPv6 addresses, IPv6 is disabled by `disable_ipv6`",
+                        Some(format!("address {addr}")),
+                    );
+                    return Err(err);
+                }
+            }
+        }
+
+        for sa in self.forbidden_server_addrs(config_type) {
+            warn!(
+                "server {} listens on an address in `forbidden_ip`, connections through it to this address will be rejected",
+                sa
+            );
+        }
+
         for inst in &self.server {
             let server = &inst.config;
 
@@ -2705,7 +3974,7 @@ impl Config {
                         return Err(err);
                     }
 
-                    if self.config_type.is_local() {
+                    if config_type.is_local() {
                         // Only server could bind to INADDR_ANY
                         let ip = sa.ip();
                         if ip.is_unspecified() {
@@ -2719,7 +3988,7 @@ impl Config {
                     }
 
                     #[cfg(feature = "local-online-config")]
-                    if self.config_type.is_online_config() {
+                    if config_type.is_online_config() {
                         // Only server could bind to INADDR_ANY
                         let ip = sa.ip();
                         if ip.is_unspecified() {
@@ -2771,85 +4040,638 @@ impl Config {
                     }
                 }
             }
+
+            if server.mode().enable_udp() {
+                if let Some(issue) = method_udp_issue(server.method()) {
+                    warn!(
+                        "server {} method {} with UDP relay enabled: {}",
+                        server.addr(),
+                        server.method(),
+                        issue
+                    );
+                }
+            }
+
+            if server.method().is_none() {
+                match server.plugin() {
+                    Some(plugin) => {
+                        warn!(
+                            "server {} method none: traffic is NOT encrypted or authenticated by shadowsocks, \
+                             its confidentiality and integrity rely entirely on plugin {}",
+                            server.addr(),
+                            plugin.plugin
+                        );
+                        if server.mode().enable_udp() && !plugin.plugin_mode.enable_udp() {
+                            warn!(
+                                "server {} method none: UDP relay doesn't go through plugin {}, UDP packets are sent in plaintext",
+                                server.addr(),
+                                plugin.plugin
+                            );
+                        }
+                    }
+                    None => {
+                        warn!(
+                            "server {} method none without plugin: all traffic, including target addresses, \
+                             is sent in plaintext and could be read or modified by anyone on the path",
+                            server.addr()
+                        );
+                    }
+                }
+            }
         }
 
         Ok(())
     }
 }
 
-impl fmt::Display for Config {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // Convert to json
+/// Parse `outbound_socks5` of the global configuration or a server
+fn parse_outbound_socks5(outbound_socks5: SSOutboundSocks5Config) -> Result<OutboundProxyConfig> {
+    let (address, username, password) = match outbound_socks5 {
+        SSOutboundSocks5Config::Simple(address) => (address, None, None),
+        SSOutboundSocks5Config::Detailed {
+            address,
+            username,
+            password,
+        } => (address, username, password),
+    };
 
-        let mut jconf = SSConfig::default();
+    let addr = match address.parse::<ServerAddr>() {
+        Ok(a) => a,
+        Err(..) => {
+            let err = Error::new(
+                ErrorKind::Invalid,
+                "invalid `outbound_socks5` address, should be either ip:port or domain:port",
+                Some(format!("address: {address}")),
+            );
+            return Err(err);
+        }
+    };
 
-        // Locals
-        if !self.local.is_empty() {
-            if self.local.len() == 1 && self.local[0].config.is_basic() {
-                let local_instance = &self.local[0];
-                let local = &local_instance.config;
-                if let Some(ref a) = local.addr {
-                    jconf.local_address = Some(match a {
-                        ServerAddr::SocketAddr(ref sa) => sa.ip().to_string(),
-                        ServerAddr::DomainName(ref dm, ..) => dm.to_string(),
-                    });
-                    jconf.local_port = Some(match a {
-                        ServerAddr::SocketAddr(ref sa) => sa.port(),
-                        ServerAddr::DomainName(.., port) => *port,
-                    });
-                }
+    let password = password.map(|p| read_variable_field_value(&p).into_owned());
+    match (&username, &password) {
+        (None, None) => {}
+        (Some(u), Some(p)) => {
+            if u.is_empty() || u.len() > 255 || p.is_empty() || p.len() > 255 {
+                let err = Error::new(
+                    ErrorKind::Invalid,
+                    "`outbound_socks5` username and password must be 1 to 255 bytes",
+                    None,
+                );
+                return Err(err);
+            }
+        }
+        _ => {
+            let err = Error::new(
+                ErrorKind::Malformed,
+                "`outbound_socks5` username and password must be provided together",
+                None,
+            );
+            return Err(err);
+        }
+    }
 
-                #[cfg(target_os = "macos")]
-                {
-                    jconf.launchd_tcp_socket_name.clone_from(&local.launchd_tcp_socket_name);
-                    jconf.launchd_udp_socket_name.clone_from(&local.launchd_udp_socket_name);
-                }
+    Ok(OutboundProxyConfig {
+        addr,
+        username,
+        password,
+    })
+}
 
-                if local.protocol != ProtocolType::Socks {
-                    jconf.protocol = Some(local.protocol.as_str().to_owned());
-                }
+/// Convert `OutboundProxyConfig` back to `outbound_socks5`
+fn to_outbound_socks5(proxy: &OutboundProxyConfig) -> SSOutboundSocks5Config {
+    if proxy.username.is_none() && proxy.password.is_none() {
+        SSOutboundSocks5Config::Simple(proxy.addr.to_string())
+    } else {
+        SSOutboundSocks5Config::Detailed {
+            address: proxy.addr.to_string(),
+            username: proxy.username.clone(),
+            password: proxy.password.clone(),
+        }
+    }
+}
 
-                // ACL
-                if let Some(ref acl) = local_instance.acl {
-                    jconf.acl = Some(acl.file_path().to_str().unwrap().to_owned());
-                }
-            } else {
-                let mut jlocals = Vec::with_capacity(self.local.len());
-                for local_instance in &self.local {
-                    let local = &local_instance.config;
+/// Check `outbound_bind_interface` is not empty, and warn if it is not supported on this platform
+fn check_outbound_bind_interface(iface: &str) -> Result<()> {
+    if iface.trim().is_empty() {
+        let err = Error::new(
+            ErrorKind::Invalid,
+            "`outbound_bind_interface` shouldn't be an empty string",
+            None,
+        );
+        return Err(err);
+    }
 
-                    let jlocal = SSLocalExtConfig {
-                        local_address: local.addr.as_ref().map(|a| match a {
-                            ServerAddr::SocketAddr(ref sa) => sa.ip().to_string(),
-                            ServerAddr::DomainName(ref dm, ..) => dm.to_string(),
-                        }),
-                        local_port: local.addr.as_ref().map(|a| match a {
-                            ServerAddr::SocketAddr(ref sa) => sa.port(),
-                            ServerAddr::DomainName(.., port) => *port,
-                        }),
-                        disabled: None,
-                        local_udp_address: local.udp_addr.as_ref().map(|udp_addr| match udp_addr {
-                            ServerAddr::SocketAddr(sa) => sa.ip().to_string(),
-                            ServerAddr::DomainName(dm, ..) => dm.to_string(),
-                        }),
-                        local_udp_port: local.udp_addr.as_ref().map(|udp_addr| match udp_addr {
-                            ServerAddr::SocketAddr(sa) => sa.port(),
-                            ServerAddr::DomainName(.., port) => *port,
-                        }),
-                        mode: Some(local.mode.to_string()),
-                        protocol: match local.protocol {
-                            ProtocolType::Socks => None,
-                            #[allow(unreachable_patterns)]
-                            p => Some(p.as_str().to_owned()),
-                        },
-                        #[cfg(target_os = "macos")]
-                        launchd_tcp_socket_name: local.launchd_tcp_socket_name.clone(),
-                        #[cfg(target_os = "macos")]
-                        launchd_udp_socket_name: local.launchd_udp_socket_name.clone(),
-                        #[cfg(feature = "local-redir")]
-                        tcp_redir: if local.tcp_redir != RedirType::tcp_default() {
-                            Some(local.tcp_redir.to_string())
-                        } else {
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "watchos",
+        target_os = "tvos",
+        windows
+    )))]
+    warn!(
+        "`outbound_bind_interface` {} is not supported on this platform, ignored",
+        iface
+    );
+
+    Ok(())
+}
+
+/// Set `unix_addr` of a local server from `local_address` `unix:/path/to/socket`
+fn set_local_unix_addr(local_config: &mut LocalConfig, path: &str, local_port: Option<u16>) -> Result<()> {
+    if local_port.is_some() {
+        let err = Error::new(
+            ErrorKind::Malformed,
+            "`local_port` cannot be set with unix domain socket `local_address`",
+            None,
+        );
+        return Err(err);
+    }
+
+    #[cfg(unix)]
+    {
+        local_config.unix_addr = Some(PathBuf::from(path));
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = local_config;
+        let err = Error::new(
+            ErrorKind::Invalid,
+            "unix domain socket `local_address` is only supported on unix platforms",
+            Some(format!("local_address unix:{path}")),
+        );
+        Err(err)
+    }
+}
+
+/// Check `max_connections` is positive and could be the permits of a `Semaphore`
+fn check_max_connections(max_connections: i64) -> Result<usize> {
+    match usize::try_from(max_connections) {
+        Ok(n) if n > 0 && n <= tokio::sync::Semaphore::MAX_PERMITS => Ok(n),
+        _ => {
+            let err = Error::new(
+                ErrorKind::Invalid,
+                "invalid `max_connections`, must be a positive integer not larger than `Semaphore::MAX_PERMITS`",
+                Some(max_connections.to_string()),
+            );
+            Err(err)
+        }
+    }
+}
+
+/// Set `max_retries` and `retry_backoff` (milliseconds) of a server, values of the server take precedence over the global ones
+fn set_server_retries(svr: &mut ServerConfig, retries: [Option<i64>; 2], global_retries: [Option<i64>; 2]) -> Result<()> {
+    let check = |key: &str, n: i64| match u64::try_from(n) {
+        Ok(n) => Ok(n),
+        Err(..) => {
+            let err = Error::new(
+                ErrorKind::Invalid,
+                "invalid retry configuration, must be a non-negative integer",
+                Some(format!("{key} {n}")),
+            );
+            Err(err)
+        }
+    };
+
+    let [max_retries, retry_backoff] = retries;
+    let [global_max_retries, global_retry_backoff] = global_retries;
+
+    if let Some(n) = max_retries.or(global_max_retries) {
+        let n = check("max_retries", n)?;
+        svr.set_max_retries(u32::try_from(n).unwrap_or(u32::MAX));
+    }
+    if let Some(n) = retry_backoff.or(global_retry_backoff) {
+        svr.set_retry_backoff(Duration::from_millis(check("retry_backoff", n)?));
+    }
+
+    Ok(())
+}
+
+/// Check if `domain` matches any of `regexes`, see [`Config::domain_matches_forbidden_regex`]
+pub(crate) fn matches_forbidden_domain_regex(regexes: &[Regex], domain: &str) -> bool {
+    if regexes.is_empty() {
+        return false;
+    }
+
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    regexes.iter().any(|re| re.is_match(&domain))
+}
+
+/// `method` could be omitted if the server's plugin handles encryption, which implies `none`
+fn implied_plugin_method(plugin: Option<&str>) -> Option<&'static str> {
+    plugin.filter(|p| !p.is_empty()).map(|_| "none")
+}
+
+/// Set `fallback_method` of a server
+///
+/// Servers probe requests by decrypting their first chunks, so only AEAD and AEAD-2022 methods without `users` are supported.
+fn set_server_fallback_method(svr: &mut ServerConfig, m: &str) -> Result<()> {
+    let method = match parse_method(m) {
+        Ok(method) => method,
+        Err(..) => {
+            let err = Error::new(
+                ErrorKind::Invalid,
+                "unsupported fallback_method",
+                Some(format!("`{m}` is not a supported method")),
+            );
+            return Err(err);
+        }
+    };
+
+    // Methods that couldn't be probed have no probe length
+    if request_probe_len(svr.method()) == 0 || request_probe_len(method) == 0 {
+        let err = Error::new(
+            ErrorKind::Invalid,
+            "`fallback_method` only supports AEAD and AEAD-2022 methods",
+            Some(format!("method {}, fallback_method {}", svr.method(), method)),
+        );
+        return Err(err);
+    }
+
+    if svr.user_manager().is_some() {
+        let err = Error::new(
+            ErrorKind::Invalid,
+            "`fallback_method` is not supported by servers with `users`",
+            None,
+        );
+        return Err(err);
+    }
+
+    if let Err(serr) = svr.set_fallback_method(method) {
+        let err = Error::new(
+            ErrorKind::Invalid,
+            "invalid password for `fallback_method`",
+            Some(format!("{}", serr)),
+        );
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+/// Set `kdf` of a server
+///
+/// Parameters are only accepted by methods deriving their keys from `password`, see [`KdfConfig`].
+fn set_server_kdf(svr: &mut ServerConfig, kdf: &SSKdfConfig) -> Result<()> {
+    let kdf = KdfConfig {
+        iterations: kdf.iterations,
+    };
+
+    if let Err(serr) = svr.set_kdf(kdf) {
+        let err = Error::new(ErrorKind::Invalid, "invalid `kdf`", Some(format!("{}", serr)));
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+/// Set `valid_from` and `valid_until` of a server
+fn set_server_validity(svr: &mut ServerConfig, valid_from: Option<&str>, valid_until: Option<&str>) -> Result<()> {
+    let parse = |key: &str, value: &str| match parse_rfc3339(value) {
+        Some(t) => Ok(t),
+        None => {
+            let err = Error::new(
+                ErrorKind::Malformed,
+                "malformed timestamp, must be in RFC3339 format, like `2024-01-01T00:00:00Z`",
+                Some(format!("`{key}`: {value}")),
+            );
+            Err(err)
+        }
+    };
+
+    if let Some(t) = valid_from {
+        svr.set_valid_from(parse("valid_from", t)?);
+    }
+    if let Some(t) = valid_until {
+        svr.set_valid_until(parse("valid_until", t)?);
+    }
+
+    Ok(())
+}
+
+/// Parse a RFC3339 timestamp, like `2024-01-01T08:00:00+08:00`
+fn parse_rfc3339(s: &str) -> Option<SystemTime> {
+    OffsetDateTime::parse(s, &Rfc3339).ok().map(SystemTime::from)
+}
+
+/// Format a time as RFC3339 timestamp in UTC, like `2024-01-01T00:00:00Z`
+///
+/// Times parsed by `parse_rfc3339` are always in the supported range of years 0000 to 9999.
+fn format_rfc3339(t: SystemTime) -> String {
+    OffsetDateTime::from(t)
+        .format(&Rfc3339)
+        .expect("timestamp out of RFC3339 range")
+}
+
+/// Set `timeout`, `connect_timeout` and `idle_timeout` (in seconds) of a server, `0` means no timeout
+///
+/// Server's own values take precedence over the global ones,
+/// and `connect_timeout`, `idle_timeout` take precedence over `timeout` of the same level.
+fn set_server_timeouts(svr: &mut ServerConfig, own: [Option<u64>; 3], global: [Option<u64>; 3]) {
+    let [timeout, connect_timeout, idle_timeout] = own;
+    let [global_timeout, global_connect_timeout, global_idle_timeout] = global;
+
+    if let Some(t) = timeout {
+        svr.set_timeout(Duration::from_secs(t));
+    }
+    // Only set if configured, so that servers without any timeout are serialized without one.
+    // `0` is kept to disable DEFAULT_TIMEOUT
+    if timeout.is_some() || global_timeout.is_some() {
+        let t = svr.effective_timeout(global_timeout.map(Duration::from_secs));
+        svr.set_timeout(t.unwrap_or(Duration::ZERO));
+    }
+    if let Some(t) = connect_timeout.or(timeout).or(global_connect_timeout) {
+        svr.set_connect_timeout(Duration::from_secs(t));
+    }
+    if let Some(t) = idle_timeout.or(timeout).or(global_idle_timeout) {
+        svr.set_idle_timeout(Duration::from_secs(t));
+    }
+}
+
+/// `timeout`, `connect_timeout` and `idle_timeout` (in seconds) of a server to be serialized
+///
+/// `connect_timeout` and `idle_timeout` are omitted if loading would derive the same value from `timeout` or
+/// [`DEFAULT_TIMEOUT`], and are `0` if they are disabled while `timeout` isn't set.
+fn server_timeouts_to_json(svr: &ServerConfig) -> [Option<u64>; 3] {
+    let timeout = svr.timeout();
+    let derived = timeout.or(Some(DEFAULT_TIMEOUT));
+    let to_json = |t: Option<Duration>| (t != derived).then(|| t.map_or(0, |t| t.as_secs()));
+
+    [
+        timeout.map(|t| t.as_secs()),
+        to_json(svr.connect_timeout()),
+        to_json(svr.idle_timeout()),
+    ]
+}
+
+/// Serialize a server instance into an entry of `servers`
+fn server_instance_to_ssconfig(inst: &ServerInstanceConfig) -> SSServerExtConfig {
+    let svr = &inst.config;
+    let [timeout, connect_timeout, idle_timeout] = server_timeouts_to_json(svr);
+
+    SSServerExtConfig {
+        server: match *svr.addr() {
+            ServerAddr::SocketAddr(ref sa) => sa.ip().to_string(),
+            ServerAddr::DomainName(ref dm, ..) => dm.to_string(),
+        },
+        server_port: SSServerPortConfig::Port(svr.addr().port()),
+        password: if svr.method().is_none() {
+            None
+        } else {
+            Some(svr.password().to_string())
+        },
+        method: Some(svr.method().to_string()),
+        users: svr.user_manager().map(|m| {
+            let mut vu = Vec::new();
+            for u in m.users_iter() {
+                vu.push(SSServerUserConfig {
+                    name: u.name().to_owned(),
+                    password: u.encoded_key(),
+                });
+            }
+            vu
+        }),
+        disabled: None,
+        plugin: svr.plugin().map(|p| p.plugin.to_string()),
+        plugin_opts: svr.plugin().and_then(|p| p.plugin_opts.clone()),
+        plugin_args: svr.plugin().and_then(|p| {
+            if p.plugin_args.is_empty() {
+                None
+            } else {
+                Some(p.plugin_args.clone())
+            }
+        }),
+        plugin_mode: match svr.plugin() {
+            None => None,
+            Some(p) => match p.plugin_mode {
+                Mode::TcpOnly => None,
+                _ => Some(p.plugin_mode.to_string()),
+            },
+        },
+        timeout,
+        connect_timeout,
+        idle_timeout,
+        max_connections: svr.max_connections().map(|n| n as i64),
+        max_retries: svr.max_retries().map(i64::from),
+        retry_backoff: svr.retry_backoff().map(|d| d.as_millis() as i64),
+        fallback_method: svr.fallback_method().map(|m| m.to_string()),
+        kdf: svr.kdf().map(|k| SSKdfConfig {
+            iterations: k.iterations,
+        }),
+        valid_from: svr.valid_from().map(format_rfc3339),
+        valid_until: svr.valid_until().map(format_rfc3339),
+        remarks: svr.remarks().map(ToOwned::to_owned),
+        id: svr.id().map(ToOwned::to_owned),
+        group: svr.group().map(ToOwned::to_owned),
+        mode: Some(svr.mode().to_string()),
+        tcp_weight: if (svr.weight().tcp_weight() - 1.0).abs() > f32::EPSILON {
+            Some(svr.weight().tcp_weight())
+        } else {
+            None
+        },
+        udp_weight: if (svr.weight().udp_weight() - 1.0).abs() > f32::EPSILON {
+            Some(svr.weight().udp_weight())
+        } else {
+            None
+        },
+        acl: inst
+            .acl
+            .as_ref()
+            .and_then(|a| a.file_path().to_str().map(ToOwned::to_owned)),
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        outbound_fwmark: inst.outbound_fwmark.map(i64::from),
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        outbound_fwmark: None,
+        outbound_bind_addr: inst.outbound_bind_addr,
+        outbound_bind_interface: inst.outbound_bind_interface.clone(),
+        outbound_udp_allow_fragmentation: inst.outbound_udp_allow_fragmentation,
+        outbound_socks5: inst.outbound_proxy.as_ref().map(to_outbound_socks5),
+    }
+}
+
+/// Parse `method` of a server
+///
+/// `auto` is only allowed in local configurations. There is no negotiation in the protocol, it is resolved from the
+/// `password` while loading:
+///
+/// - A base64 encoded key of an AEAD-2022 method selects the first AEAD-2022 method of `auto_methods` with that key length
+/// - Other passwords are valid for every non-2022 method, so `auto_methods` must have exactly one of them
+fn parse_server_method(
+    m: &str,
+    password: Option<&str>,
+    config_type: ConfigType,
+    auto_methods: &[CipherKind],
+) -> Result<CipherKind> {
+    if m != AUTO_METHOD {
+        return match parse_method(m) {
+            Ok(method) => Ok(method),
+            Err(..) => {
+                let err = Error::new(
+                    ErrorKind::Invalid,
+                    "unsupported method",
+                    Some(format!("`{m}` is not a supported method")),
+                );
+                Err(err)
+            }
+        };
+    }
+
+    if !config_type.is_local() {
+        let err = Error::new(
+            ErrorKind::Invalid,
+            "`auto` method is only supported by local configurations",
+            None,
+        );
+        return Err(err);
+    }
+
+    let usable = |method: CipherKind| match password {
+        Some(password) => ServerConfig::new(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)), password, method).is_ok(),
+        None => method.is_none(),
+    };
+
+    #[cfg(feature = "aead-cipher-2022")]
+    if let Some(method) = auto_methods
+        .iter()
+        .copied()
+        .find(|m| m.category() == shadowsocks::crypto::CipherCategory::Aead2022 && usable(*m))
+    {
+        debug!("method auto resolved to {}", method);
+        return Ok(method);
+    }
+
+    let candidates = auto_methods.iter().copied().filter(|m| usable(*m)).collect::<Vec<_>>();
+    match candidates[..] {
+        [method] => {
+            debug!("method auto resolved to {}", method);
+            return Ok(method);
+        }
+        [] => {}
+        _ => {
+            let err = Error::new(
+                ErrorKind::Invalid,
+                "`auto` method is ambiguous with the `password`, set `method` or a single candidate in `auto_methods`",
+                Some(format!(
+                    "candidates: [{}]",
+                    candidates
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                )),
+            );
+            return Err(err);
+        }
+    }
+
+    let err = Error::new(
+        ErrorKind::Invalid,
+        "no method in `auto_methods` is usable with the `password`",
+        Some(format!(
+            "auto_methods: [{}]",
+            auto_methods
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<String>>()
+                .join(", ")
+        )),
+    );
+    Err(err)
+}
+
+/// Known problems of methods when they are used for UDP relay
+fn method_udp_issue(method: CipherKind) -> Option<&'static str> {
+    match method.category() {
+        #[cfg(feature = "stream-cipher")]
+        shadowsocks::crypto::CipherCategory::Stream => {
+            Some("stream ciphers have no integrity check, corrupted or tampered packets couldn't be detected")
+        }
+        _ => None,
+    }
+}
+
+impl Config {
+    /// Convert to the serializable configuration
+    fn to_ssconfig(&self) -> SSConfig {
+        let mut jconf = SSConfig::default();
+
+        // Locals
+        if !self.local.is_empty() {
+            if self.local.len() == 1 && self.local[0].config.is_basic() && self.local[0].servers.is_none() {
+                let local_instance = &self.local[0];
+                let local = &local_instance.config;
+                if let Some(ref a) = local.addr {
+                    jconf.local_address = Some(SSLocalAddressConfig::Address(match a {
+                        ServerAddr::SocketAddr(ref sa) => sa.ip().to_string(),
+                        ServerAddr::DomainName(ref dm, ..) => dm.to_string(),
+                    }));
+                    jconf.local_port = Some(match a {
+                        ServerAddr::SocketAddr(ref sa) => sa.port(),
+                        ServerAddr::DomainName(.., port) => *port,
+                    });
+                }
+
+                #[cfg(target_os = "macos")]
+                {
+                    jconf.launchd_tcp_socket_name.clone_from(&local.launchd_tcp_socket_name);
+                    jconf.launchd_udp_socket_name.clone_from(&local.launchd_udp_socket_name);
+                }
+
+                if local.protocol != ProtocolType::Socks {
+                    jconf.protocol = Some(local.protocol.as_str().to_owned());
+                }
+
+                // ACL
+                if let Some(ref acl) = local_instance.acl {
+                    jconf.acl = Some(acl.file_path().to_str().unwrap().to_owned());
+                }
+            } else {
+                let mut jlocals = Vec::with_capacity(self.local.len());
+                for local_instance in &self.local {
+                    let local = &local_instance.config;
+
+                    #[allow(unused_mut)]
+                    let mut local_address = local.addr.as_ref().map(|a| match a {
+                        ServerAddr::SocketAddr(ref sa) => sa.ip().to_string(),
+                        ServerAddr::DomainName(ref dm, ..) => dm.to_string(),
+                    });
+                    #[cfg(unix)]
+                    if let Some(ref path) = local.unix_addr {
+                        local_address = Some(format!("unix:{}", path.display()));
+                    }
+
+                    let jlocal = SSLocalExtConfig {
+                        local_address,
+                        local_port: local.addr.as_ref().map(|a| match a {
+                            ServerAddr::SocketAddr(ref sa) => sa.port(),
+                            ServerAddr::DomainName(.., port) => *port,
+                        }),
+                        disabled: None,
+                        local_udp_address: local.udp_addr.as_ref().map(|udp_addr| match udp_addr {
+                            ServerAddr::SocketAddr(sa) => sa.ip().to_string(),
+                            ServerAddr::DomainName(dm, ..) => dm.to_string(),
+                        }),
+                        local_udp_port: local.udp_addr.as_ref().map(|udp_addr| match udp_addr {
+                            ServerAddr::SocketAddr(sa) => sa.port(),
+                            ServerAddr::DomainName(.., port) => *port,
+                        }),
+                        mode: Some(local.mode.to_string()),
+                        protocol: match local.protocol {
+                            ProtocolType::Socks => None,
+                            #[allow(unreachable_patterns)]
+                            p => Some(p.as_str().to_owned()),
+                        },
+                        #[cfg(target_os = "macos")]
+                        launchd_tcp_socket_name: local.launchd_tcp_socket_name.clone(),
+                        #[cfg(target_os = "macos")]
+                        launchd_udp_socket_name: local.launchd_udp_socket_name.clone(),
+                        #[cfg(feature = "local-redir")]
+                        tcp_redir: if local.tcp_redir != RedirType::tcp_default() {
+                            Some(local.tcp_redir.to_string())
+                        } else {
                             None
                         },
                         #[cfg(feature = "local-redir")]
@@ -2926,6 +4748,16 @@ impl fmt::Display for Config {
 
                         #[cfg(feature = "local")]
                         socks5_auth_config_path: None,
+                        #[cfg(feature = "local")]
+                        local_client_acl: local.client_acl.as_ref().map(|acl| SSLocalClientAclConfig {
+                            allow: (!acl.allow.is_empty()).then(|| acl.allow.iter().map(ToString::to_string).collect()),
+                            deny: (!acl.deny.is_empty()).then(|| acl.deny.iter().map(ToString::to_string).collect()),
+                        }),
+
+                        #[cfg(feature = "local-http")]
+                        local_http_username: local.http_proxy_auth.as_ref().map(|(u, _)| u.clone()),
+                        #[cfg(feature = "local-http")]
+                        local_http_password: local.http_proxy_auth.as_ref().map(|(_, p)| p.clone()),
 
                         #[cfg(feature = "local-fake-dns")]
                         fake_dns_record_expire_duration: local.fake_dns_record_expire_duration.map(|d| d.as_secs()),
@@ -2939,6 +4771,8 @@ impl fmt::Display for Config {
                             .as_ref()
                             .and_then(|n| n.to_str().map(ToOwned::to_owned)),
 
+                        servers: local_instance.servers.clone(),
+
                         acl: local_instance
                             .acl
                             .as_ref()
@@ -2962,10 +4796,7 @@ impl fmt::Display for Config {
                     ServerAddr::SocketAddr(ref sa) => sa.ip().to_string(),
                     ServerAddr::DomainName(ref dm, ..) => dm.to_string(),
                 });
-                jconf.server_port = Some(match *svr.addr() {
-                    ServerAddr::SocketAddr(ref sa) => sa.port(),
-                    ServerAddr::DomainName(.., port) => port,
-                });
+                jconf.server_port = Some(SSServerPortConfig::Port(svr.addr().port()));
                 jconf.method = Some(svr.method().to_string());
                 jconf.password = if svr.method().is_none() {
                     None
@@ -2988,7 +4819,16 @@ impl fmt::Display for Config {
                         _ => Some(p.plugin_mode.to_string()),
                     },
                 };
-                jconf.timeout = svr.timeout().map(|t| t.as_secs());
+                [jconf.timeout, jconf.connect_timeout, jconf.idle_timeout] = server_timeouts_to_json(svr);
+                jconf.max_connections = svr.max_connections().map(|n| n as i64);
+                jconf.max_retries = svr.max_retries().map(i64::from);
+                jconf.retry_backoff = svr.retry_backoff().map(|d| d.as_millis() as i64);
+                jconf.fallback_method = svr.fallback_method().map(|m| m.to_string());
+                jconf.kdf = svr.kdf().map(|k| SSKdfConfig {
+                    iterations: k.iterations,
+                });
+                jconf.valid_from = svr.valid_from().map(format_rfc3339);
+                jconf.valid_until = svr.valid_until().map(format_rfc3339);
                 jconf.mode = Some(svr.mode().to_string());
 
                 if let Some(ref acl) = inst.acl {
@@ -3000,74 +4840,8 @@ impl fmt::Display for Config {
                 let mut vsvr = Vec::new();
 
                 for inst in &self.server {
-                    let svr = &inst.config;
-
-                    vsvr.push(SSServerExtConfig {
-                        server: match *svr.addr() {
-                            ServerAddr::SocketAddr(ref sa) => sa.ip().to_string(),
-                            ServerAddr::DomainName(ref dm, ..) => dm.to_string(),
-                        },
-                        server_port: match *svr.addr() {
-                            ServerAddr::SocketAddr(ref sa) => sa.port(),
-                            ServerAddr::DomainName(.., port) => port,
-                        },
-                        password: if svr.method().is_none() {
-                            None
-                        } else {
-                            Some(svr.password().to_string())
-                        },
-                        method: svr.method().to_string(),
-                        users: svr.user_manager().map(|m| {
-                            let mut vu = Vec::new();
-                            for u in m.users_iter() {
-                                vu.push(SSServerUserConfig {
-                                    name: u.name().to_owned(),
-                                    password: u.encoded_key(),
-                                });
-                            }
-                            vu
-                        }),
-                        disabled: None,
-                        plugin: svr.plugin().map(|p| p.plugin.to_string()),
-                        plugin_opts: svr.plugin().and_then(|p| p.plugin_opts.clone()),
-                        plugin_args: svr.plugin().and_then(|p| {
-                            if p.plugin_args.is_empty() {
-                                None
-                            } else {
-                                Some(p.plugin_args.clone())
-                            }
-                        }),
-                        plugin_mode: match svr.plugin() {
-                            None => None,
-                            Some(p) => match p.plugin_mode {
-                                Mode::TcpOnly => None,
-                                _ => Some(p.plugin_mode.to_string()),
-                            },
-                        },
-                        timeout: svr.timeout().map(|t| t.as_secs()),
-                        remarks: svr.remarks().map(ToOwned::to_owned),
-                        id: svr.id().map(ToOwned::to_owned),
-                        mode: Some(svr.mode().to_string()),
-                        tcp_weight: if (svr.weight().tcp_weight() - 1.0).abs() > f32::EPSILON {
-                            Some(svr.weight().tcp_weight())
-                        } else {
-                            None
-                        },
-                        udp_weight: if (svr.weight().udp_weight() - 1.0).abs() > f32::EPSILON {
-                            Some(svr.weight().udp_weight())
-                        } else {
-                            None
-                        },
-                        acl: inst
-                            .acl
-                            .as_ref()
-                            .and_then(|a| a.file_path().to_str().map(ToOwned::to_owned)),
-                        #[cfg(any(target_os = "linux", target_os = "android"))]
-                        outbound_fwmark: inst.outbound_fwmark,
-                        outbound_bind_addr: inst.outbound_bind_addr,
-                        outbound_bind_interface: inst.outbound_bind_interface.clone(),
-                        outbound_udp_allow_fragmentation: inst.outbound_udp_allow_fragmentation,
-                    });
+                    let svr = server_instance_to_ssconfig(inst);
+                    vsvr.push(SSServerEntryConfig::Detailed(Box::new(svr)));
                 }
 
                 jconf.servers = Some(vsvr);
@@ -3140,11 +4914,16 @@ impl fmt::Display for Config {
             }
         }
 
+        jconf.shutdown_timeout = self.shutdown_timeout.map(|t| t.as_secs());
         jconf.udp_timeout = self.udp_timeout.map(|t| t.as_secs());
 
         jconf.udp_max_associations = self.udp_max_associations;
 
         jconf.udp_mtu = self.udp_mtu;
+        jconf.udp_bind_address = self.udp_bind_addr.map(|a| a.to_string());
+        jconf.udp_max_payload = self.udp_max_payload;
+
+        jconf.limit_rate = self.max_rate_bytes_per_sec.map(SSRateLimitConfig::BytesPerSec);
 
         #[cfg(all(unix, not(target_os = "android")))]
         {
@@ -3155,13 +4934,25 @@ impl fmt::Display for Config {
             jconf.ipv6_first = Some(self.ipv6_first);
         }
 
+        if self.disable_ipv6 {
+            jconf.disable_ipv6 = Some(self.disable_ipv6);
+        }
+
         if self.ipv6_only {
             jconf.ipv6_only = Some(self.ipv6_only);
         }
 
+        if self.reuse_port {
+            jconf.reuse_port = Some(self.reuse_port);
+        }
+
+        if !self.reuse_address {
+            jconf.reuse_address = Some(self.reuse_address);
+        }
+
         #[cfg(any(target_os = "linux", target_os = "android"))]
         {
-            jconf.outbound_fwmark = self.outbound_fwmark;
+            jconf.outbound_fwmark = self.outbound_fwmark.map(i64::from);
         }
 
         #[cfg(target_os = "freebsd")]
@@ -3171,7 +4962,31 @@ impl fmt::Display for Config {
 
         jconf.outbound_bind_addr = self.outbound_bind_addr.map(|i| i.to_string());
         jconf.outbound_bind_interface.clone_from(&self.outbound_bind_interface);
+        jconf.outbound_ttl = self.outbound_ttl.map(i64::from);
         jconf.outbound_udp_allow_fragmentation = Some(self.outbound_udp_allow_fragmentation);
+        jconf.outbound_socks5 = self.outbound_proxy.as_ref().map(to_outbound_socks5);
+
+        if !self.forbidden_ip.is_empty() {
+            jconf.forbidden_ip = Some(self.sorted_forbidden_ips().iter().map(ToString::to_string).collect());
+        }
+        if !self.forbidden_domain_regex.is_empty() {
+            jconf.forbidden_domain_regex = Some(
+                self.forbidden_domain_regex
+                    .iter()
+                    .map(|re| re.as_str().to_owned())
+                    .collect(),
+            );
+        }
+        jconf.udp_allowed_ports = self
+            .udp_allowed_ports
+            .as_ref()
+            .map(|ports| ports.iter().map(|&p| i64::from(p)).collect());
+        if !self.bypass_ip.is_empty() {
+            jconf.bypass_ip = Some(self.bypass_ip.iter().map(ToString::to_string).collect());
+        }
+        if !self.proxy_ip.is_empty() {
+            jconf.proxy_ip = Some(self.proxy_ip.iter().map(ToString::to_string).collect());
+        }
 
         // Security
         if self.security.replay_attack.policy != ReplayAttackPolicy::default() {
@@ -3182,6 +4997,19 @@ impl fmt::Display for Config {
             });
         }
 
+        // Log
+        jconf.log_level.clone_from(&self.log_level);
+        jconf.log_file.clone_from(&self.log_file);
+        if self.access_log {
+            jconf.access_log = Some(self.access_log);
+        }
+
+        if self.version != CONFIG_VERSION {
+            jconf.version = Some(self.version);
+        }
+
+        jconf.extra.clone_from(&self.extra);
+
         // Balancer
         if self.balancer.max_server_rtt.is_some() || self.balancer.check_interval.is_some() {
             jconf.balancer = Some(SSBalancerConfig {
@@ -3191,6 +5019,15 @@ impl fmt::Display for Config {
             });
         }
 
+        if let Some(ref health_check) = self.health_check {
+            jconf.health_check = Some(SSHealthCheckConfig {
+                interval: health_check.interval.as_ref().map(Duration::as_secs),
+                timeout: health_check.timeout.as_ref().map(Duration::as_secs),
+                target: health_check.target.as_ref().map(ToString::to_string),
+            });
+        }
+        jconf.preferred_server = self.preferred_server;
+
         // ACL
         if let Some(ref acl) = self.acl {
             jconf.acl = Some(acl.file_path().to_str().unwrap().to_owned());
@@ -3205,22 +5042,1062 @@ impl fmt::Display for Config {
             });
         }
 
-        write!(f, "{}", json5::to_string(&jconf).unwrap())
+        jconf
     }
-}
 
-/// Parse variable value if it is an environment variable
-///
-/// If value is in format `${VAR_NAME}` then it will try to read from `VAR_NAME` environment variable.
-/// It will return the original value if fails to read `${VAR_NAME}`.
-pub fn read_variable_field_value(value: &str) -> Cow<'_, str> {
-    if let Some(left_over) = value.strip_prefix("${") {
-        if let Some(var_name) = left_over.strip_suffix('}') {
-            match env::var(var_name) {
-                Ok(value) => return value.into(),
-                Err(err) => {
-                    warn!(
-                        "couldn't read password from environment variable {}, error: {}",
+    /// Save Config to a File
+    ///
+    /// Configuration will be written to a temporary file in the same directory first, then renamed to `filename`.
+    pub fn save_to_file<P: AsRef<Path>>(&self, filename: P) -> Result<()> {
+        let filename = filename.as_ref();
+
+        let content = match serde_json::to_string_pretty(&self.to_ssconfig()) {
+            Ok(c) => c,
+            Err(err) => {
+                let err = Error::new(
+                    ErrorKind::Invalid,
+                    "failed to serialize configuration",
+                    Some(err.to_string()),
+                );
+                return Err(err);
+            }
+        };
+
+        let mut tmp_name = filename.file_name().map(ToOwned::to_owned).unwrap_or_default();
+        tmp_name.push(format!(".{}.tmp", std::process::id()));
+        let tmp_path = filename.with_file_name(tmp_name);
+
+        let result = (|| {
+            let mut writer = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&tmp_path)?;
+            writer.write_all(content.as_bytes())?;
+            writer.write_all(b"\n")?;
+            writer.sync_all()?;
+            fs::rename(&tmp_path, filename)
+        })();
+
+        if let Err(err) = result {
+            let _ = fs::remove_file(&tmp_path);
+
+            let err = Error::new(
+                ErrorKind::IoError,
+                "failed to save configuration",
+                Some(format!("file {}, error: {err}", filename.display())),
+            );
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Find the server by its `remarks`
+    ///
+    /// Matching is exact and case-sensitive, the first matched server is returned.
+    pub fn server_by_remarks(&self, name: &str) -> Option<&ServerConfig> {
+        self.server
+            .iter()
+            .map(|inst| &inst.config)
+            .find(|svr| svr.remarks() == Some(name))
+    }
+
+    /// Add a server from a SIP002 URL (`ss://...`), returns its index in `server`
+    ///
+    /// The server won't be added if there is already a server with the same address, method, password and plugin,
+    /// the index of the existing one is returned instead.
+    pub fn add_server_from_url(&mut self, url: &str) -> Result<usize> {
+        let svr = match ServerConfig::from_url(url) {
+            Ok(svr) => svr,
+            Err(err) => {
+                let err = Error::new(ErrorKind::Invalid, "invalid server url", Some(err.to_string()));
+                return Err(err);
+            }
+        };
+
+        let plugin_key = |svr: &ServerConfig| svr.plugin().map(|p| (p.plugin.clone(), p.plugin_opts.clone()));
+        let existing = self.server.iter().position(|inst| {
+            let other = &inst.config;
+            other.addr() == svr.addr()
+                && other.method() == svr.method()
+                && other.password() == svr.password()
+                && plugin_key(other) == plugin_key(&svr)
+        });
+        if let Some(idx) = existing {
+            return Ok(idx);
+        }
+
+        self.server.push(ServerInstanceConfig::with_server_config(svr));
+        Ok(self.server.len() - 1)
+    }
+
+    /// Remove the first server with address `addr`, returns `false` if there is no such server
+    ///
+    /// Server indices in `locals` and `preferred_server` are updated, references to the removed server are dropped.
+    /// Removing the last server referenced by a local is refused, because that local would be left without servers.
+    pub fn remove_server(&mut self, addr: &ServerAddr) -> Result<bool, Error> {
+        let removed_idx = match self.server.iter().position(|inst| inst.config.addr() == addr) {
+            Some(idx) => idx,
+            None => return Ok(false),
+        };
+
+        let orphans_local = self.local.iter().any(|local| match local.servers {
+            Some(ref servers) => servers.iter().all(|&idx| idx == removed_idx),
+            None => false,
+        });
+        if orphans_local {
+            let err = Error::new(
+                ErrorKind::Invalid,
+                "server is the last one referenced by a local",
+                Some(addr.to_string()),
+            );
+            return Err(err);
+        }
+
+        self.server.remove(removed_idx);
+
+        let new_index = |idx: usize| match idx.cmp(&removed_idx) {
+            Ordering::Less => Some(idx),
+            Ordering::Equal => None,
+            Ordering::Greater => Some(idx - 1),
+        };
+
+        for local in &mut self.local {
+            if let Some(ref mut servers) = local.servers {
+                *servers = servers.iter().filter_map(|&idx| new_index(idx)).collect();
+            }
+        }
+        self.preferred_server = self.preferred_server.and_then(new_index);
+
+        Ok(true)
+    }
+
+    /// Replace the first server with address `addr` by `new`, returns `false` if there is no such server
+    ///
+    /// Instance level settings of the server, like its ACL and outbound options, are kept.
+    pub fn replace_server(&mut self, addr: &ServerAddr, new: ServerConfig) -> bool {
+        match self.server.iter_mut().find(|inst| inst.config.addr() == addr) {
+            Some(inst) => {
+                inst.config = new;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get all servers tagged with `group`
+    ///
+    /// Matching is exact and case-sensitive.
+    pub fn servers_in_group(&self, group: &str) -> Vec<&ServerConfig> {
+        self.server
+            .iter()
+            .map(|inst| &inst.config)
+            .filter(|svr| svr.group() == Some(group))
+            .collect()
+    }
+
+    /// Resolve addresses of all servers, see [`ServerAddr::resolve`]
+    ///
+    /// Results are in the same order as `server`, with their indexes. Failures don't stop resolving the other servers.
+    pub fn preflight_resolve(&self) -> Vec<(usize, io::Result<SocketAddr>)> {
+        self.server
+            .iter()
+            .enumerate()
+            .map(|(idx, inst)| (idx, inst.config.addr().resolve()))
+            .collect()
+    }
+
+    /// Sum of integer TCP weights of all servers, see [`SERVER_WEIGHT_SCALE`]
+    pub fn total_weight(&self) -> u64 {
+        self.server.iter().map(|inst| server_weight(&inst.config)).sum()
+    }
+
+    /// Pick a server by weight with a random `rng_value`
+    ///
+    /// Servers with weight 0 will never be picked. Returns `None` if all servers' weights are 0.
+    pub fn pick_weighted(&self, rng_value: u64) -> Option<&ServerConfig> {
+        let total_weight = self.total_weight();
+        if total_weight == 0 {
+            return None;
+        }
+
+        let mut value = rng_value % total_weight;
+        for inst in &self.server {
+            let weight = server_weight(&inst.config);
+            if value < weight {
+                return Some(&inst.config);
+            }
+            value -= weight;
+        }
+
+        unreachable!("rng_value % total_weight should be less than total_weight");
+    }
+
+    /// Sort `server` by address and then port
+    ///
+    /// This is opt-in, servers are kept in the order they were loaded unless this is called. It should be called after
+    /// merging servers from multiple sources to get reproducible balancing across restarts. IP addresses are ordered
+    /// before domain names, and servers with the same address keep their relative order. Server indices in `locals`
+    /// and `preferred_server` are updated to the new order.
+    pub fn sort_servers(&mut self) {
+        fn cmp_addr(a: &ServerAddr, b: &ServerAddr) -> Ordering {
+            match (a, b) {
+                (ServerAddr::SocketAddr(a), ServerAddr::SocketAddr(b)) => {
+                    a.ip().cmp(&b.ip()).then_with(|| a.port().cmp(&b.port()))
+                }
+                (ServerAddr::SocketAddr(..), ServerAddr::DomainName(..)) => Ordering::Less,
+                (ServerAddr::DomainName(..), ServerAddr::SocketAddr(..)) => Ordering::Greater,
+                (ServerAddr::DomainName(a, ap), ServerAddr::DomainName(b, bp)) => a.cmp(b).then_with(|| ap.cmp(bp)),
+            }
+        }
+
+        // Stable sort, order[new_idx] = old_idx
+        let mut order = (0..self.server.len()).collect::<Vec<usize>>();
+        order.sort_by(|&a, &b| cmp_addr(self.server[a].config.addr(), self.server[b].config.addr()));
+
+        let mut new_index = vec![0; order.len()];
+        for (new_idx, &old_idx) in order.iter().enumerate() {
+            new_index[old_idx] = new_idx;
+        }
+
+        let mut servers = std::mem::take(&mut self.server)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        self.server = order
+            .iter()
+            .map(|&old_idx| servers[old_idx].take().expect("server moved twice"))
+            .collect();
+
+        for local in &mut self.local {
+            if let Some(ref mut servers) = local.servers {
+                for idx in servers.iter_mut() {
+                    *idx = new_index[*idx];
+                }
+            }
+        }
+        if let Some(ref mut idx) = self.preferred_server {
+            *idx = new_index[*idx];
+        }
+    }
+
+    /// Networks of `forbidden_ip` in a stable order, with duplicates removed
+    ///
+    /// IPv4 networks are ordered before IPv6 networks, then by address and prefix length numerically.
+    pub fn sorted_forbidden_ips(&self) -> Vec<IpNet> {
+        let mut nets = self.forbidden_ip.clone();
+        nets.sort_unstable();
+        nets.dedup();
+        nets
+    }
+
+    /// Number of servers using each method
+    pub fn method_counts(&self) -> HashMap<CipherKind, usize> {
+        let mut counts = HashMap::new();
+        for inst in &self.server {
+            *counts.entry(inst.config.method()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// `ss://` URLs of all servers, including their plugins and remarks
+    pub fn to_url_list(&self) -> Vec<String> {
+        self.server.iter().map(|inst| inst.config.to_url()).collect()
+    }
+
+    /// Subscription of all servers, base64 encoded `ss://` URLs, one per line
+    ///
+    /// It could be loaded back by [`Config::load_servers_from_url_file`].
+    pub fn to_subscription(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(self.to_url_list().join("\n"))
+    }
+
+    /// One line summary for logging at startup
+    ///
+    /// Contains the number of servers, methods, whether UDP is enabled, local listen addresses and DNS cache capacity.
+    /// Passwords and keys are never included.
+    pub fn summary(&self) -> String {
+        let mut methods = Vec::new();
+        for inst in &self.server {
+            let method = inst.config.method();
+            if !methods.contains(&method) {
+                methods.push(method);
+            }
+        }
+        let methods = methods.iter().map(ToString::to_string).collect::<Vec<String>>();
+
+        let udp_enabled = self.server.iter().any(|inst| inst.config.mode().enable_udp())
+            || self.local.iter().any(|inst| inst.config.mode.enable_udp());
+
+        let locals = self
+            .local
+            .iter()
+            .filter_map(|inst| {
+                #[cfg(unix)]
+                if let Some(ref path) = inst.config.unix_addr {
+                    return Some(format!("{} unix:{}", inst.config.protocol.as_str(), path.display()));
+                }
+
+                inst.config
+                    .addr
+                    .as_ref()
+                    .map(|addr| format!("{} {}", inst.config.protocol.as_str(), addr))
+            })
+            .collect::<Vec<String>>();
+
+        let dns_cache = match self.dns_cache_size {
+            None => "default".to_owned(),
+            Some(0) => "disabled".to_owned(),
+            Some(size) => size.to_string(),
+        };
+
+        format!(
+            "{} server(s), methods: [{}], udp: {}, local: [{}], dns cache: {}",
+            self.server.len(),
+            methods.join(", "),
+            if udp_enabled { "enabled" } else { "disabled" },
+            locals.join(", "),
+            dns_cache,
+        )
+    }
+
+    /// Find the route of a destination address by `bypass_ip` and `proxy_ip`
+    ///
+    /// The most specific (longest prefix) network in both lists wins. If an address is matched by networks with
+    /// the same prefix length in both lists, `bypass_ip` takes precedence.
+    pub fn route_for(&self, addr: &IpAddr) -> Route {
+        // IPv4-mapped IPv6 addresses are matched as IPv4
+        let addr = match addr {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(*addr),
+            IpAddr::V4(..) => *addr,
+        };
+
+        fn longest_match(nets: &[IpNet], addr: &IpAddr) -> Option<u8> {
+            nets.iter()
+                .filter(|net| net.contains(addr))
+                .map(IpNet::prefix_len)
+                .max()
+        }
+
+        match (
+            longest_match(&self.bypass_ip, &addr),
+            longest_match(&self.proxy_ip, &addr),
+        ) {
+            (None, None) => Route::Default,
+            (Some(..), None) => Route::Direct,
+            (None, Some(..)) => Route::Proxy,
+            (Some(bypass), Some(proxy)) => {
+                if bypass >= proxy {
+                    Route::Direct
+                } else {
+                    Route::Proxy
+                }
+            }
+        }
+    }
+
+    /// Display Config with all passwords and keys replaced by `"***"`, safe for logging
+    ///
+    /// `plugin_opts` and values of unknown keys are also replaced, they may contain secrets.
+    pub fn redacted_display(&self) -> RedactedConfig<'_> {
+        RedactedConfig { config: self }
+    }
+
+    /// Compare with `other` (normally the newly loaded configuration), for logging changes of reloading
+    ///
+    /// Configurations are compared in their serialized form, so every key written by `to_string` is covered.
+    /// Servers are identified by their addresses. Secrets are never included in the result.
+    pub fn diff(&self, other: &Config) -> ConfigDiff {
+        let mut diff = ConfigDiff::default();
+
+        for new_inst in &other.server {
+            let new_svr = &new_inst.config;
+            match self.server.iter().find(|inst| inst.config.addr() == new_svr.addr()) {
+                None => diff.added_servers.push(new_svr.addr().clone()),
+                Some(old_inst) => {
+                    let old = server_instance_to_ssconfig(old_inst);
+                    let new = server_instance_to_ssconfig(new_inst);
+                    let mut old_shown = server_instance_to_ssconfig(old_inst);
+                    let mut new_shown = server_instance_to_ssconfig(new_inst);
+                    redact_server_ssconfig(&mut old_shown);
+                    redact_server_ssconfig(&mut new_shown);
+
+                    let changes = diff_serialized(&old, &new, &old_shown, &new_shown)
+                        .into_iter()
+                        .map(|(key, old_value, new_value)| {
+                            if old_value == new_value {
+                                // Redacted secrets
+                                format!("{key} changed")
+                            } else {
+                                format!("{key} {old_value} -> {new_value}")
+                            }
+                        })
+                        .collect::<Vec<_>>();
+
+                    if !changes.is_empty() {
+                        diff.changed_servers.push((new_svr.addr().clone(), changes));
+                    }
+                }
+            }
+        }
+        for old_inst in &self.server {
+            let old_addr = old_inst.config.addr();
+            if !other.server.iter().any(|inst| inst.config.addr() == old_addr) {
+                diff.removed_servers.push(old_addr.clone());
+            }
+        }
+
+        // Servers are compared above, they are removed or they would be serialized as top-level keys
+        let without_servers = |config: &Config, redacted: bool| {
+            let mut config = config.clone();
+            config.server.clear();
+            let mut jconf = config.to_ssconfig();
+            if redacted {
+                redact_ssconfig(&mut jconf);
+            }
+            jconf
+        };
+        diff.changed_fields = diff_serialized(
+            &without_servers(self, false),
+            &without_servers(other, false),
+            &without_servers(self, true),
+            &without_servers(other, true),
+        )
+        .into_iter()
+        .filter(|(key, ..)| key != "forbidden_ip")
+        .collect();
+
+        diff.added_forbidden_ip = other
+            .forbidden_ip
+            .iter()
+            .filter(|ip| !self.forbidden_ip.contains(ip))
+            .cloned()
+            .collect();
+        diff.removed_forbidden_ip = self
+            .forbidden_ip
+            .iter()
+            .filter(|ip| !other.forbidden_ip.contains(ip))
+            .cloned()
+            .collect();
+
+        diff
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Convert to json
+        write!(f, "{}", json5::to_string(&self.to_ssconfig()).unwrap())
+    }
+}
+
+impl From<ServerConfig> for Config {
+    /// Server configuration with only one server, the other fields are the same as [`Config::new`]
+    fn from(svr_cfg: ServerConfig) -> Config {
+        let mut config = Config::new(ConfigType::Server);
+        config.server.push(ServerInstanceConfig::with_server_config(svr_cfg));
+        config
+    }
+}
+
+/// Differences between two [`Config`]s, returned by [`Config::diff`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    /// Servers that only exist in the new configuration
+    pub added_servers: Vec<ServerAddr>,
+    /// Servers that only exist in the old configuration
+    pub removed_servers: Vec<ServerAddr>,
+    /// Servers that exist in both, with descriptions of their changes
+    pub changed_servers: Vec<(ServerAddr, Vec<String>)>,
+    /// Changed top-level keys, `(key, old value, new value)` in JSON, secrets are redacted
+    pub changed_fields: Vec<(String, String, String)>,
+    /// Networks added to `forbidden_ip`
+    pub added_forbidden_ip: Vec<IpNet>,
+    /// Networks removed from `forbidden_ip`
+    pub removed_forbidden_ip: Vec<IpNet>,
+}
+
+impl ConfigDiff {
+    /// Check if nothing changed
+    pub fn is_empty(&self) -> bool {
+        *self == ConfigDiff::default()
+    }
+}
+
+/// Route of a destination address, returned by [`Config::route_for`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    /// Connect directly
+    Direct,
+    /// Connect through proxy
+    Proxy,
+    /// Not matched by any rules, follow the default behavior
+    Default,
+}
+
+/// Placeholder of secrets in [`Config::redacted_display`]
+const REDACTED_SECRET: &str = "***";
+
+/// Helper for displaying a [`Config`] without secrets, created by [`Config::redacted_display`]
+pub struct RedactedConfig<'a> {
+    config: &'a Config,
+}
+
+impl fmt::Display for RedactedConfig<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut jconf = self.config.to_ssconfig();
+        redact_ssconfig(&mut jconf);
+        write!(f, "{}", json5::to_string(&jconf).unwrap())
+    }
+}
+
+/// Replace all secrets in `jconf` by [`REDACTED_SECRET`]
+fn redact_ssconfig(jconf: &mut SSConfig) {
+    redact_secret(&mut jconf.password);
+    // Plugins' options usually have their own credentials
+    redact_secret(&mut jconf.plugin_opts);
+    // Unknown keys may be secrets of features that are not enabled, or misspelled keys
+    for value in jconf.extra.values_mut() {
+        *value = REDACTED_SECRET.into();
+    }
+    if let Some(ref mut servers) = jconf.servers {
+        for svr in servers {
+            match svr {
+                SSServerEntryConfig::Url(url) => *url = REDACTED_SECRET.to_owned(),
+                SSServerEntryConfig::Detailed(svr) => redact_server_ssconfig(svr),
+            }
+        }
+    }
+    if let Some(SSOutboundSocks5Config::Detailed { ref mut password, .. }) = jconf.outbound_socks5 {
+        redact_secret(password);
+    }
+    #[cfg(feature = "local-http")]
+    if let Some(ref mut locals) = jconf.locals {
+        for local in locals {
+            redact_secret(&mut local.local_http_password);
+        }
+    }
+}
+
+/// Replace all secrets in an entry of `servers` by [`REDACTED_SECRET`]
+fn redact_server_ssconfig(svr: &mut SSServerExtConfig) {
+    redact_secret(&mut svr.password);
+    redact_secret(&mut svr.plugin_opts);
+    if let Some(SSOutboundSocks5Config::Detailed { ref mut password, .. }) = svr.outbound_socks5 {
+        redact_secret(password);
+    }
+    if let Some(ref mut users) = svr.users {
+        for user in users {
+            user.password = REDACTED_SECRET.to_owned();
+        }
+    }
+}
+
+fn redact_secret(value: &mut Option<String>) {
+    if value.is_some() {
+        *value = Some(REDACTED_SECRET.to_owned());
+    }
+}
+
+/// Keys with different values in serialized `old` and `new`, `(key, old value, new value)`
+///
+/// Values are compared in `old` and `new`, but displayed from `old_shown` and `new_shown`, which have secrets redacted.
+/// Keys that are not serialized have their default values, they are displayed as `default`.
+fn diff_serialized<T: Serialize>(old: &T, new: &T, old_shown: &T, new_shown: &T) -> Vec<(String, String, String)> {
+    let to_map = |value: &T| match serde_json::to_value(value) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    let (old, new, old_shown, new_shown) = (to_map(old), to_map(new), to_map(old_shown), to_map(new_shown));
+    let shown = |map: &serde_json::Map<String, serde_json::Value>, key: &str| match map.get(key) {
+        Some(value) => value.to_string(),
+        None => "default".to_owned(),
+    };
+
+    old.keys()
+        .chain(new.keys().filter(|key| !old.contains_key(*key)))
+        .filter(|key| old.get(*key) != new.get(*key))
+        .map(|key| (key.clone(), shown(&old_shown, key), shown(&new_shown, key)))
+        .collect()
+}
+
+/// Convert 1-based `line` and `column` (in characters) to byte offset of `s`
+fn location_byte_offset(s: &str, line: usize, column: usize) -> Option<usize> {
+    let line_start = s
+        .split_inclusive('\n')
+        .take(line.checked_sub(1)?)
+        .map(str::len)
+        .sum::<usize>();
+    let rest = s.get(line_start..)?;
+    let column_offset = rest
+        .char_indices()
+        .nth(column.checked_sub(1)?)
+        .map(|(i, _)| i)
+        .unwrap_or(rest.len());
+    Some(line_start + column_offset)
+}
+
+/// Check `outbound_fwmark` is a valid `SO_MARK` value
+fn check_outbound_fwmark(fwmark: i64) -> Result<u32, Error> {
+    match u32::try_from(fwmark) {
+        Ok(fwmark) => Ok(fwmark),
+        Err(..) => {
+            let err = Error::new(
+                ErrorKind::Invalid,
+                "invalid `outbound_fwmark`, must be in [0, 4294967295]",
+                Some(fwmark.to_string()),
+            );
+            Err(err)
+        }
+    }
+}
+
+/// Integer TCP weight of `svr_cfg`
+fn server_weight(svr_cfg: &ServerConfig) -> u64 {
+    (svr_cfg.weight().tcp_weight() * SERVER_WEIGHT_SCALE as f32).round() as u64
+}
+
+/// Prefix of errors reported by `DuplicateKeyChecker`
+const DUPLICATE_KEY_ERROR_PREFIX: &str = "duplicate key ";
+
+/// Check if any object in JSON `s` has duplicate keys
+///
+/// Parsers keep only one of the duplicated keys silently, or report errors without names of keys (`servers`' items).
+/// Syntax errors are ignored here, they will be reported by the following parsing.
+fn check_duplicate_keys(s: &str) -> Result<(), Error> {
+    match json5::from_str::<DuplicateKeyChecker>(s) {
+        Err(json5::Error::Message { msg, location }) if msg.starts_with(DUPLICATE_KEY_ERROR_PREFIX) => {
+            let detail = match location {
+                Some(location) => format!("line {}, column {}: {}", location.line, location.column, msg),
+                None => msg,
+            };
+            let err = Error::new(ErrorKind::Malformed, "duplicate key in object", Some(detail));
+            Err(err)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Accepts any JSON value, fails if any object has duplicate keys
+struct DuplicateKeyChecker;
+
+impl<'de> Deserialize<'de> for DuplicateKeyChecker {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(DuplicateKeyCheckerVisitor)
+    }
+}
+
+struct DuplicateKeyCheckerVisitor;
+
+impl<'de> Visitor<'de> for DuplicateKeyCheckerVisitor {
+    type Value = DuplicateKeyChecker;
+
+    fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str("any JSON value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<Self::Value, E> {
+        Ok(DuplicateKeyChecker)
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<Self::Value, E> {
+        Ok(DuplicateKeyChecker)
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<Self::Value, E> {
+        Ok(DuplicateKeyChecker)
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<Self::Value, E> {
+        Ok(DuplicateKeyChecker)
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<Self::Value, E> {
+        Ok(DuplicateKeyChecker)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(DuplicateKeyChecker)
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E> {
+        Ok(DuplicateKeyChecker)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        while seq.next_element::<DuplicateKeyChecker>()?.is_some() {}
+        Ok(DuplicateKeyChecker)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut keys = HashSet::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if keys.contains(&key) {
+                return Err(de::Error::custom(format!("{DUPLICATE_KEY_ERROR_PREFIX}`{key}`")));
+            }
+            map.next_value::<DuplicateKeyChecker>()?;
+            keys.insert(key);
+        }
+        Ok(DuplicateKeyChecker)
+    }
+}
+
+/// Read configuration file, at most `MAX_CONFIG_FILE_SIZE` bytes
+fn read_config_file(filename: &Path) -> Result<String, Error> {
+    let file = OpenOptions::new().read(true).open(filename)?;
+    let file_size = file.metadata()?.len();
+    if file_size > MAX_CONFIG_FILE_SIZE {
+        let err = Error::new(
+            ErrorKind::Malformed,
+            "configuration file is too large",
+            Some(format!("{file_size} bytes, at most {MAX_CONFIG_FILE_SIZE} bytes")),
+        );
+        return Err(err);
+    }
+
+    // File may grow after checking its metadata
+    let mut content = String::new();
+    BufReader::new(file)
+        .take(MAX_CONFIG_FILE_SIZE + 1)
+        .read_to_string(&mut content)?;
+    if content.len() as u64 > MAX_CONFIG_FILE_SIZE {
+        let err = Error::new(
+            ErrorKind::Malformed,
+            "configuration file is too large",
+            Some(format!("at most {MAX_CONFIG_FILE_SIZE} bytes")),
+        );
+        return Err(err);
+    }
+
+    Ok(content)
+}
+
+/// Decode `content` if the whole subscription is base64 encoded, otherwise returns it unchanged
+fn decode_base64_subscription(content: String) -> String {
+    if content.contains("://") {
+        return content;
+    }
+
+    // Subscriptions may be wrapped into multiple lines, with or without paddings
+    let encoded = content.chars().filter(|c| !c.is_ascii_whitespace()).collect::<String>();
+    let encoded = encoded.trim_end_matches('=');
+
+    let decoded = base64::engine::general_purpose::STANDARD_NO_PAD
+        .decode(encoded)
+        .or_else(|_| base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(encoded));
+    match decoded.map(String::from_utf8) {
+        Ok(Ok(decoded)) => decoded,
+        _ => content,
+    }
+}
+
+/// Formats of configuration files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfigFileFormat {
+    Json,
+    Yaml,
+}
+
+impl ConfigFileFormat {
+    /// Format by the extension of `filename`
+    fn from_path(filename: &Path) -> Option<ConfigFileFormat> {
+        let ext = filename.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "json" | "json5" | "jsonc" => Some(ConfigFileFormat::Json),
+            "yaml" | "yml" => Some(ConfigFileFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Format by the content, JSON is preferred because YAML is mostly a superset of JSON
+    fn sniff(content: &str) -> ConfigFileFormat {
+        if json5::from_str::<serde_json::Value>(content).is_ok() {
+            return ConfigFileFormat::Json;
+        }
+
+        #[cfg(feature = "config-yaml")]
+        if let Ok(serde_json::Value::Object(..)) = serde_yaml::from_str::<serde_json::Value>(content) {
+            return ConfigFileFormat::Yaml;
+        }
+
+        // Reports errors of JSON
+        ConfigFileFormat::Json
+    }
+}
+
+/// Convert YAML `content` read from `filename` to JSON
+#[cfg(feature = "config-yaml")]
+fn yaml_to_json(content: &str, filename: &Path) -> Result<String> {
+    let yaml_error = |err: serde_yaml::Error| {
+        Error::new(
+            ErrorKind::JsonParsingError,
+            "yaml parse error",
+            Some(format!("path {}, error: {err}", filename.display())),
+        )
+    };
+
+    let value = serde_yaml::from_str::<serde_json::Value>(content).map_err(yaml_error)?;
+
+    // Type errors are checked here, otherwise they would be reported with positions in the converted JSON
+    serde_yaml::from_str::<SSConfig>(content).map_err(yaml_error)?;
+
+    Ok(value.to_string())
+}
+
+#[cfg(not(feature = "config-yaml"))]
+fn yaml_to_json(_content: &str, filename: &Path) -> Result<String> {
+    let err = Error::new(
+        ErrorKind::Invalid,
+        "yaml configuration requires the `config-yaml` feature",
+        Some(format!("path {}", filename.display())),
+    );
+    Err(err)
+}
+
+/// Read configuration file `filename`, and convert it to JSON if it is in the other formats
+///
+/// Formats are chosen in the same way as [`Config::load_from_file`].
+pub fn read_config_content(filename: &Path) -> Result<String> {
+    let content = read_config_file(filename)?;
+
+    let format = match ConfigFileFormat::from_path(filename) {
+        Some(format) => format,
+        None => ConfigFileFormat::sniff(&content),
+    };
+
+    match format {
+        ConfigFileFormat::Json => Ok(content),
+        ConfigFileFormat::Yaml => yaml_to_json(&content, filename),
+    }
+}
+
+/// Convert json5 error of `content` read from `filename`, with the byte offset of syntax errors
+fn json5_error_with_offset(err: json5::Error, content: &str, filename: &Path) -> Error {
+    let offset = match err {
+        json5::Error::Message {
+            location: Some(ref location),
+            ..
+        } => location_byte_offset(content, location.line, location.column),
+        json5::Error::Message { location: None, .. } => None,
+    };
+
+    let mut err = Error::from(err);
+    if let (Some(offset), Some(detail)) = (offset, err.detail.as_mut()) {
+        detail.push_str(&format!(" (byte offset {offset} of {})", filename.display()));
+    }
+    err
+}
+
+/// Merge files in `include` of `root` (loaded from `filename`) into `root`
+///
+/// `including` are canonical paths of files that are being included, for detecting circular includes.
+fn merge_config_includes(
+    root: &mut serde_json::Value,
+    filename: &Path,
+    including: &mut Vec<PathBuf>,
+) -> Result<(), Error> {
+    let root = match root.as_object_mut() {
+        Some(o) => o,
+        None => {
+            let err = Error::new(
+                ErrorKind::Malformed,
+                "configuration should be an object",
+                Some(format!("path {}", filename.display())),
+            );
+            return Err(err);
+        }
+    };
+
+    let patterns = match root.remove("include") {
+        None => return Ok(()),
+        Some(serde_json::Value::Array(patterns)) => patterns,
+        Some(..) => {
+            let err = Error::new(
+                ErrorKind::Malformed,
+                "`include` should be an array of paths",
+                Some(format!("path {}", filename.display())),
+            );
+            return Err(err);
+        }
+    };
+
+    let base_dir = filename.parent().unwrap_or_else(|| Path::new("."));
+
+    for pattern in patterns {
+        let pattern = match pattern {
+            serde_json::Value::String(p) => p,
+            p => {
+                let err = Error::new(
+                    ErrorKind::Malformed,
+                    "`include` should be an array of paths",
+                    Some(format!("path {}, invalid item {p}", filename.display())),
+                );
+                return Err(err);
+            }
+        };
+
+        for include_path in expand_include_pattern(base_dir, &pattern)? {
+            let canonical_path = fs::canonicalize(&include_path)?;
+            if including.contains(&canonical_path) {
+                let err = Error::new(
+                    ErrorKind::Invalid,
+                    "circular `include`",
+                    Some(format!(
+                        "{} is included by {} recursively",
+                        include_path.display(),
+                        filename.display()
+                    )),
+                );
+                return Err(err);
+            }
+
+            let content = read_config_content(&include_path)?;
+            check_duplicate_keys(&content)?;
+            let mut fragment = match json5::from_str::<serde_json::Value>(&content) {
+                Ok(v) => v,
+                Err(err) => return Err(json5_error_with_offset(err, &content, &include_path)),
+            };
+
+            including.push(canonical_path);
+            merge_config_includes(&mut fragment, &include_path, including)?;
+            including.pop();
+
+            // Checked in merge_config_includes
+            let fragment = match fragment {
+                serde_json::Value::Object(o) => o,
+                _ => unreachable!("included configuration is not an object"),
+            };
+
+            for (key, value) in fragment {
+                match (root.get_mut(&key), value) {
+                    (None, value) => {
+                        root.insert(key, value);
+                    }
+                    (Some(serde_json::Value::Array(values)), serde_json::Value::Array(mut fragment_values)) => {
+                        values.append(&mut fragment_values);
+                    }
+                    // Keys in the including file have higher priority
+                    (Some(..), _) => {}
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Expand `pattern` of `include` to paths of files, `*` and `?` are only allowed in the file name
+fn expand_include_pattern(base_dir: &Path, pattern: &str) -> Result<Vec<PathBuf>, Error> {
+    let path = if pattern.starts_with('~') {
+        expand_config_path(Path::new(pattern))?
+    } else {
+        base_dir.join(pattern)
+    };
+
+    let file_name = match path.file_name().and_then(|n| n.to_str()) {
+        Some(n) if n.contains(['*', '?']) => n.to_owned(),
+        _ => {
+            if path.to_string_lossy().contains(['*', '?']) {
+                let err = Error::new(
+                    ErrorKind::Invalid,
+                    "wildcards in `include` are only supported in file names",
+                    Some(pattern.to_owned()),
+                );
+                return Err(err);
+            }
+            return Ok(vec![path]);
+        }
+    };
+
+    let dir = path.parent().unwrap_or(base_dir);
+    if dir.to_string_lossy().contains(['*', '?']) {
+        let err = Error::new(
+            ErrorKind::Invalid,
+            "wildcards in `include` are only supported in file names",
+            Some(pattern.to_owned()),
+        );
+        return Err(err);
+    }
+
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+
+        let name = entry.file_name();
+        let name = match name.to_str() {
+            Some(n) => n,
+            None => continue,
+        };
+
+        // Hidden files are only matched explicitly, like shells
+        if name.starts_with('.') && !file_name.starts_with('.') {
+            continue;
+        }
+
+        if wildcard_match(&file_name, name) {
+            paths.push(entry.path());
+        }
+    }
+
+    // Merge in a stable order
+    paths.sort();
+    Ok(paths)
+}
+
+/// Match `name` with `pattern`, `*` matches any characters and `?` matches one character
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    let (mut pi, mut ni) = (0, 0);
+    // Position of the last `*` in pattern, and the position in name that it is matching to
+    let mut star = None;
+
+    while ni < name.len() {
+        if pi < pattern.len() && (pattern[pi] == '?' || pattern[pi] == name[ni]) {
+            pi += 1;
+            ni += 1;
+        } else if pi < pattern.len() && pattern[pi] == '*' {
+            star = Some((pi, ni));
+            pi += 1;
+        } else if let Some((spi, sni)) = star {
+            // Let the last `*` match one more character
+            pi = spi + 1;
+            ni = sni + 1;
+            star = Some((spi, sni + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[pi..].iter().all(|&c| c == '*')
+}
+
+/// Parse variable value if it is an environment variable
+///
+/// If value is in format `${VAR_NAME}` then it will try to read from `VAR_NAME` environment variable.
+/// It will return the original value if fails to read `${VAR_NAME}`.
+pub fn read_variable_field_value(value: &str) -> Cow<'_, str> {
+    if let Some(left_over) = value.strip_prefix("${") {
+        if let Some(var_name) = left_over.strip_suffix('}') {
+            match env::var(var_name) {
+                Ok(value) => return value.into(),
+                Err(err) => {
+                    warn!(
+                        "couldn't read password from environment variable {}, error: {}",
                         var_name, err
                     );
                 }
@@ -3230,3 +6107,487 @@ pub fn read_variable_field_value(value: &str) -> Cow<'_, str> {
 
     value.into()
 }
+
+/// Parse rate limit into bytes per second
+///
+/// Bytes units `K`, `M`, `G` (optionally with `B`) are multiples of 1024, bits units
+/// `kbit`, `mbit`, `gbit` are multiples of 1000 bits, like `tc`. Units are case insensitive.
+fn parse_rate_limit(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let pos = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(pos);
+    let num = num.parse::<u64>().ok()?;
+
+    match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => Some(num),
+        "k" | "kb" => num.checked_mul(1024),
+        "m" | "mb" => num.checked_mul(1024 * 1024),
+        "g" | "gb" => num.checked_mul(1024 * 1024 * 1024),
+        "bit" => Some(num / 8),
+        "kbit" => num.checked_mul(1_000).map(|n| n / 8),
+        "mbit" => num.checked_mul(1_000_000).map(|n| n / 8),
+        "gbit" => num.checked_mul(1_000_000_000).map(|n| n / 8),
+        _ => None,
+    }
+}
+
+/// Values of the top-level configuration inherited by entries of `servers`
+#[derive(Clone, Copy)]
+struct ServerEntryDefaults<'a> {
+    config_type: ConfigType,
+    server_source: ServerSource,
+    global_method: Option<&'a str>,
+    global_password: Option<&'a String>,
+    global_fallback_method: Option<&'a String>,
+    global_kdf: Option<&'a SSKdfConfig>,
+    global_valid_from: Option<&'a str>,
+    global_valid_until: Option<&'a str>,
+    global_timeouts: [Option<u64>; 3],
+    global_max_connections: Option<usize>,
+    global_retries: [Option<i64>; 2],
+    global_mode: Mode,
+    auto_methods: &'a [CipherKind],
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    outbound_fwmark: Option<u32>,
+    outbound_bind_addr: Option<IpAddr>,
+    outbound_bind_interface: Option<&'a String>,
+    outbound_udp_allow_fragmentation: Option<bool>,
+}
+
+/// Load an entry of `servers` into `servers`, disabled entries are skipped
+fn load_server_entry(
+    svr: SSServerEntryConfig,
+    defaults: &ServerEntryDefaults<'_>,
+    servers: &mut Vec<ServerInstanceConfig>,
+) -> Result<()> {
+    let ServerEntryDefaults {
+        config_type,
+        server_source,
+        global_method,
+        global_password,
+        global_fallback_method,
+        global_kdf,
+        global_valid_from,
+        global_valid_until,
+        global_timeouts,
+        global_max_connections,
+        global_retries,
+        global_mode,
+        auto_methods,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        outbound_fwmark,
+        outbound_bind_addr,
+        outbound_bind_interface,
+        outbound_udp_allow_fragmentation,
+    } = *defaults;
+
+    let svr = match svr {
+        SSServerEntryConfig::Detailed(svr) => *svr,
+        SSServerEntryConfig::Url(url) => {
+            let mut nsvr = match ServerConfig::from_url(&url) {
+                Ok(svr) => svr,
+                Err(serr) => {
+                    let err = Error::new(
+                        ErrorKind::Malformed,
+                        "invalid server URL in `servers`",
+                        Some(serr.to_string()),
+                    );
+                    return Err(err);
+                }
+            };
+            nsvr.set_source(server_source);
+            set_server_timeouts(&mut nsvr, [None; 3], global_timeouts);
+            if let Some(n) = global_max_connections {
+                nsvr.set_max_connections(n);
+            }
+            set_server_retries(&mut nsvr, [None; 2], global_retries)?;
+            if let Some(m) = global_fallback_method {
+                set_server_fallback_method(&mut nsvr, m)?;
+            }
+            if let Some(kdf) = global_kdf {
+                set_server_kdf(&mut nsvr, kdf)?;
+            }
+            set_server_validity(&mut nsvr, global_valid_from, global_valid_until)?;
+
+            // Server will derive mode from the global scope
+            if matches!(config_type, ConfigType::Server | ConfigType::Manager) {
+                nsvr.set_mode(global_mode);
+            }
+
+            // URLs couldn't carry per-entry options like `disabled` and `acl`, only the global ones are applied
+            servers.push(ServerInstanceConfig {
+                config: nsvr,
+                acl: None,
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                outbound_fwmark,
+                outbound_bind_addr,
+                outbound_bind_interface: outbound_bind_interface.cloned(),
+                outbound_udp_allow_fragmentation,
+                outbound_proxy: None,
+            });
+            return Ok(());
+        }
+    };
+
+    // Skip if server is disabled
+    if svr.disabled.unwrap_or(false) {
+        return Ok(());
+    }
+
+    let address = svr.server;
+    let ports = svr.server_port.to_ports()?;
+    let port = ports[0];
+
+    let addr = match address.parse::<Ipv4Addr>() {
+        Ok(v4) => ServerAddr::SocketAddr(SocketAddr::V4(SocketAddrV4::new(v4, port))),
+        Err(..) => match address.parse::<Ipv6Addr>() {
+            Ok(v6) => ServerAddr::SocketAddr(SocketAddr::V6(SocketAddrV6::new(v6, port, 0, 0))),
+            Err(..) => ServerAddr::DomainName(address, port),
+        },
+    };
+
+    // `method` and `password` inherit from the outer fields if they are not set
+    let method = match svr
+        .method
+        .as_deref()
+        .or(global_method)
+        .or_else(|| implied_plugin_method(svr.plugin.as_deref()))
+    {
+        Some(m) => parse_server_method(
+            m,
+            svr.password
+                .as_ref()
+                .or(global_password)
+                .map(|pwd| read_variable_field_value(pwd))
+                .as_deref(),
+            config_type,
+            auto_methods,
+        )?,
+        None => {
+            let err = Error::new(
+                ErrorKind::MissingField,
+                "`method` is required",
+                Some(format!("`method` is required for server {addr}")),
+            );
+            return Err(err);
+        }
+    };
+
+    // Only "password" support getting from environment variable.
+    let password = match svr.password.as_ref().or(global_password) {
+        Some(pwd) => read_variable_field_value(pwd),
+        None => {
+            if method.is_none() {
+                String::new().into()
+            } else {
+                let err = Error::new(
+                    ErrorKind::MissingField,
+                    "`password` is required",
+                    Some(format!("`password` is required for method {method}")),
+                );
+                return Err(err);
+            }
+        }
+    };
+
+    let mut nsvr = match ServerConfig::new(addr, password, method) {
+        Ok(svr) => svr,
+        Err(serr) => {
+            // Errors are all about keys, for example, AEAD-2022 keys with wrong length
+            let err = Error::new(
+                ErrorKind::Invalid,
+                "server config create failed",
+                Some(format!("{}", serr)),
+            );
+            return Err(err);
+        }
+    };
+    nsvr.set_source(server_source);
+
+    // Extensible Identity Header, Users
+    if let Some(users) = svr.users {
+        let mut user_manager = ServerUserManager::new();
+
+        for user in users {
+            let user = match ServerUser::with_encoded_key(user.name, &user.password) {
+                Ok(u) => u,
+                Err(..) => {
+                    let err = Error::new(
+                        ErrorKind::Malformed,
+                        "`users[].password` should be base64 encoded",
+                        None,
+                    );
+                    return Err(err);
+                }
+            };
+
+            user_manager.add_user(user);
+        }
+
+        nsvr.set_user_manager(user_manager);
+    }
+
+    match svr.mode {
+        Some(mode) => match mode.parse::<Mode>() {
+            Ok(mode) => nsvr.set_mode(mode),
+            Err(..) => {
+                let err = Error::new(ErrorKind::Invalid, "invalid `mode`", None);
+                return Err(err);
+            }
+        },
+        None => {
+            // Server will derive mode from the global scope
+            if matches!(config_type, ConfigType::Server | ConfigType::Manager) {
+                nsvr.set_mode(global_mode);
+            }
+        }
+    }
+
+    if let Some(p) = svr.plugin {
+        // SIP008 allows "plugin" to be an empty string
+        // Empty string implies "no plugin"
+        if !p.is_empty() {
+            let plugin = PluginConfig {
+                plugin: p,
+                plugin_opts: svr.plugin_opts,
+                plugin_args: svr.plugin_args.unwrap_or_default(),
+                plugin_mode: match svr.plugin_mode {
+                    None => Mode::TcpOnly,
+                    Some(ref mode) => match mode.parse::<Mode>() {
+                        Ok(m) => m,
+                        Err(..) => {
+                            let e = Error::new(
+                                ErrorKind::Malformed,
+                                "malformed `plugin_mode`, must be one of `tcp_only`, `udp_only` and `tcp_and_udp`",
+                                None,
+                            );
+                            return Err(e);
+                        }
+                    },
+                },
+            };
+            nsvr.set_plugin(plugin);
+        }
+    }
+
+    set_server_timeouts(
+        &mut nsvr,
+        [svr.timeout, svr.connect_timeout, svr.idle_timeout],
+        global_timeouts,
+    );
+
+    let max_connections = match svr.max_connections {
+        Some(n) => Some(check_max_connections(n)?),
+        None => global_max_connections,
+    };
+    if let Some(n) = max_connections {
+        nsvr.set_max_connections(n);
+    }
+
+    set_server_retries(&mut nsvr, [svr.max_retries, svr.retry_backoff], global_retries)?;
+
+    if let Some(m) = svr.fallback_method.as_ref().or(global_fallback_method) {
+        set_server_fallback_method(&mut nsvr, m)?;
+    }
+
+    if let Some(kdf) = svr.kdf.as_ref().or(global_kdf) {
+        set_server_kdf(&mut nsvr, kdf)?;
+    }
+
+    set_server_validity(
+        &mut nsvr,
+        svr.valid_from.as_deref().or(global_valid_from),
+        svr.valid_until.as_deref().or(global_valid_until),
+    )?;
+
+    if let Some(remarks) = svr.remarks {
+        nsvr.set_remarks(remarks);
+    }
+
+    if let Some(id) = svr.id {
+        nsvr.set_id(id);
+    }
+
+    if let Some(group) = svr.group {
+        nsvr.set_group(group);
+    }
+
+    if svr.tcp_weight.is_some() || svr.udp_weight.is_some() {
+        let tcp_weight = svr.tcp_weight.unwrap_or(1.0);
+        if !(0.0..=1.0).contains(&tcp_weight) {
+            let err = Error::new(ErrorKind::Invalid, "invalid `tcp_weight`, must be in [0, 1]", None);
+            return Err(err);
+        }
+        let udp_weight = svr.udp_weight.unwrap_or(1.0);
+        if !(0.0..=1.0).contains(&udp_weight) {
+            let err = Error::new(ErrorKind::Invalid, "invalid `udp_weight`, must be in [0, 1]", None);
+            return Err(err);
+        }
+        let mut weight = ServerWeight::new();
+        weight.set_tcp_weight(tcp_weight);
+        weight.set_udp_weight(udp_weight);
+        nsvr.set_weight(weight);
+    }
+
+    let mut server_instance = ServerInstanceConfig {
+        config: nsvr,
+        acl: None,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        outbound_fwmark,
+        outbound_bind_addr,
+        outbound_bind_interface: outbound_bind_interface.cloned(),
+        outbound_udp_allow_fragmentation,
+        outbound_proxy: None,
+    };
+
+    if let Some(acl_path) = svr.acl {
+        let acl = match AccessControl::load_from_file(&acl_path) {
+            Ok(acl) => acl,
+            Err(err) => {
+                let err = Error::new(
+                    ErrorKind::IoError,
+                    "acl loading failed",
+                    Some(format!("file {acl_path}, error: {err}")),
+                );
+                return Err(err);
+            }
+        };
+        server_instance.acl = Some(acl);
+    }
+
+    if let Some(fwmark) = svr.outbound_fwmark {
+        let fwmark = check_outbound_fwmark(fwmark)?;
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            server_instance.outbound_fwmark = Some(fwmark);
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        warn!(
+            "`outbound_fwmark` {} is only supported on Linux and Android, ignored",
+            fwmark
+        );
+    }
+
+    if let Some(outbound_bind_addr) = svr.outbound_bind_addr {
+        server_instance.outbound_bind_addr = Some(outbound_bind_addr);
+    }
+
+    if let Some(ref outbound_bind_interface) = svr.outbound_bind_interface {
+        check_outbound_bind_interface(outbound_bind_interface)?;
+        server_instance.outbound_bind_interface = Some(outbound_bind_interface.clone());
+    }
+
+    if let Some(outbound_udp_allow_fragmentation) = svr.outbound_udp_allow_fragmentation {
+        server_instance.outbound_udp_allow_fragmentation = Some(outbound_udp_allow_fragmentation);
+    }
+
+    if let Some(outbound_socks5) = svr.outbound_socks5 {
+        server_instance.outbound_proxy = Some(parse_outbound_socks5(outbound_socks5)?);
+    }
+
+    push_server_instance_ports(servers, server_instance, &ports);
+    Ok(())
+}
+
+/// Push a copy of `server_instance` for every port in `ports`, they only differ in port
+fn push_server_instance_ports(
+    servers: &mut Vec<ServerInstanceConfig>,
+    server_instance: ServerInstanceConfig,
+    ports: &[u16],
+) {
+    for &port in ports {
+        let mut server_instance = server_instance.clone();
+        let addr = match server_instance.config.addr() {
+            ServerAddr::SocketAddr(sa) => ServerAddr::SocketAddr(SocketAddr::new(sa.ip(), port)),
+            ServerAddr::DomainName(dn, ..) => ServerAddr::DomainName(dn.clone(), port),
+        };
+        server_instance.config.set_addr(addr);
+        servers.push(server_instance);
+    }
+}
+
+/// Parse an IP address or a CIDR network
+fn parse_ip_net(s: &str) -> Option<IpNet> {
+    match s.parse::<IpNet>() {
+        Ok(net) => Some(net),
+        Err(..) => s.parse::<IpAddr>().ok().map(IpNet::from),
+    }
+}
+
+/// Expand the leading `~` or `~user` of `path` and make it absolute
+fn expand_config_path(path: &Path) -> io::Result<PathBuf> {
+    let path = match path.to_str().and_then(|p| p.strip_prefix('~')) {
+        None => path.to_owned(),
+        Some(left_over) => {
+            let (user, rest) = match left_over.find(['/', std::path::MAIN_SEPARATOR]) {
+                Some(pos) => (&left_over[..pos], &left_over[pos + 1..]),
+                None => (left_over, ""),
+            };
+
+            let home = if user.is_empty() {
+                current_home_dir()
+            } else {
+                user_home_dir(user)
+            };
+
+            match home {
+                Some(home) => home.join(rest),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("couldn't find home directory of \"~{user}\""),
+                    ));
+                }
+            }
+        }
+    };
+
+    if path.is_absolute() {
+        Ok(path)
+    } else {
+        Ok(env::current_dir()?.join(path))
+    }
+}
+
+fn current_home_dir() -> Option<PathBuf> {
+    #[cfg(windows)]
+    let home = env::var_os("USERPROFILE");
+    #[cfg(not(windows))]
+    let home = env::var_os("HOME");
+
+    match home {
+        Some(home) if !home.is_empty() => Some(PathBuf::from(home)),
+        _ => None,
+    }
+}
+
+#[cfg(unix)]
+fn user_home_dir(user: &str) -> Option<PathBuf> {
+    use std::{
+        ffi::{CStr, CString, OsStr},
+        mem,
+        os::unix::ffi::OsStrExt,
+        ptr,
+    };
+
+    let name = CString::new(user).ok()?;
+    let mut buf = vec![0 as libc::c_char; 16384];
+    let mut pwd: libc::passwd = unsafe { mem::zeroed() };
+    let mut result: *mut libc::passwd = ptr::null_mut();
+
+    let ret = unsafe { libc::getpwnam_r(name.as_ptr(), &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if ret != 0 || result.is_null() || pwd.pw_dir.is_null() {
+        return None;
+    }
+
+    let dir = unsafe { CStr::from_ptr(pwd.pw_dir) };
+    Some(PathBuf::from(OsStr::from_bytes(dir.to_bytes())))
+}
+
+#[cfg(not(unix))]
+fn user_home_dir(_user: &str) -> Option<PathBuf> {
+    None
+}
+
+#[cfg(test)]
+mod test;
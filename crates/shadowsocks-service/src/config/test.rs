@@ -0,0 +1,2285 @@
+use super::*;
+
+/// Loads `keys` on top of a single `"none"` server at `127.0.0.1:8388`
+fn load_with(keys: &str, config_type: ConfigType) -> Result<Config> {
+    let sep = if keys.is_empty() { "" } else { ", " };
+    Config::load_from_str(
+        &format!(r#"{{"server": "127.0.0.1", "server_port": 8388, "method": "none"{sep}{keys}}}"#),
+        config_type,
+    )
+}
+
+#[test]
+fn test_config_json_error_location() {
+    let err = Config::load_from_str(
+        "{\n    \"server\": \"127.0.0.1\",\n    \"server_port\" 8388\n}",
+        ConfigType::Server,
+    )
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::JsonParsingError);
+    assert!(err.detail.as_ref().unwrap().starts_with("line 3, column "), "{err}");
+}
+
+#[test]
+fn test_config_error_kind() {
+    let err = Config::load_from_str("{", ConfigType::Server).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::JsonParsingError);
+
+    let err = Config::load_from_str(
+        r#"{"server": "127.0.0.1", "server_port": 8388, "method": "aes-256-gcm"}"#,
+        ConfigType::Server,
+    )
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::MissingField);
+
+    let err = Config::load_from_str(r#"{"server": "127.0.0.1", "method": "none"}"#, ConfigType::Server).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Malformed);
+
+    let err = Config::load_from_str(
+        r#"{"server": "127.0.0.1", "server_port": 8388, "method": "foo-bar", "password": "x"}"#,
+        ConfigType::Server,
+    )
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+}
+
+#[test]
+fn test_config_local_servers() {
+    let config = Config::load_from_str(
+        r#"{
+            "locals": [
+                {"local_address": "127.0.0.1", "local_port": 1080, "servers": [1]},
+                {"local_address": "127.0.0.1", "local_port": 1081}
+            ],
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "method": "none"},
+                {"server": "127.0.0.1", "server_port": 8389, "method": "none"}
+            ]
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+    config.check_integrity().unwrap();
+    assert_eq!(config.local[0].servers, Some(vec![1]));
+    assert_eq!(config.local[1].servers, None);
+
+    let config = Config::load_from_str(
+        r#"{
+            "locals": [{"local_address": "127.0.0.1", "local_port": 1080, "servers": [2]}],
+            "servers": [{"server": "127.0.0.1", "server_port": 8388, "method": "none"}]
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+    let err = config.check_integrity().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+}
+
+#[test]
+fn test_config_save_to_file() {
+    let config = Config::load_from_str(
+        r#"{"server": "127.0.0.1", "server_port": 8388, "method": "aes-256-gcm", "password": "p@ssw0rd"}"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    let path = env::temp_dir().join(format!("ss-config-save-{}.json", std::process::id()));
+    config.save_to_file(&path).unwrap();
+    let loaded = Config::load_from_file(&path, ConfigType::Server);
+    let _ = fs::remove_file(&path);
+
+    let loaded = loaded.unwrap();
+    assert_eq!(loaded.server.len(), 1);
+    assert_eq!(loaded.server[0].config.addr(), config.server[0].config.addr());
+    assert_eq!(loaded.server[0].config.method(), CipherKind::AES_256_GCM);
+    assert_eq!(loaded.server[0].config.password(), "p@ssw0rd");
+}
+
+#[test]
+fn test_config_load_profile() {
+    let s = r#"{
+        "profiles": {
+            "dev": {"server": "127.0.0.1", "server_port": 8388, "method": "none"},
+            "prod": {"server": "127.0.0.1", "server_port": 9388, "method": "none"}
+        }
+    }"#;
+
+    let config = Config::load_profile_from_str(s, "prod", ConfigType::Server).unwrap();
+    assert_eq!(config.server.len(), 1);
+    assert_eq!(config.server[0].config.addr().port(), 9388);
+
+    let err = Config::load_profile_from_str(s, "staging", ConfigType::Server).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+}
+
+#[test]
+fn test_config_connect_idle_timeout() {
+    let config = Config::load_from_str(
+        r#"{
+            "timeout": 300,
+            "connect_timeout": 10,
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "method": "none"},
+                {"server": "127.0.0.1", "server_port": 8389, "method": "none", "timeout": 5},
+                {"server": "127.0.0.1", "server_port": 8390, "method": "none", "idle_timeout": 60}
+            ]
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    let svr = &config.server[0].config;
+    assert_eq!(svr.connect_timeout(), Some(Duration::from_secs(10)));
+    assert_eq!(svr.idle_timeout(), Some(Duration::from_secs(300)));
+
+    let svr = &config.server[1].config;
+    assert_eq!(svr.connect_timeout(), Some(Duration::from_secs(5)));
+    assert_eq!(svr.idle_timeout(), Some(Duration::from_secs(5)));
+
+    let svr = &config.server[2].config;
+    assert_eq!(svr.connect_timeout(), Some(Duration::from_secs(10)));
+    assert_eq!(svr.idle_timeout(), Some(Duration::from_secs(60)));
+}
+
+#[test]
+fn test_config_server_by_remarks() {
+    let config = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "method": "none", "remarks": "tokyo-1"},
+                {"server": "127.0.0.1", "server_port": 8389, "method": "none", "remarks": "osaka-1"}
+            ]
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    assert_eq!(config.server_by_remarks("osaka-1").unwrap().addr().port(), 8389);
+    assert_eq!(config.server_by_remarks("tokyo-1").unwrap().addr().port(), 8388);
+    assert!(config.server_by_remarks("Tokyo-1").is_none());
+    assert!(config.server_by_remarks("tokyo").is_none());
+}
+
+#[test]
+fn test_config_server_port_range() {
+    let config = Config::load_from_str(
+        r#"{
+            "server": "0.0.0.0", "server_port": "40000-40002", "method": "aes-256-gcm", "password": "password",
+            "servers": [
+                {"server": "0.0.0.0", "server_port": 8388, "method": "none"},
+                {"server": "0.0.0.0", "server_port": " 50000 - 50001 ", "method": "none"}
+            ]
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    let ports: Vec<u16> = config.server.iter().map(|inst| inst.config.addr().port()).collect();
+    assert_eq!(ports, [40000, 40001, 40002, 8388, 50000, 50001]);
+    assert!(config.server[..3]
+        .iter()
+        .all(|inst| inst.config.password() == "password" && inst.config.method() == CipherKind::AES_256_GCM));
+
+    let config = Config::load_from_str(
+        r#"{"server": "0.0.0.0", "server_port": "40000-41023", "method": "none"}"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+    assert_eq!(config.server.len(), MAX_SERVER_PORT_RANGE);
+
+    for (server_port, kind) in [
+        (r#""40010-40000""#, ErrorKind::Invalid),
+        (r#""1-65535""#, ErrorKind::Invalid),
+        (r#""40000-41024""#, ErrorKind::Invalid),
+        (r#""40000-70000""#, ErrorKind::Malformed),
+        (r#""40000-""#, ErrorKind::Malformed),
+    ] {
+        let err = Config::load_from_str(
+            &format!(r#"{{"server": "0.0.0.0", "server_port": {server_port}, "method": "none"}}"#),
+            ConfigType::Server,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind, kind, "{server_port}");
+    }
+}
+
+#[test]
+fn test_config_disable_ipv6() {
+    let config = Config::load_from_str(
+        r#"{"server": "0.0.0.0", "server_port": 8388, "method": "none", "ipv6_first": true, "disable_ipv6": true}"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+    assert!(config.ipv6_first);
+    assert!(config.disable_ipv6);
+    config.check_integrity().unwrap();
+
+    let config = Config::load_from_str(
+        r#"{"server": "::", "server_port": 8388, "method": "none", "disable_ipv6": true}"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+    assert_eq!(config.check_integrity().unwrap_err().kind, ErrorKind::Invalid);
+
+    // Default local address falls back to IPv4 loopback
+    let config = load_with(
+        r#""locals": [{"local_port": 1080}], "ipv6_first": true, "disable_ipv6": true"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+    assert_eq!(
+        config.local[0].config.addr,
+        Some(ServerAddr::SocketAddr("127.0.0.1:1080".parse().unwrap()))
+    );
+}
+
+#[test]
+fn test_method_udp_issue() {
+    assert!(method_udp_issue(CipherKind::NONE).is_none());
+    assert!(method_udp_issue(CipherKind::AES_256_GCM).is_none());
+    #[cfg(feature = "stream-cipher")]
+    assert!(method_udp_issue(CipherKind::AES_256_CTR).is_some());
+}
+
+#[test]
+fn test_config_acl() {
+    let path = env::temp_dir().join(format!("ss-acl-{}.acl", std::process::id()));
+    fs::write(
+        &path,
+        "[proxy_all]\n\n  # comment\n[bypass_list]\n10.0.0.0/8\n(malformed\n||example.com\n",
+    )
+    .unwrap();
+
+    let config = load_with(&format!(r#""acl": {:?}"#, path.to_str().unwrap()), ConfigType::Local);
+    let _ = fs::remove_file(&path);
+
+    let acl = config.unwrap().acl.unwrap();
+    assert!(!acl.check_ip_in_proxy_list(&"10.1.1.1".parse().unwrap()));
+    assert!(acl.check_ip_in_proxy_list(&"8.8.8.8".parse().unwrap()));
+    assert_eq!(acl.check_host_in_proxy_list("www.example.com"), Some(false));
+
+    let err = load_with(r#""acl": "/nonexist/file.acl""#, ConfigType::Local).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::IoError);
+}
+
+#[test]
+fn test_config_forbidden_ip_file() {
+    let path = env::temp_dir().join(format!("ss-forbidden-ip-{}.txt", std::process::id()));
+    fs::write(&path, "# blocklist\n\n10.0.0.0/8\n  192.168.1.1  \n::1\n").unwrap();
+
+    let keys = format!(
+        r#""forbidden_ip": ["127.0.0.0/8"], "forbidden_ip_file": {:?}"#,
+        path.to_str().unwrap()
+    );
+    let config = load_with(&keys, ConfigType::Server);
+    let _ = fs::remove_file(&path);
+
+    let forbidden_ip: Vec<String> = config.unwrap().forbidden_ip.iter().map(ToString::to_string).collect();
+    assert_eq!(forbidden_ip, ["127.0.0.0/8", "10.0.0.0/8", "192.168.1.1/32", "::1/128"]);
+
+    let err = load_with(r#""forbidden_ip_file": "/nonexist/blocklist.txt""#, ConfigType::Server).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::IoError);
+}
+
+#[test]
+fn test_config_servers_inherit_method_password() {
+    let config = Config::load_from_str(
+        r#"{
+            "method": "aes-256-gcm",
+            "password": "shared-password",
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388},
+                {"server": "127.0.0.1", "server_port": 8389, "method": "chacha20-ietf-poly1305", "password": "own-password"}
+            ]
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    assert_eq!(config.server.len(), 2);
+
+    let svr = &config.server[0].config;
+    assert_eq!(svr.method(), CipherKind::AES_256_GCM);
+    assert_eq!(svr.password(), "shared-password");
+
+    let svr = &config.server[1].config;
+    assert_eq!(svr.method(), CipherKind::CHACHA20_POLY1305);
+    assert_eq!(svr.password(), "own-password");
+
+    let err = Config::load_from_str(
+        r#"{"servers": [{"server": "127.0.0.1", "server_port": 8388}]}"#,
+        ConfigType::Server,
+    )
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::MissingField);
+}
+
+#[test]
+fn test_config_servers_inherit_root_defaults() {
+    let config = Config::load_from_str(
+        r#"{
+            "method": "aes-256-gcm",
+            "password": "shared-password",
+            "timeout": 300,
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388},
+                {"server": "127.0.0.1", "server_port": 8389, "timeout": 0}
+            ]
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    let svr = &config.server[0].config;
+    assert_eq!(svr.method(), CipherKind::AES_256_GCM);
+    assert_eq!(svr.password(), "shared-password");
+    assert_eq!(svr.timeout(), Some(Duration::from_secs(300)));
+
+    let svr = &config.server[1].config;
+    assert_eq!(svr.timeout(), None);
+}
+
+#[test]
+fn test_config_local_address_defaults() {
+    let config = load_with(
+        r#"
+            "local_port": 1081,
+            "locals": [{"local_address": "0.0.0.0"}, {"local_port": 1082}]
+        "#,
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    let addrs: Vec<Option<ServerAddr>> = config.local.iter().map(|l| l.config.addr.clone()).collect();
+    assert_eq!(
+        addrs,
+        [
+            Some(ServerAddr::SocketAddr("127.0.0.1:1081".parse().unwrap())),
+            Some(ServerAddr::SocketAddr(
+                format!("0.0.0.0:{DEFAULT_LOCAL_PORT}").parse().unwrap()
+            )),
+            Some(ServerAddr::SocketAddr("127.0.0.1:1082".parse().unwrap())),
+        ]
+    );
+
+    // Port assigned by the system
+    let config = load_with(r#""local_port": 0"#, ConfigType::Local).unwrap();
+    assert_eq!(
+        config.local[0]
+            .config
+            .addr
+            .as_ref()
+            .and_then(ServerAddr::as_socket_addr)
+            .map(SocketAddr::port),
+        Some(0)
+    );
+    config.check_integrity().unwrap();
+}
+
+#[test]
+fn test_config_local_address_domain() {
+    let config = load_with(
+        r#""locals": [{"local_address": "localhost", "local_port": 1080}]"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    assert_eq!(
+        config.local[0].config.addr,
+        Some(ServerAddr::DomainName("localhost".to_owned(), 1080))
+    );
+}
+
+#[test]
+fn test_config_limit_rate() {
+    for (limit_rate, expected) in [
+        ("1048576", 1048576),
+        ("\"512\"", 512),
+        ("\"1MB\"", 1024 * 1024),
+        ("\"64k\"", 64 * 1024),
+        ("\"10mbit\"", 1_250_000),
+        ("\"1 Gbit\"", 125_000_000),
+    ] {
+        let config = load_with(&format!(r#""limit_rate": {limit_rate}"#), ConfigType::Server).unwrap();
+        assert_eq!(config.max_rate_bytes_per_sec, Some(expected), "{limit_rate}");
+    }
+
+    for limit_rate in ["0", "\"10 lightyears\"", "\"fast\"", "\"99999999999999999999G\""] {
+        let err = load_with(&format!(r#""limit_rate": {limit_rate}"#), ConfigType::Server).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Invalid, "{limit_rate}");
+    }
+}
+
+#[test]
+fn test_config_servers_url() {
+    let config = Config::load_from_str(
+        r#"{
+            "servers": [
+                "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@127.0.0.1:8388#url-server",
+                {"server": "127.0.0.1", "server_port": 8389, "method": "chacha20-ietf-poly1305", "password": "password"}
+            ]
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    assert_eq!(config.server.len(), 2);
+    let svr = &config.server[0].config;
+    assert_eq!(svr.addr(), &ServerAddr::SocketAddr("127.0.0.1:8388".parse().unwrap()));
+    assert_eq!(svr.method(), CipherKind::AES_256_GCM);
+    assert_eq!(svr.password(), "password");
+    assert_eq!(svr.remarks(), Some("url-server"));
+    assert_eq!(config.server[1].config.method(), CipherKind::CHACHA20_POLY1305);
+
+    // Global settings of servers are applied to URL entries as well
+    let config = Config::load_from_str(
+        r#"{
+            "timeout": 10, "fallback_method": "aes-128-gcm", "valid_until": "2000-01-01T00:00:00Z",
+            "servers": ["ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@127.0.0.1:8388"]
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+    let svr = &config.server[0].config;
+    assert_eq!(svr.connect_timeout(), Some(Duration::from_secs(10)));
+    assert_eq!(svr.fallback_method(), Some(CipherKind::AES_128_GCM));
+    assert!(!svr.is_currently_valid(SystemTime::now()));
+
+    let err = Config::load_from_str(r#"{"servers": ["http://127.0.0.1:8388"]}"#, ConfigType::Local).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Malformed);
+}
+
+#[test]
+fn test_config_route_for() {
+    let config = load_with(
+        r#"
+            "bypass_ip": ["10.0.0.0/8", "192.168.1.0/24", "172.16.0.0/12"],
+            "proxy_ip": ["10.1.0.0/16", "192.168.0.0/16", "172.16.0.0/12", "2001:db8::/32"]
+        "#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    let route = |s: &str| config.route_for(&s.parse::<IpAddr>().unwrap());
+
+    assert_eq!(route("10.2.3.4"), Route::Direct);
+    // More specific proxy network inside bypass network
+    assert_eq!(route("10.1.2.3"), Route::Proxy);
+    // More specific bypass network inside proxy network
+    assert_eq!(route("192.168.1.1"), Route::Direct);
+    assert_eq!(route("192.168.2.1"), Route::Proxy);
+    // Same network in both lists
+    assert_eq!(route("172.16.0.1"), Route::Direct);
+    assert_eq!(route("::ffff:10.2.3.4"), Route::Direct);
+    assert_eq!(route("2001:db8::1"), Route::Proxy);
+    assert_eq!(route("8.8.8.8"), Route::Default);
+
+    let err = load_with(r#""bypass_ip": ["10.0.0.0/33"]"#, ConfigType::Server).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Malformed);
+}
+
+#[test]
+fn test_config_redacted_display() {
+    let config = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "method": "aes-256-gcm", "password": "server-secret-password"},
+                {"server": "127.0.0.1", "server_port": 8389, "method": "chacha20-ietf-poly1305", "password": "another-secret-password", "plugin": "v2ray-plugin", "plugin_opts": "server;password=plugin-secret-password"}
+            ],
+            "outbound_socks5": {"address": "127.0.0.1:1080", "username": "socks-user", "password": "socks-secret-password"},
+            "pasword": "misspelled-secret-password"
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    let output = config.redacted_display().to_string();
+    for secret in [
+        "server-secret-password",
+        "another-secret-password",
+        "socks-secret-password",
+        "plugin-secret-password",
+        "misspelled-secret-password",
+    ] {
+        assert!(!output.contains(secret), "{secret} found in {output}");
+    }
+    assert!(output.contains("socks-user"));
+
+    let value: serde_json::Value = json5::from_str(&output).unwrap();
+    assert_eq!(value["servers"][0]["password"], "***");
+    assert_eq!(value["servers"][0]["method"], "aes-256-gcm");
+    assert_eq!(value["servers"][1]["plugin"], "v2ray-plugin");
+    assert_eq!(value["pasword"], "***");
+}
+
+#[test]
+fn test_config_log_level() {
+    let err = load_with(r#""log_level": "verbose""#, ConfigType::Server).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+}
+
+#[test]
+fn test_config_dns_cache_size_clamp() {
+    let load = |size: usize| {
+        let keys = format!(r#""dns_cache_size": {size}"#);
+        load_with(&keys, ConfigType::Server).unwrap().dns_cache_size
+    };
+
+    assert_eq!(load(0), Some(0));
+    assert_eq!(load(MAX_DNS_CACHE_SIZE), Some(MAX_DNS_CACHE_SIZE));
+    assert_eq!(load(MAX_DNS_CACHE_SIZE + 1), Some(MAX_DNS_CACHE_SIZE));
+    assert_eq!(load(100000000), Some(MAX_DNS_CACHE_SIZE));
+}
+
+#[test]
+fn test_config_dns_cache_disabled() {
+    let load = |dns: &str| load_with(dns, ConfigType::Server).unwrap().dns_cache_size;
+
+    assert_eq!(load(r#""dns_cache_capacity": 0"#), Some(0));
+    assert_eq!(load(r#""dns_cache": false"#), Some(0));
+    assert_eq!(load(r#""dns_cache": false, "dns_cache_size": 1024"#), Some(0));
+    assert_eq!(load(r#""dns_cache": true, "dns_cache_size": 1024"#), Some(1024));
+    assert_eq!(load(r#""dns_cache": true"#), None);
+}
+
+#[test]
+fn test_config_local_requirement() {
+    // Local servers could be added after loading, for example, from command line arguments
+    let config = load_with("", ConfigType::Local).unwrap();
+    assert!(config.local.is_empty());
+    let err = config.check_integrity().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::MissingField);
+
+    let err = load_with(r#""locals": [{"mode": "tcp_and_udp"}]"#, ConfigType::Local).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::MissingField);
+}
+
+#[test]
+fn test_expand_config_path() {
+    let path = expand_config_path(Path::new("config.json")).unwrap();
+    assert_eq!(path, env::current_dir().unwrap().join("config.json"));
+
+    if let Some(home) = current_home_dir() {
+        let path = expand_config_path(Path::new("~/.config/ss.json")).unwrap();
+        assert_eq!(path, home.join(".config/ss.json"));
+    }
+}
+
+#[cfg(feature = "local-http")]
+#[test]
+fn test_config_local_http_auth() {
+    let config = load_with(
+        r#"
+            "locals": [{
+                "protocol": "http",
+                "local_address": "127.0.0.1",
+                "local_port": 3128,
+                "local_http_username": "user",
+                "local_http_password": "p@ss:word"
+            }]
+        "#,
+        ConfigType::Local,
+    )
+    .unwrap();
+    assert_eq!(
+        config.local[0].config.http_proxy_auth,
+        Some(("user".to_owned(), "p@ss:word".to_owned()))
+    );
+
+    let redacted = config.redacted_display().to_string();
+    assert!(!redacted.contains("p@ss:word"));
+    assert!(redacted.contains("\"user\""));
+
+    let reloaded = Config::load_from_str(&config.to_string(), ConfigType::Local).unwrap();
+    assert_eq!(
+        reloaded.local[0].config.http_proxy_auth,
+        config.local[0].config.http_proxy_auth
+    );
+
+    let err = load_with(
+        r#""locals": [{"protocol": "http", "local_port": 3128, "local_http_username": "user"}]"#,
+        ConfigType::Local,
+    )
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::MissingField);
+
+    // HTTP requests are also sniffed by socks locals
+    let config = load_with(
+        r#""locals": [{"protocol": "auto", "local_port": 1080, "local_http_username": "user", "local_http_password": "p"}]"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+    assert!(config.local[0].config.http_proxy_auth.is_some());
+
+    #[cfg(feature = "local-tunnel")]
+    {
+        let err = load_with(
+            r#"
+                "locals": [{
+                    "protocol": "tunnel", "local_port": 5353, "forward_address": "8.8.8.8", "forward_port": 53,
+                    "local_http_username": "user", "local_http_password": "p"
+                }]
+            "#,
+            ConfigType::Local,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Invalid);
+    }
+}
+
+#[test]
+fn test_config_diff() {
+    let old = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "method": "none", "password": ""},
+                {"server": "127.0.0.1", "server_port": 8389, "method": "chacha20-ietf-poly1305", "password": "old-secret"},
+                {"server": "127.0.0.1", "server_port": 8390, "method": "none", "password": ""}
+            ],
+            "forbidden_ip": ["10.0.0.0/8", "192.168.0.0/16"],
+            "no_delay": false
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+    assert!(old.diff(&old).is_empty());
+
+    let new = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8389, "method": "chacha20-ietf-poly1305", "password": "new-secret"},
+                {"server": "127.0.0.1", "server_port": 8390, "method": "none", "password": ""},
+                {"server": "127.0.0.1", "server_port": 8391, "method": "none", "password": ""}
+            ],
+            "forbidden_ip": ["10.0.0.0/8", "172.16.0.0/12"],
+            "no_delay": true
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    let diff = old.diff(&new);
+    assert_eq!(diff.added_servers, ["127.0.0.1:8391".parse::<ServerAddr>().unwrap()]);
+    assert_eq!(diff.removed_servers, ["127.0.0.1:8388".parse::<ServerAddr>().unwrap()]);
+    assert_eq!(
+        diff.changed_servers,
+        [(
+            "127.0.0.1:8389".parse::<ServerAddr>().unwrap(),
+            vec!["password changed".to_owned()]
+        )]
+    );
+    assert_eq!(
+        diff.changed_fields,
+        [("no_delay".to_owned(), "default".to_owned(), "true".to_owned())]
+    );
+    assert_eq!(diff.added_forbidden_ip, ["172.16.0.0/12".parse::<IpNet>().unwrap()]);
+    assert_eq!(diff.removed_forbidden_ip, ["192.168.0.0/16".parse::<IpNet>().unwrap()]);
+
+    let debug = format!("{diff:?}");
+    assert!(!debug.contains("old-secret") && !debug.contains("new-secret"));
+
+    // Every serialized key is compared, including locals
+    let old = load_with(r#""local_address": "127.0.0.1", "local_port": 1080"#, ConfigType::Local).unwrap();
+    let new = load_with(
+        r#"
+            "idle_timeout": 60, "max_connections": 10,
+            "local_address": "127.0.0.1", "local_port": 1081,
+            "shutdown_timeout": 5
+        "#,
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    let diff = old.diff(&new);
+    assert_eq!(
+        diff.changed_servers,
+        [(
+            "127.0.0.1:8388".parse::<ServerAddr>().unwrap(),
+            vec![
+                "idle_timeout default -> 60".to_owned(),
+                "max_connections default -> 10".to_owned()
+            ]
+        )]
+    );
+    let mut changed_keys = diff
+        .changed_fields
+        .iter()
+        .map(|(key, ..)| key.as_str())
+        .collect::<Vec<_>>();
+    changed_keys.sort_unstable();
+    assert_eq!(changed_keys, ["local_port", "shutdown_timeout"]);
+}
+
+#[test]
+fn test_config_servers_in_group() {
+    let config = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "method": "none", "group": "us"},
+                {"server": "127.0.0.1", "server_port": 8389, "method": "none", "group": "eu"},
+                {"server": "127.0.0.1", "server_port": 8390, "method": "none", "group": "us"},
+                {"server": "127.0.0.1", "server_port": 8391, "method": "none"}
+            ]
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    let ports = |group| {
+        config
+            .servers_in_group(group)
+            .into_iter()
+            .map(|svr| svr.addr().port())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ports("us"), [8388, 8390]);
+    assert_eq!(ports("eu"), [8389]);
+    assert!(ports("US").is_empty());
+
+    let reloaded = Config::load_from_str(&config.to_string(), ConfigType::Local).unwrap();
+    assert_eq!(reloaded.server[1].config.group(), Some("eu"));
+    assert_eq!(reloaded.server[3].config.group(), None);
+}
+
+#[test]
+fn test_location_byte_offset() {
+    let s = "{\n  \"a\": 1,\n  \u{e9}x\n}";
+    assert_eq!(location_byte_offset(s, 1, 1), Some(0));
+    assert_eq!(location_byte_offset(s, 2, 3), Some(4));
+    assert_eq!(location_byte_offset(s, 3, 4), Some(16));
+    assert_eq!(location_byte_offset(s, 0, 1), None);
+}
+
+#[cfg(feature = "aead-cipher-2022")]
+#[test]
+fn test_config_aead_2022_key_length() {
+    // Keys are base64 encoded bytes of 0x01, and longer ones with 1 extra byte
+    let key16 = "AQEBAQEBAQEBAQEBAQEBAQ==";
+    let key17 = "AQEBAQEBAQEBAQEBAQEBAQE=";
+    let key32 = "AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE=";
+    let key33 = "AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEB";
+
+    for (method, key_len, key, bad_key) in [
+        ("2022-blake3-aes-128-gcm", 16, key16, key17),
+        ("2022-blake3-aes-256-gcm", 32, key32, key33),
+        ("2022-blake3-chacha20-poly1305", 32, key32, key33),
+    ] {
+        let kind = parse_method(method).unwrap();
+        assert_eq!(kind.key_len(), key_len);
+
+        let load = |password: &str| {
+            Config::load_from_str(
+                &format!(
+                    r#"{{"server": "127.0.0.1", "server_port": 8388, "method": "{method}", "password": "{password}"}}"#
+                ),
+                ConfigType::Server,
+            )
+        };
+
+        let config = load(key).unwrap();
+        assert_eq!(config.server[0].config.method(), kind);
+
+        let err = load(bad_key).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Invalid);
+        assert!(err.detail.unwrap().contains(&format!("expecting {key_len} bytes")));
+    }
+}
+
+#[test]
+fn test_config_type_of_loaded_config() {
+    let config = load_with(r#""local_port": 1080"#, ConfigType::Local).unwrap();
+    assert_eq!(config.config_type, ConfigType::Local);
+    assert!(config.is_local() && !config.is_server());
+
+    let config = load_with(r#""local_port": 1080"#, ConfigType::Server).unwrap();
+    assert_eq!(config.config_type, ConfigType::Server);
+    assert!(config.is_server() && !config.is_local());
+}
+
+#[cfg(feature = "hickory-dns")]
+#[test]
+fn test_config_dns_list() {
+    use hickory_resolver::proto::xfer::Protocol;
+
+    let load = |dns: &str| load_with(&format!(r#""dns": {dns}"#), ConfigType::Server);
+
+    let name_servers = |config: &Config| {
+        let DnsConfig::HickoryDns(ref c) = config.dns else {
+            panic!("unexpected dns {:?}", config.dns);
+        };
+        c.name_servers()
+            .iter()
+            .map(|ns| (ns.socket_addr.to_string(), ns.protocol))
+            .collect::<Vec<_>>()
+    };
+
+    // Protocol prefix applies to every entry of an array, but to the whole comma separated list
+    let config = load(r#"["8.8.8.8", "tcp://1.1.1.1:5353"]"#).unwrap();
+    assert_eq!(
+        name_servers(&config),
+        [
+            ("8.8.8.8:53".to_owned(), Protocol::Udp),
+            ("8.8.8.8:53".to_owned(), Protocol::Tcp),
+            ("1.1.1.1:5353".to_owned(), Protocol::Tcp),
+        ]
+    );
+    assert!(config.dns_in_order);
+    let config = load(r#""tcp://8.8.8.8,1.1.1.1:5353""#).unwrap();
+    assert!(!config.dns_in_order);
+    assert_eq!(
+        name_servers(&config),
+        [
+            ("8.8.8.8:53".to_owned(), Protocol::Tcp),
+            ("1.1.1.1:5353".to_owned(), Protocol::Tcp),
+        ]
+    );
+
+    assert_eq!(
+        load(r#"["8.8.8.8", "8.8.8.8.8"]"#).unwrap_err().kind,
+        ErrorKind::Invalid
+    );
+    assert_eq!(load(r#"["8.8.8.8", "system"]"#).unwrap_err().kind, ErrorKind::Invalid);
+    assert_eq!(load("[]").unwrap_err().kind, ErrorKind::Invalid);
+}
+
+#[test]
+fn test_config_manager_address() {
+    let load = |manager: &str| {
+        Config::load_from_str(&format!(r#"{{"method": "none", {manager}}}"#), ConfigType::Manager)
+            .map(|config| config.manager.unwrap().addr)
+    };
+
+    let addr = load(r#""manager_address": "127.0.0.1:5300""#).unwrap();
+    assert!(matches!(addr, ManagerAddr::SocketAddr(sa) if sa == "127.0.0.1:5300".parse().unwrap()));
+    let addr = load(r#""manager_address": "::1", "manager_port": 5300"#).unwrap();
+    assert!(matches!(addr, ManagerAddr::SocketAddr(sa) if sa == "[::1]:5300".parse().unwrap()));
+    let addr = load(r#""manager_address": "localhost:5300""#).unwrap();
+    assert!(matches!(addr, ManagerAddr::DomainName(ref dm, 5300) if dm == "localhost"));
+    #[cfg(unix)]
+    {
+        let addr = load(r#""manager_address": "/tmp/manager.sock""#).unwrap();
+        assert!(matches!(addr, ManagerAddr::UnixSocketAddr(ref p) if p == Path::new("/tmp/manager.sock")));
+    }
+
+    assert_eq!(
+        load(r#""manager_address": "localhost:port""#).unwrap_err().kind,
+        ErrorKind::Invalid
+    );
+    assert_eq!(
+        load(r#""manager_address": " ", "manager_port": 5300"#)
+            .unwrap_err()
+            .kind,
+        ErrorKind::Invalid
+    );
+}
+
+#[test]
+fn test_config_add_server_from_url() {
+    let mut config = Config::load_from_str(
+        r#"{"server": "127.0.0.1", "server_port": 8388, "method": "chacha20-ietf-poly1305", "password": "secret"}"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+    let url = config.server[0].config.to_url();
+
+    assert_eq!(config.add_server_from_url(&url).unwrap(), 0);
+    assert_eq!(config.server.len(), 1);
+
+    let other = ServerConfig::new(
+        "127.0.0.1:8389".parse::<ServerAddr>().unwrap(),
+        "secret",
+        CipherKind::CHACHA20_POLY1305,
+    )
+    .unwrap();
+    assert_eq!(config.add_server_from_url(&other.to_url()).unwrap(), 1);
+    assert_eq!(config.add_server_from_url(&other.to_url()).unwrap(), 1);
+    assert_eq!(config.server.len(), 2);
+    assert_eq!(config.server[1].config.addr().port(), 8389);
+
+    let err = config.add_server_from_url("http://127.0.0.1:8388").unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+    assert_eq!(config.server.len(), 2);
+}
+
+#[test]
+fn test_config_default_timeout() {
+    let config = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "method": "none"},
+                {"server": "127.0.0.1", "server_port": 8389, "method": "none", "idle_timeout": 60},
+                {"server": "127.0.0.1", "server_port": 8390, "method": "none", "timeout": 0}
+            ]
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    let svr = &config.server[0].config;
+    assert_eq!(svr.timeout(), None);
+    assert_eq!(svr.connect_timeout(), Some(DEFAULT_TIMEOUT));
+    assert_eq!(svr.idle_timeout(), Some(DEFAULT_TIMEOUT));
+
+    let svr = &config.server[1].config;
+    assert_eq!(svr.connect_timeout(), Some(DEFAULT_TIMEOUT));
+    assert_eq!(svr.idle_timeout(), Some(Duration::from_secs(60)));
+
+    let svr = &config.server[2].config;
+    assert_eq!(svr.timeout(), None);
+    assert_eq!(svr.connect_timeout(), None);
+    assert_eq!(svr.idle_timeout(), None);
+
+    // The default is not written back, but disabled timeouts are
+    let serialized = config.to_string();
+    let reloaded = Config::load_from_str(&serialized, ConfigType::Server).unwrap();
+    let servers = serde_json::from_str::<serde_json::Value>(&serialized).unwrap()["servers"].clone();
+    assert!(servers[0].get("timeout").is_none());
+    assert!(servers[0].get("connect_timeout").is_none());
+    assert!(servers[0].get("idle_timeout").is_none());
+    for (svr, reloaded_svr) in config.server.iter().zip(reloaded.server.iter()) {
+        assert_eq!(svr.config.connect_timeout(), reloaded_svr.config.connect_timeout());
+        assert_eq!(svr.config.idle_timeout(), reloaded_svr.config.idle_timeout());
+    }
+
+    // Servers not loaded from configuration files, e.g. from command line arguments or URLs
+    let svr = ServerConfig::new("127.0.0.1:8388".parse::<ServerAddr>().unwrap(), "", CipherKind::NONE).unwrap();
+    assert_eq!(svr.connect_timeout(), Some(DEFAULT_TIMEOUT));
+    assert_eq!(svr.idle_timeout(), Some(DEFAULT_TIMEOUT));
+
+    let config = Config::load_from_str(
+        r#"{"timeout": 60, "servers": [{"server": "127.0.0.1", "server_port": 8388, "method": "none"}]}"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+    assert_eq!(config.server[0].config.timeout(), Some(Duration::from_secs(60)));
+}
+
+#[test]
+fn test_config_zero_timeout() {
+    let config = Config::load_from_str(
+        r#"{
+            "timeout": 0,
+            "idle_timeout": 300,
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "method": "none"},
+                {"server": "127.0.0.1", "server_port": 8389, "method": "none", "timeout": 60},
+                {"server": "127.0.0.1", "server_port": 8390, "method": "none", "connect_timeout": 0, "idle_timeout": 0}
+            ]
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    let svr = &config.server[0].config;
+    assert_eq!(svr.timeout(), None);
+    assert_eq!(svr.connect_timeout(), None);
+    assert_eq!(svr.idle_timeout(), Some(Duration::from_secs(300)));
+
+    let svr = &config.server[1].config;
+    assert_eq!(svr.connect_timeout(), Some(Duration::from_secs(60)));
+    assert_eq!(svr.idle_timeout(), Some(Duration::from_secs(60)));
+
+    // Server's own `0` isn't overridden by the global values
+    let svr = &config.server[2].config;
+    assert_eq!(svr.connect_timeout(), None);
+    assert_eq!(svr.idle_timeout(), None);
+}
+
+#[test]
+fn test_config_version() {
+    let load = |keys: &str| load_with(keys, ConfigType::Server);
+
+    assert_eq!(load("").unwrap().version, CONFIG_VERSION);
+    assert_eq!(load(r#""version": 1"#).unwrap().version, 1);
+
+    // Newer versions are loaded with warnings
+    let config = load(r#""version": 2"#).unwrap();
+    assert_eq!(config.version, 2);
+    assert!(config.to_string().contains("\"version\":2"));
+
+    let err = load(r#""version": 0"#).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+}
+
+#[test]
+fn test_wildcard_match() {
+    assert!(wildcard_match("*.json", "servers.json"));
+    assert!(wildcard_match("*.json", ".json"));
+    assert!(wildcard_match("s?rv*.json", "servers.json"));
+    assert!(wildcard_match("a*b*c", "aXbYbZc"));
+    assert!(!wildcard_match("*.json", "servers.json5"));
+    assert!(!wildcard_match("?.json", "ab.json"));
+}
+
+#[test]
+fn test_load_from_file_include() {
+    let dir = env::temp_dir().join(format!("ss-config-include-{}", std::process::id()));
+    let fragment_dir = dir.join("servers.d");
+    fs::create_dir_all(&fragment_dir).unwrap();
+
+    let config_path = dir.join("config.json");
+    fs::write(
+        &config_path,
+        r#"{
+            "include": ["servers.d/*.json"],
+            "servers": [{"server": "127.0.0.1", "server_port": 8388, "password": "p0", "method": "aes-128-gcm"}],
+            "timeout": 10
+        }"#,
+    )
+    .unwrap();
+    fs::write(
+        fragment_dir.join("a.json"),
+        r#"{
+            "servers": [{"server": "127.0.0.1", "server_port": 8389, "password": "p1", "method": "aes-128-gcm"}],
+            "timeout": 20,
+            "include": ["../extra/b.json"]
+        }"#,
+    )
+    .unwrap();
+    fs::write(fragment_dir.join("ignored.txt"), "not a json").unwrap();
+    fs::create_dir_all(dir.join("extra")).unwrap();
+    fs::write(
+        dir.join("extra").join("b.json"),
+        r#"{"servers": [{"server": "127.0.0.1", "server_port": 8390, "password": "p2", "method": "aes-128-gcm"}]}"#,
+    )
+    .unwrap();
+
+    let config = Config::load_from_file(&config_path, ConfigType::Server).unwrap();
+    let ports = config.server.iter().map(|s| s.config.addr().port()).collect::<Vec<_>>();
+    assert_eq!(ports, [8388, 8389, 8390]);
+    // Keys of the main configuration win
+    assert_eq!(config.server[1].config.timeout(), Some(Duration::from_secs(10)));
+
+    // Circular include
+    fs::write(dir.join("extra").join("b.json"), r#"{"include": ["../config.json"]}"#).unwrap();
+    let err = Config::load_from_file(&config_path, ConfigType::Server).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Invalid));
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    // Only files could include
+    let err = Config::load_from_str(r#"{"include": ["a.json"]}"#, ConfigType::Server).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Invalid));
+}
+
+#[test]
+fn test_config_pick_weighted() {
+    let config = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "password": "p", "method": "none", "tcp_weight": 0.5},
+                {"server": "127.0.0.1", "server_port": 8389, "password": "p", "method": "none", "tcp_weight": 0},
+                {"server": "127.0.0.1", "server_port": 8390, "password": "p", "method": "none"}
+            ]
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    assert_eq!(config.total_weight(), 1500);
+
+    let mut picks = [0usize; 3];
+    for rng_value in 0..3000 {
+        let svr = config.pick_weighted(rng_value).unwrap();
+        picks[(svr.addr().port() - 8388) as usize] += 1;
+    }
+    assert_eq!(picks, [1000, 0, 2000]);
+
+    let config = Config::load_from_str(
+        r#"{"servers": [{"server": "127.0.0.1", "server_port": 8388, "password": "p", "method": "none", "tcp_weight": 0}]}"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+    assert_eq!(config.total_weight(), 0);
+    assert!(config.pick_weighted(0).is_none());
+}
+
+#[test]
+fn test_config_server_timeout_precedence() {
+    let config = Config::load_from_str(
+        r#"{
+            "timeout": 10,
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "password": "p", "method": "none", "timeout": 20},
+                {"server": "127.0.0.1", "server_port": 8389, "password": "p", "method": "none", "timeout": 0},
+                {"server": "127.0.0.1", "server_port": 8390, "password": "p", "method": "none"}
+            ]
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    let timeouts = config.server.iter().map(|s| s.config.timeout()).collect::<Vec<_>>();
+    assert_eq!(
+        timeouts,
+        [Some(Duration::from_secs(20)), None, Some(Duration::from_secs(10))]
+    );
+}
+
+#[test]
+fn test_config_udp_allowed_ports() {
+    let config = Config::load_from_str(
+        r#"{
+            "server": "127.0.0.1",
+            "server_port": 8388,
+            "password": "p",
+            "method": "none",
+            "udp_allowed_ports": [853, 53, 53]
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+    assert_eq!(config.udp_allowed_ports, Some(vec![53, 853]));
+
+    for ports in ["[]", "[0]", "[-1]", "[65536]"] {
+        let s = format!(
+            r#"{{"server": "127.0.0.1", "server_port": 8388, "password": "p", "method": "none", "udp_allowed_ports": {ports}}}"#
+        );
+        assert!(Config::load_from_str(&s, ConfigType::Server).is_err());
+    }
+}
+
+#[test]
+fn test_config_preflight_resolve() {
+    let config = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "password": "p", "method": "none"},
+                {"server": "localhost", "server_port": 8389, "password": "p", "method": "none"},
+                {"server": "shadowsocks.invalid", "server_port": 8390, "password": "p", "method": "none"}
+            ]
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    let results = config.preflight_resolve();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].0, 0);
+    assert_eq!(
+        results[0].1.as_ref().unwrap(),
+        &"127.0.0.1:8388".parse::<SocketAddr>().unwrap()
+    );
+    assert_eq!(results[1].1.as_ref().unwrap().port(), 8389);
+    // .invalid is reserved, it never resolves
+    assert_eq!(results[2].0, 2);
+    assert!(results[2].1.is_err());
+}
+
+#[test]
+fn test_config_server_error_index() {
+    let err = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "password": "p", "method": "aes-128-gcm"},
+                {"server": "127.0.0.1", "server_port": 8389, "method": "aes-128-gcm"},
+                {"server": "127.0.0.1", "server_port": 8390, "password": "p", "method": "aes-128-gcm"}
+            ]
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap_err();
+
+    assert!(matches!(err.kind, ErrorKind::MissingField));
+    assert!(err.detail.unwrap().starts_with("servers[1]: "));
+}
+
+#[cfg(all(feature = "local-redir", any(target_os = "linux", target_os = "android")))]
+#[test]
+fn test_config_redir_type() {
+    fn load(tcp_redir: &str, udp_redir: &str) -> Result<Config, Error> {
+        let s = format!(
+            r#"{{
+                "locals": [{{
+                    "local_address": "127.0.0.1",
+                    "local_port": 60080,
+                    "protocol": "redir",
+                    "tcp_redir": "{tcp_redir}",
+                    "udp_redir": "{udp_redir}"
+                }}],
+                "server": "127.0.0.1",
+                "server_port": 8388,
+                "password": "p",
+                "method": "none"
+            }}"#
+        );
+        Config::load_from_str(&s, ConfigType::Local)
+    }
+
+    let config = load("tproxy", "tproxy").unwrap();
+    assert_eq!(config.local[0].config.tcp_redir, RedirType::TProxy);
+    assert_eq!(config.local[0].config.udp_redir, RedirType::TProxy);
+
+    assert!(load("redirect", "tproxy").is_ok());
+    assert!(matches!(
+        load("tproxy", "redirect").unwrap_err().kind,
+        ErrorKind::Invalid
+    ));
+    assert!(matches!(load("pf", "tproxy").unwrap_err().kind, ErrorKind::Malformed));
+}
+
+#[test]
+fn test_config_duplicate_keys() {
+    for s in [
+        r#"{"server": "127.0.0.1", "server_port": 8388, "password": "a", "password": "b", "method": "none"}"#,
+        r#"{"servers": [{"server": "127.0.0.1", "server_port": 8388, "password": "a", "password": "b", "method": "none"}]}"#,
+    ] {
+        let err = Config::load_from_str(s, ConfigType::Server).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Malformed));
+        assert!(err.detail.unwrap().contains("`password`"));
+    }
+
+    // Keys in different objects are not duplicated
+    Config::load_from_str(
+        r#"{"password": "a", "servers": [{"server": "127.0.0.1", "server_port": 8388, "password": "b", "method": "none"}]}"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_config_preferred_server() {
+    let s = r#"{
+        "servers": [
+            {"server": "127.0.0.1", "server_port": 8388, "password": "p", "method": "none"},
+            {"server": "127.0.0.1", "server_port": 8389, "password": "p", "method": "none"}
+        ],
+        "preferred_server": 1
+    }"#;
+    let config = Config::load_from_str(s, ConfigType::Local).unwrap();
+    assert_eq!(config.preferred_server, Some(1));
+
+    let config = Config::load_from_str(&config.to_string(), ConfigType::Local).unwrap();
+    assert_eq!(config.preferred_server, Some(1));
+
+    let err = Config::load_from_str(
+        &s.replace("\"preferred_server\": 1", "\"preferred_server\": 2"),
+        ConfigType::Local,
+    )
+    .unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Invalid));
+}
+
+#[test]
+fn test_config_extra_keys() {
+    let config = Config::load_from_str(
+        r#"{
+            "server": "127.0.0.1",
+            "server_port": 8388,
+            "password": "p",
+            "method": "none",
+            "x_experimental": {"enabled": true, "level": 3}
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+    assert_eq!(
+        config.extra.get("x_experimental"),
+        Some(&serde_json::json!({"enabled": true, "level": 3}))
+    );
+    // Recognized keys are not in `extra`
+    assert_eq!(config.extra.len(), 1);
+
+    let config = Config::load_from_str(&config.to_string(), ConfigType::Server).unwrap();
+    assert_eq!(
+        config.extra.get("x_experimental"),
+        Some(&serde_json::json!({"enabled": true, "level": 3}))
+    );
+}
+
+#[test]
+fn test_config_outbound_fwmark() {
+    let load = |fwmark: &str| {
+        Config::load_from_str(
+            &format!(
+                r#"{{"server": "127.0.0.1", "server_port": 8388, "password": "p", "method": "none", "outbound_fwmark": {fwmark}}}"#
+            ),
+            ConfigType::Server,
+        )
+    };
+
+    let config = load("255").unwrap();
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        assert_eq!(config.outbound_fwmark, Some(255));
+        assert_eq!(config.server[0].outbound_fwmark, Some(255));
+        let config = Config::load_from_str(&config.to_string(), ConfigType::Server).unwrap();
+        assert_eq!(config.outbound_fwmark, Some(255));
+    }
+    // Ignored on the other platforms
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    assert!(config.extra.is_empty());
+
+    for fwmark in ["-1", "4294967296"] {
+        assert!(matches!(load(fwmark).unwrap_err().kind, ErrorKind::Invalid));
+    }
+}
+
+#[test]
+fn test_config_outbound_ttl() {
+    for ttl in ["0", "256", "-1"] {
+        let err = load_with(&format!(r#""outbound_ttl": {ttl}"#), ConfigType::Server).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Invalid, "{ttl}");
+    }
+}
+
+#[test]
+fn test_config_summary() {
+    let config = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "password": "secret-password-1", "method": "aes-256-gcm"},
+                {"server": "127.0.0.1", "server_port": 8389, "password": "secret-password-2", "method": "aes-256-gcm"}
+            ],
+            "mode": "tcp_and_udp",
+            "dns_cache_size": 128
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    let summary = config.summary();
+    assert!(summary.starts_with("2 server(s)"), "{summary}");
+    assert!(summary.contains("methods: [aes-256-gcm]"), "{summary}");
+    assert!(summary.contains("udp: enabled"), "{summary}");
+    assert!(summary.contains("dns cache: 128"), "{summary}");
+    assert!(!summary.contains("secret-password"), "{summary}");
+}
+
+#[test]
+fn test_config_local_address_array() {
+    let load = |local_address: &str| {
+        load_with(
+            &format!(r#""local_address": {local_address}, "local_port": 1080"#),
+            ConfigType::Local,
+        )
+    };
+
+    let config = load(r#"["127.0.0.1"]"#).unwrap();
+    assert_eq!(config.local.len(), 1);
+    assert_eq!(
+        config.local[0].config.addr,
+        Some(ServerAddr::from("127.0.0.1:1080".parse::<SocketAddr>().unwrap()))
+    );
+
+    let config = load(r#"["127.0.0.1", "::1"]"#).unwrap();
+    let addrs = config
+        .local
+        .iter()
+        .map(|local| local.config.addr.clone().unwrap())
+        .collect::<Vec<ServerAddr>>();
+    assert_eq!(
+        addrs,
+        [
+            ServerAddr::from("127.0.0.1:1080".parse::<SocketAddr>().unwrap()),
+            ServerAddr::from("[::1]:1080".parse::<SocketAddr>().unwrap()),
+        ]
+    );
+    assert!(config
+        .local
+        .iter()
+        .all(|local| local.config.protocol == ProtocolType::Socks));
+
+    let err = load("[]").unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Malformed));
+}
+
+#[test]
+fn test_config_validate_as() {
+    let server_config = Config::load_from_str(
+        r#"{"server": "0.0.0.0", "server_port": 8388, "password": "p", "method": "aes-256-gcm"}"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+    server_config.validate_as(ConfigType::Server).unwrap();
+    // No local listeners
+    let err = server_config.validate_as(ConfigType::Local).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::MissingField);
+    // No `manager_address`
+    let err = server_config.validate_as(ConfigType::Manager).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::MissingField);
+
+    let local_config = Config::load_from_str(
+        r#"{"locals": [{"local_address": "127.0.0.1", "local_port": 1080}]}"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+    local_config.validate_as(ConfigType::Local).unwrap();
+    // No servers
+    let err = local_config.validate_as(ConfigType::Server).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::MissingField);
+}
+
+#[test]
+fn test_config_forbidden_server_ip() {
+    let config = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "10.0.0.1", "server_port": 8388, "method": "none"},
+                {"server": "example.com", "server_port": 8388, "method": "none"}
+            ],
+            "forbidden_ip": ["10.0.0.0/8"]
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    assert!(config.is_forbidden_ip(&"10.0.0.1".parse().unwrap()));
+    assert!(!config.is_forbidden_ip(&"127.0.0.1".parse().unwrap()));
+    assert_eq!(
+        config.forbidden_server_addrs(ConfigType::Server),
+        ["10.0.0.1:8388".parse::<SocketAddr>().unwrap()]
+    );
+    // Only a warning
+    config.check_integrity().unwrap();
+
+    // `forbidden_ip` doesn't apply to sslocal
+    assert!(config.forbidden_server_addrs(ConfigType::Local).is_empty());
+}
+
+#[test]
+fn test_config_from_server_config() {
+    let svr_cfg = ServerConfig::new(
+        "127.0.0.1:8388".parse::<SocketAddr>().unwrap(),
+        "password",
+        CipherKind::AES_256_GCM,
+    )
+    .unwrap();
+
+    let config = Config::from(svr_cfg);
+    assert!(config.config_type.is_server());
+    assert_eq!(config.server.len(), 1);
+    assert_eq!(config.server[0].config.addr().to_string(), "127.0.0.1:8388");
+    config.check_integrity().unwrap();
+}
+
+#[test]
+fn test_config_load_servers_from_url_file() {
+    let urls = "# subscription\n\nss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@127.0.0.1:8388#a\n  ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@127.0.0.1:8389  \n";
+    let path = env::temp_dir().join(format!("ss-url-file-{}.txt", std::process::id()));
+
+    fs::write(&path, urls).unwrap();
+    let servers = Config::load_servers_from_url_file(&path);
+    let addrs: Vec<String> = servers.unwrap().iter().map(|svr| svr.addr().to_string()).collect();
+    assert_eq!(addrs, ["127.0.0.1:8388", "127.0.0.1:8389"]);
+
+    // Whole file is base64 encoded
+    let encoded = base64::engine::general_purpose::STANDARD.encode(urls);
+    fs::write(&path, encoded).unwrap();
+    let servers = Config::load_servers_from_url_file(&path);
+    assert_eq!(servers.unwrap().len(), 2);
+
+    fs::write(
+        &path,
+        "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@127.0.0.1:8388\nhttp://127.0.0.1\n\nss://invalid\n",
+    )
+    .unwrap();
+    let err = Config::load_servers_from_url_file(&path).unwrap_err();
+    let _ = fs::remove_file(&path);
+    assert_eq!(err.kind, ErrorKind::Malformed);
+    let detail = err.detail.unwrap();
+    assert!(detail.contains("line 2:") && detail.contains("line 4:"), "{detail}");
+}
+
+#[cfg(feature = "local-http")]
+#[test]
+fn test_config_protocol_auto() {
+    let config = Config::load_from_str(
+        r#"{
+            "server": "127.0.0.1",
+            "server_port": 8388,
+            "method": "none",
+            "locals": [{"protocol": "auto", "local_address": "127.0.0.1", "local_port": 1080}]
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+    assert_eq!(config.local[0].config.protocol, ProtocolType::Socks);
+}
+
+#[test]
+fn test_config_sort_servers() {
+    let load = || {
+        Config::load_from_str(
+            r#"{
+                "servers": [
+                    {"server": "example.com", "server_port": 8388, "method": "none"},
+                    {"server": "127.0.0.2", "server_port": 8388, "method": "none"},
+                    {"server": "127.0.0.1", "server_port": 8389, "method": "none"},
+                    {"server": "127.0.0.1", "server_port": 8388, "method": "none"}
+                ],
+                "locals": [{"local_port": 1080, "servers": [0, 3]}],
+                "preferred_server": 1
+            }"#,
+            ConfigType::Local,
+        )
+        .unwrap()
+    };
+
+    let mut config = load();
+    config.sort_servers();
+    let addrs: Vec<String> = config.server.iter().map(|s| s.config.addr().to_string()).collect();
+    assert_eq!(
+        addrs,
+        ["127.0.0.1:8388", "127.0.0.1:8389", "127.0.0.2:8388", "example.com:8388"]
+    );
+    assert_eq!(config.local[0].servers, Some(vec![3, 0]));
+    assert_eq!(config.preferred_server, Some(2));
+
+    // Sorting again doesn't change anything
+    config.sort_servers();
+    let resorted: Vec<String> = config.server.iter().map(|s| s.config.addr().to_string()).collect();
+    assert_eq!(resorted, addrs);
+}
+
+#[test]
+fn test_config_remove_server() {
+    let mut config = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "method": "none"},
+                {"server": "127.0.0.1", "server_port": 8389, "method": "none"},
+                {"server": "example.com", "server_port": 8388, "method": "none"}
+            ],
+            "locals": [{"local_port": 1080, "servers": [0, 2]}],
+            "preferred_server": 2
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    assert!(!config.remove_server(&ServerAddr::from(("example.org", 8388))).unwrap());
+    assert_eq!(config.server.len(), 3);
+
+    assert!(config
+        .remove_server(&ServerAddr::from("127.0.0.1:8388".parse::<SocketAddr>().unwrap()))
+        .unwrap());
+    let addrs: Vec<String> = config.server.iter().map(|s| s.config.addr().to_string()).collect();
+    assert_eq!(addrs, ["127.0.0.1:8389", "example.com:8388"]);
+    assert_eq!(config.local[0].servers, Some(vec![1]));
+    assert_eq!(config.preferred_server, Some(1));
+    config.check_integrity().unwrap();
+
+    // The local would be left without servers
+    let err = config
+        .remove_server(&ServerAddr::from(("example.com", 8388)))
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+    assert_eq!(config.server.len(), 2);
+    assert_eq!(config.local[0].servers, Some(vec![1]));
+    assert_eq!(config.preferred_server, Some(1));
+    config.check_integrity().unwrap();
+
+    assert!(config
+        .remove_server(&ServerAddr::from("127.0.0.1:8389".parse::<SocketAddr>().unwrap()))
+        .unwrap());
+    assert_eq!(config.local[0].servers, Some(vec![0]));
+    assert_eq!(config.preferred_server, Some(0));
+    config.check_integrity().unwrap();
+}
+
+#[test]
+fn test_config_replace_server() {
+    let mut config = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "method": "none"},
+                {"server": "127.0.0.1", "server_port": 8389, "method": "none"}
+            ]
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    let addr = ServerAddr::from("127.0.0.1:8389".parse::<SocketAddr>().unwrap());
+    let new = ServerConfig::new(
+        ServerAddr::from("127.0.0.1:8390".parse::<SocketAddr>().unwrap()),
+        "password",
+        CipherKind::AES_256_GCM,
+    )
+    .unwrap();
+
+    assert!(config.replace_server(&addr, new.clone()));
+    let svr = &config.server[1].config;
+    assert_eq!(svr.addr().to_string(), "127.0.0.1:8390");
+    assert_eq!(svr.method(), CipherKind::AES_256_GCM);
+
+    // Already replaced
+    assert!(!config.replace_server(&addr, new));
+    assert_eq!(config.server.len(), 2);
+}
+
+#[test]
+fn test_config_auto_method() {
+    let load = |extra: &str, config_type: ConfigType| {
+        Config::load_from_str(
+            &format!(
+                r#"{{"server": "127.0.0.1", "server_port": 8388, "password": "p", "method": "auto", "local_port": 1080{extra}}}"#
+            ),
+            config_type,
+        )
+    };
+
+    // Plain passwords are valid for every non-2022 method
+    let err = load("", ConfigType::Local).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+    let err = load(r#", "auto_methods": ["aes-128-gcm", "aes-256-gcm"]"#, ConfigType::Local).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+
+    let config = load(r#", "auto_methods": ["aes-128-gcm"]"#, ConfigType::Local).unwrap();
+    assert_eq!(config.server[0].config.method(), CipherKind::AES_128_GCM);
+    // Serialized with the resolved method
+    assert!(config.to_string().contains("aes-128-gcm"));
+
+    let err = load(r#", "auto_methods": ["foo"]"#, ConfigType::Local).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+
+    // AEAD-2022 keys select the method by their lengths
+    #[cfg(feature = "aead-cipher-2022")]
+    for (key, method) in [
+        ("AAAAAAAAAAAAAAAAAAAAAA==", CipherKind::AEAD2022_BLAKE3_AES_128_GCM),
+        (
+            "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=",
+            CipherKind::AEAD2022_BLAKE3_AES_256_GCM,
+        ),
+    ] {
+        let config = Config::load_from_str(
+            &format!(r#"{{"server": "127.0.0.1", "server_port": 8388, "password": "{key}", "method": "auto", "local_port": 1080}}"#),
+            ConfigType::Local,
+        )
+        .unwrap();
+        assert_eq!(config.server[0].config.method(), method);
+    }
+
+    let err = load("", ConfigType::Server).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+}
+
+#[test]
+fn test_config_max_connections() {
+    let config = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "method": "none"},
+                {"server": "127.0.0.1", "server_port": 8389, "method": "none", "max_connections": 10}
+            ],
+            "max_connections": 1024
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+    assert_eq!(config.server[0].config.max_connections(), Some(1024));
+    assert_eq!(config.server[1].config.max_connections(), Some(10));
+
+    let config = Config::load_from_str(&config.to_string(), ConfigType::Server).unwrap();
+    assert_eq!(config.server[1].config.max_connections(), Some(10));
+
+    // Checked directly, JSON numbers this large lose precision
+    let max_permits = tokio::sync::Semaphore::MAX_PERMITS;
+    assert_eq!(check_max_connections(max_permits as i64).unwrap(), max_permits);
+    let err = check_max_connections(max_permits as i64 + 1).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+
+    for n in ["0", "-1"] {
+        let err = load_with(&format!(r#""max_connections": {n}"#), ConfigType::Server).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Invalid);
+    }
+}
+
+#[test]
+fn test_config_server_retries() {
+    let config = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "method": "none"},
+                {"server": "127.0.0.1", "server_port": 8389, "method": "none", "max_retries": 0, "retry_backoff": 50}
+            ],
+            "max_retries": 3,
+            "retry_backoff": 200
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+    assert_eq!(config.server[0].config.max_retries(), Some(3));
+    assert_eq!(
+        config.server[0].config.retry_backoff(),
+        Some(Duration::from_millis(200))
+    );
+    assert_eq!(config.server[1].config.max_retries(), Some(0));
+    assert_eq!(config.server[1].config.retry_backoff(), Some(Duration::from_millis(50)));
+
+    let config = Config::load_from_str(&config.to_string(), ConfigType::Local).unwrap();
+    assert_eq!(config.server[0].config.max_retries(), Some(3));
+    assert_eq!(config.server[1].config.retry_backoff(), Some(Duration::from_millis(50)));
+
+    let config = load_with("", ConfigType::Local).unwrap();
+    assert_eq!(config.server[0].config.max_retries(), None);
+    assert_eq!(config.server[0].config.retry_backoff(), None);
+
+    for kv in [r#""max_retries": -1"#, r#""retry_backoff": -1"#] {
+        let err = load_with(kv, ConfigType::Local).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Invalid);
+    }
+}
+
+#[cfg(feature = "config-yaml")]
+#[test]
+fn test_config_load_from_file_formats() {
+    let dir = env::temp_dir().join(format!("ss-config-formats-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let json = r#"{"server": "127.0.0.1", "server_port": 8388, "method": "none"}"#;
+    let yaml = "server: 127.0.0.1\nserver_port: 8388\nmethod: none\n";
+    let load = |name: &str, content: &str| {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        Config::load_from_file(&path, ConfigType::Server)
+    };
+
+    for (name, content) in [
+        ("config.json", json),
+        ("config.yaml", yaml),
+        ("config.yml", yaml),
+        ("config.conf", json),
+        ("config.conf", yaml),
+    ] {
+        let config = load(name, content).unwrap_or_else(|err| panic!("{name}: {err}"));
+        assert_eq!(config.server[0].config.addr().to_string(), "127.0.0.1:8388", "{name}");
+    }
+
+    let err = load("config.yaml", "server: [127.0.0.1\n").unwrap_err();
+    assert!(err.desc.contains("yaml"), "{err}");
+
+    // Positions of type errors are in the YAML file
+    let err = load(
+        "config.yaml",
+        "server: 127.0.0.1\nserver_port: 8388\nmethod: none\nfast_open: 1\n",
+    )
+    .unwrap_err();
+    assert!(err.desc.contains("yaml"), "{err}");
+    assert!(err.detail.as_deref().unwrap().contains("line 4"), "{err}");
+
+    let err = load("config.json", yaml).unwrap_err();
+    assert!(err.desc.contains("json"), "{err}");
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_config_method_counts() {
+    let config = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "password": "p", "method": "aes-256-gcm"},
+                {"server": "127.0.0.1", "server_port": 8389, "password": "p", "method": "chacha20-ietf-poly1305"},
+                {"server": "127.0.0.1", "server_port": 8390, "password": "p", "method": "aes-256-gcm"}
+            ]
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    let counts = config.method_counts();
+    assert_eq!(counts.len(), 2);
+    assert_eq!(counts[&CipherKind::AES_256_GCM], 2);
+    assert_eq!(counts[&CipherKind::CHACHA20_POLY1305], 1);
+}
+
+#[test]
+fn test_config_outbound_bind_interface() {
+    let load = |iface: &str| load_with(&format!(r#""outbound_bind_interface": {iface:?}"#), ConfigType::Server);
+
+    let config = load("eth1").unwrap();
+    assert_eq!(config.outbound_bind_interface.as_deref(), Some("eth1"));
+    assert!(config.to_string().contains(r#""outbound_bind_interface":"eth1""#));
+
+    for iface in ["", "  "] {
+        assert_eq!(load(iface).unwrap_err().kind, ErrorKind::Invalid);
+    }
+}
+
+#[test]
+fn test_config_fallback_method() {
+    let config = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "password": "p", "method": "aes-256-gcm"},
+                {"server": "127.0.0.1", "server_port": 8389, "password": "p", "method": "aes-128-gcm", "fallback_method": "aes-256-gcm"}
+            ],
+            "fallback_method": "chacha20-ietf-poly1305"
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    assert_eq!(
+        config.server[0].config.fallback_method(),
+        Some(CipherKind::CHACHA20_POLY1305)
+    );
+    assert_eq!(config.server[1].config.fallback_method(), Some(CipherKind::AES_256_GCM));
+
+    let config = Config::load_from_str(&config.to_string(), ConfigType::Server).unwrap();
+    assert_eq!(
+        config.server[0].config.fallback_method(),
+        Some(CipherKind::CHACHA20_POLY1305)
+    );
+    assert_eq!(config.server[1].config.fallback_method(), Some(CipherKind::AES_256_GCM));
+
+    let err = load_with(r#""fallback_method": "aes-256-gcm""#, ConfigType::Server).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+}
+
+#[test]
+fn test_config_mode_enable_udp() {
+    let load = |keys: &str| load_with(keys, ConfigType::Server).unwrap().server[0].config.mode();
+
+    assert_eq!(load(""), Mode::TcpOnly);
+    assert_eq!(load(r#""mode": "udp_only""#), Mode::UdpOnly);
+    assert_eq!(load(r#""enable_udp": true"#), Mode::TcpAndUdp);
+    assert_eq!(load(r#""enable_udp": false"#), Mode::TcpOnly);
+    assert_eq!(load(r#""mode": "tcp_only", "enable_udp": true"#), Mode::TcpOnly);
+    assert_eq!(load(r#""mode": "tcp_and_udp", "enable_udp": false"#), Mode::TcpAndUdp);
+
+    let mode = load(r#""enable_udp": true"#);
+    assert!(mode.enable_udp() && mode.enable_tcp());
+}
+
+#[test]
+fn test_config_kdf() {
+    let config = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "password": "p", "method": "aes-256-gcm"},
+                {"server": "127.0.0.1", "server_port": 8389, "password": "p", "method": "aes-128-gcm", "kdf": {"iterations": 1}}
+            ],
+            "kdf": {"iterations": 1}
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    assert_eq!(config.server[0].config.kdf(), Some(&KdfConfig { iterations: 1 }));
+    assert_eq!(config.server[1].config.kdf(), Some(&KdfConfig { iterations: 1 }));
+
+    let config = Config::load_from_str(&config.to_string(), ConfigType::Server).unwrap();
+    assert_eq!(config.server[0].config.kdf(), Some(&KdfConfig { iterations: 1 }));
+
+    let err = Config::load_from_str(
+        r#"{"server": "127.0.0.1", "server_port": 8388, "password": "p", "method": "aes-256-gcm", "kdf": {"iterations": 1000}}"#,
+        ConfigType::Server,
+    )
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+
+    let err = load_with(r#""kdf": {"iterations": 1}"#, ConfigType::Server).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+
+    let err = Config::load_from_str(
+        r#"{
+            "server": "127.0.0.1",
+            "server_port": 8388,
+            "password": "AAAAAAAAAAAAAAAAAAAAAA==",
+            "method": "2022-blake3-aes-128-gcm",
+            "kdf": {"iterations": 1}
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+}
+
+#[test]
+fn test_config_server_validity() {
+    let config = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "method": "none", "valid_until": "2024-01-01T08:00:00.5+08:00"},
+                {"server": "127.0.0.1", "server_port": 8389, "method": "none", "start_time": "1969-12-31T23:59:59Z"}
+            ]
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    let valid_until = SystemTime::UNIX_EPOCH + Duration::from_millis(1_704_067_200_500);
+    let svr_cfg = &config.server[0].config;
+    assert_eq!(svr_cfg.valid_until(), Some(valid_until));
+    assert!(svr_cfg.is_currently_valid(valid_until));
+    assert!(!svr_cfg.is_currently_valid(valid_until + Duration::from_secs(1)));
+    assert_eq!(
+        config.server[1].config.valid_from(),
+        Some(SystemTime::UNIX_EPOCH - Duration::from_secs(1))
+    );
+
+    let config = Config::load_from_str(&config.to_string(), ConfigType::Local).unwrap();
+    assert_eq!(config.server[0].config.valid_until(), Some(valid_until));
+    assert_eq!(
+        config.server[1].config.valid_from(),
+        Some(SystemTime::UNIX_EPOCH - Duration::from_secs(1))
+    );
+
+    for t in [
+        "2024-02-30T00:00:00Z",
+        "2024-01-01 00:00:00",
+        "2024-01-01T24:00:00Z",
+        "tomorrow",
+    ] {
+        let err = load_with(&format!(r#""valid_from": "{t}""#), ConfigType::Local).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Malformed);
+    }
+}
+
+#[test]
+fn test_config_to_subscription() {
+    let config = Config::load_from_str(
+        r#"{
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8388, "password": "p", "method": "aes-256-gcm", "remarks": "hk 1"},
+                {"server": "example.com", "server_port": 8389, "password": "p", "method": "chacha20-ietf-poly1305", "plugin": "obfs-local", "plugin_opts": "obfs=http"}
+            ]
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    let urls = config.to_url_list();
+    assert_eq!(urls.len(), 2);
+    assert!(urls.iter().all(|u| u.starts_with("ss://")));
+
+    let path = env::temp_dir().join(format!("ss-subscription-{}.txt", std::process::id()));
+    fs::write(&path, config.to_subscription()).unwrap();
+    let servers = Config::load_servers_from_url_file(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(servers.len(), 2);
+    assert_eq!(servers[0].addr(), config.server[0].config.addr());
+    assert_eq!(servers[0].method(), CipherKind::AES_256_GCM);
+    assert_eq!(servers[0].remarks(), Some("hk 1"));
+    assert_eq!(servers[1].addr(), config.server[1].config.addr());
+    let plugin = servers[1].plugin().unwrap();
+    assert_eq!(plugin.plugin, "obfs-local");
+    assert_eq!(plugin.plugin_opts.as_deref(), Some("obfs=http"));
+}
+
+#[test]
+fn test_config_health_check() {
+    let config = Config::load_from_str(
+        r#"{
+            "server": "127.0.0.1",
+            "server_port": 8388,
+            "method": "none",
+            "health_check": {"interval": 30, "timeout": 3, "target": "example.com:8080"}
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    let health_check = config.health_check.as_ref().unwrap();
+    assert_eq!(health_check.interval, Some(Duration::from_secs(30)));
+    assert_eq!(health_check.timeout, Some(Duration::from_secs(3)));
+    let target = health_check.target.as_ref().unwrap();
+    assert_eq!(target.addr, Address::DomainNameAddress("example.com".to_owned(), 8080));
+    assert_eq!(target.path, "/");
+
+    let config = Config::load_from_str(&config.to_string(), ConfigType::Local).unwrap();
+    assert_eq!(
+        config.health_check.unwrap().target.unwrap().to_string(),
+        "http://example.com:8080/"
+    );
+
+    let target = "http://127.0.0.1/generate_204".parse::<HealthCheckTarget>().unwrap();
+    assert_eq!(target.addr, Address::SocketAddress("127.0.0.1:80".parse().unwrap()));
+    assert_eq!(target.path, "/generate_204");
+    assert!("https://example.com".parse::<HealthCheckTarget>().is_err());
+
+    let err = Config::load_from_str(
+        r#"{
+            "local_address": "127.0.0.1",
+            "local_port": 1080,
+            "server": "127.0.0.1",
+            "server_port": 8388,
+            "method": "none",
+            "health_check": {"interval": 0}
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap()
+    .check_integrity()
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+}
+
+#[test]
+fn test_config_plugin_without_method() {
+    let config = Config::load_from_str(
+        r#"{
+            "server": "127.0.0.1",
+            "server_port": 8388,
+            "plugin": "v2ray-plugin",
+            "plugin_opts": "tls",
+            "servers": [
+                {"server": "127.0.0.1", "server_port": 8389, "plugin": "v2ray-plugin"},
+                {"server": "127.0.0.1", "server_port": 8390, "method": "plaintext"}
+            ]
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    assert!(config.server.iter().all(|s| s.config.method().is_none()));
+    config.check_integrity().unwrap();
+
+    let err = Config::load_from_str(
+        r#"{"servers": [{"server": "127.0.0.1", "server_port": 8388}]}"#,
+        ConfigType::Server,
+    )
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::MissingField);
+}
+
+#[cfg(feature = "local")]
+#[test]
+fn test_config_local_client_acl() {
+    let config = Config::load_from_str(
+        r#"{
+            "locals": [
+                {
+                    "local_address": "::",
+                    "local_port": 1080,
+                    "local_client_acl": {
+                        "allow": ["127.0.0.1/32", "192.168.0.0/16"],
+                        "deny": ["192.168.1.100/32"]
+                    }
+                },
+                {
+                    "local_address": "127.0.0.1",
+                    "local_port": 1081
+                }
+            ]
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    let acl = config.local[0].config.client_acl.as_ref().unwrap();
+    assert!(acl.is_allowed(&"127.0.0.1".parse().unwrap()));
+    assert!(acl.is_allowed(&"192.168.2.1".parse().unwrap()));
+    assert!(acl.is_allowed(&"::ffff:192.168.2.1".parse().unwrap()));
+    assert!(!acl.is_allowed(&"192.168.1.100".parse().unwrap()));
+    assert!(!acl.is_allowed(&"::ffff:192.168.1.100".parse().unwrap()));
+    assert!(!acl.is_allowed(&"10.0.0.1".parse().unwrap()));
+    assert!(config.local[1].config.client_acl.is_none());
+
+    let deny_only = LocalClientAclConfig {
+        allow: Vec::new(),
+        deny: vec!["10.0.0.0/8".parse().unwrap()],
+    };
+    assert!(deny_only.is_allowed(&"192.168.1.1".parse().unwrap()));
+    assert!(!deny_only.is_allowed(&"10.1.2.3".parse().unwrap()));
+
+    let reloaded = Config::load_from_str(&config.to_string(), ConfigType::Local).unwrap();
+    let reloaded_acl = reloaded.local[0].config.client_acl.as_ref().unwrap();
+    assert_eq!(reloaded_acl.allow, acl.allow);
+    assert_eq!(reloaded_acl.deny, acl.deny);
+    assert!(Config::load_from_str(
+        r#"{"locals": [{"local_port": 1080, "local_client_acl": {"allow": ["not-a-cidr"]}}]}"#,
+        ConfigType::Local
+    )
+    .is_err());
+}
+
+#[test]
+fn test_config_key_round_trip() {
+    let round_trip = |keys: &str, config_type: ConfigType, check: &dyn Fn(&Config) -> bool| {
+        let config = load_with(keys, config_type).unwrap();
+        assert!(check(&config), "{keys}");
+        let reloaded = Config::load_from_str(&config.to_string(), config_type).unwrap();
+        assert!(check(&reloaded), "{keys} reloaded");
+    };
+
+    round_trip(r#""reuse_address": false"#, ConfigType::Server, &|c| !c.reuse_address);
+    round_trip(r#""shutdown_timeout": 10"#, ConfigType::Server, &|c| {
+        c.shutdown_timeout == Some(Duration::from_secs(10))
+    });
+    round_trip(r#""udp_max_payload": 4096"#, ConfigType::Server, &|c| {
+        c.udp_max_payload == Some(4096)
+    });
+    round_trip(r#""outbound_ttl": 64"#, ConfigType::Server, &|c| {
+        c.outbound_ttl == Some(64)
+    });
+    round_trip(
+        r#""log_level": "Debug", "log_file": "/var/log/ss.log""#,
+        ConfigType::Server,
+        &|c| c.log_level.as_deref() == Some("debug") && c.log_file.as_deref() == Some("/var/log/ss.log"),
+    );
+    #[cfg(feature = "local")]
+    round_trip(r#""access_log": true"#, ConfigType::Local, &|c| c.access_log);
+    #[cfg(feature = "local")]
+    round_trip(r#""udp_bind_address": "203.0.113.1:1080""#, ConfigType::Local, &|c| {
+        c.udp_bind_addr == Some("203.0.113.1:1080".parse().unwrap())
+    });
+
+    // Defaults are left out of the serialized config
+    let config = load_with("", ConfigType::Local).unwrap();
+    assert!(config.reuse_address);
+    assert_eq!(config.shutdown_timeout, None);
+    let serialized = config.to_string();
+    assert!(!serialized.contains("reuse_address"));
+    #[cfg(feature = "local")]
+    {
+        assert!(!config.access_log);
+        assert_eq!(config.udp_bind_addr, None);
+        assert!(!serialized.contains("access_log"));
+    }
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn test_config_server_outbound_socks5() {
+    let config = Config::load_from_str(
+        r#"{
+            "outbound_socks5": "127.0.0.1:1080",
+            "servers": [
+                {
+                    "server": "127.0.0.1",
+                    "server_port": 8388,
+                    "method": "none",
+                    "outbound_socks5": {"address": "127.0.0.1:1081", "username": "user", "password": "pass"}
+                },
+                {
+                    "server": "127.0.0.1",
+                    "server_port": 8389,
+                    "method": "none"
+                }
+            ]
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    let global = config.outbound_proxy.as_ref();
+    let proxy = config.server[0].effective_outbound_proxy(global).unwrap();
+    assert_eq!(proxy.addr.to_string(), "127.0.0.1:1081");
+    assert_eq!(proxy.username.as_deref(), Some("user"));
+    let proxy = config.server[1].effective_outbound_proxy(global).unwrap();
+    assert_eq!(proxy.addr.to_string(), "127.0.0.1:1080");
+
+    let reloaded = Config::load_from_str(&config.to_string(), ConfigType::Server).unwrap();
+    let proxy = reloaded.server[0].outbound_proxy.as_ref().unwrap();
+    assert_eq!(proxy.password.as_deref(), Some("pass"));
+    assert!(reloaded.server[1].outbound_proxy.is_none());
+
+    assert!(!config.redacted_display().to_string().contains(r#""password":"pass""#));
+    assert!(Config::load_from_str(
+        r#"{"servers": [{"server": "127.0.0.1", "server_port": 8388, "method": "none", "outbound_socks5": "bad"}]}"#,
+        ConfigType::Server
+    )
+    .is_err());
+}
+
+#[cfg(feature = "local")]
+#[test]
+fn test_config_udp_bind_address() {
+    let err = load_with(r#""udp_bind_address": "203.0.113.1""#, ConfigType::Local).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+}
+
+#[cfg(all(unix, feature = "local"))]
+#[test]
+fn test_config_local_unix_addr() {
+    let config = load_with(
+        r#""locals": [{"local_address": "unix:/run/ss.sock"}]"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    let local = &config.local[0].config;
+    assert_eq!(local.addr, None);
+    assert_eq!(local.unix_addr, Some(PathBuf::from("/run/ss.sock")));
+
+    let reloaded = Config::load_from_str(&config.to_string(), ConfigType::Local).unwrap();
+    assert_eq!(reloaded.local[0].config.unix_addr, local.unix_addr);
+
+    let err = load_with(
+        r#""locals": [{"local_address": "unix:/run/ss.sock", "local_port": 1080}]"#,
+        ConfigType::Local,
+    )
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Malformed);
+
+    let err = load_with(
+        r#""locals": [{"local_address": "unix:/run/ss.sock", "mode": "tcp_and_udp"}]"#,
+        ConfigType::Local,
+    )
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Invalid);
+
+    // Top-level `local_address`
+    let config = load_with(r#""local_address": "unix:/run/ss.sock""#, ConfigType::Local).unwrap();
+    assert_eq!(config.local.len(), 1);
+    assert_eq!(config.local[0].config.addr, None);
+    assert_eq!(config.local[0].config.unix_addr, Some(PathBuf::from("/run/ss.sock")));
+    config.check_integrity().unwrap();
+
+    let err = load_with(
+        r#""local_address": "unix:/run/ss.sock", "local_port": 1080"#,
+        ConfigType::Local,
+    )
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Malformed);
+}
+
+#[test]
+fn test_config_udp_max_payload() {
+    for size in [511, 65508] {
+        let err = Config::load_from_str(&format!(r#"{{"udp_max_payload": {size}}}"#), ConfigType::Server).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Invalid);
+    }
+}
+
+#[test]
+fn test_config_forbidden_domain_regex() {
+    let config = Config::load_from_str(
+        r#"{
+            "server": "127.0.0.1",
+            "server_port": 8388,
+            "method": "none",
+            "forbidden_domain_regex": ["(^|\\.)example\\.com$", "^ads[0-9]+\\."]
+        }"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    assert!(config.domain_matches_forbidden_regex("example.com"));
+    assert!(config.domain_matches_forbidden_regex("WWW.Example.COM."));
+    assert!(config.domain_matches_forbidden_regex("ads12.example.org"));
+    assert!(!config.domain_matches_forbidden_regex("notexample.com"));
+    assert!(!config.domain_matches_forbidden_regex("ads.example.org"));
+
+    let config = Config::load_from_str(&config.to_string(), ConfigType::Server).unwrap();
+    assert_eq!(config.forbidden_domain_regex.len(), 2);
+    assert!(config.domain_matches_forbidden_regex("www.example.com"));
+
+    let err = load_with(r#""forbidden_domain_regex": ["(unclosed"]"#, ConfigType::Server).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Malformed);
+    assert!(err.detail.unwrap().contains("(unclosed"));
+}
+
+#[test]
+fn test_config_sorted_forbidden_ips() {
+    let config = load_with(
+        r#""forbidden_ip": ["::1", "192.168.0.0/16", "10.0.0.1", "10.0.0.0/8", "fe80::/10", "10.0.0.1"]"#,
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    let sorted: Vec<String> = config.sorted_forbidden_ips().iter().map(ToString::to_string).collect();
+    assert_eq!(
+        sorted,
+        ["10.0.0.0/8", "10.0.0.1/32", "192.168.0.0/16", "::1/128", "fe80::/10"]
+    );
+
+    let serialized = config.to_string();
+    let reloaded = Config::load_from_str(&serialized, ConfigType::Server).unwrap();
+    assert_eq!(reloaded.to_string(), serialized);
+}
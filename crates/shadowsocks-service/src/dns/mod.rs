@@ -1,7 +1,7 @@
 //! DNS resolvers
 
 #[cfg(feature = "hickory-dns")]
-use hickory_resolver::config::ResolverOpts;
+use hickory_resolver::config::{ResolverOpts, ServerOrderingStrategy};
 use log::trace;
 use shadowsocks::{dns_resolver::DnsResolver, net::ConnectOpts};
 
@@ -10,6 +10,7 @@ use crate::config::DnsConfig;
 #[allow(unused_variables, dead_code)]
 pub async fn build_dns_resolver(
     dns: DnsConfig,
+    dns_in_order: bool,
     ipv6_first: bool,
     dns_cache_size: Option<usize>,
     connect_opts: &ConnectOpts,
@@ -56,12 +57,17 @@ pub async fn build_dns_resolver(
         }
         #[cfg(feature = "hickory-dns")]
         DnsConfig::HickoryDns(dns) => {
-            let mut opts_opt = None;
+            let mut opts = ResolverOpts::default();
             if let Some(dns_cache_size) = dns_cache_size {
-                let mut opts = ResolverOpts::default();
                 opts.cache_size = dns_cache_size;
-                opts_opt = Some(opts);
             }
+            if dns_in_order {
+                // Name servers are tried one by one in the configured order,
+                // the next one is used after `opts.timeout` (5s) or failure of the previous one
+                opts.server_ordering_strategy = ServerOrderingStrategy::UserProvidedOrder;
+                opts.num_concurrent_reqs = 1;
+            }
+            let opts_opt = Some(opts);
 
             match DnsResolver::hickory_resolver(dns, opts_opt, connect_opts.clone()).await {
                 Ok(r) => Some(r),
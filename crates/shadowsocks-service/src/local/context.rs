@@ -1,8 +1,8 @@
 //! Shadowsocks Local Server Context
 
-use std::sync::Arc;
 #[cfg(feature = "local-dns")]
 use std::{net::IpAddr, time::Duration};
+use std::{net::SocketAddr, sync::Arc};
 
 #[cfg(feature = "local-dns")]
 use lru_time_cache::LruCache;
@@ -11,14 +11,18 @@ use shadowsocks::{
     context::{Context, SharedContext},
     dns_resolver::DnsResolver,
     net::{AcceptOpts, ConnectOpts},
-    relay::Address,
+    relay::{udprelay::MAXIMUM_UDP_PAYLOAD_SIZE, Address},
 };
 #[cfg(feature = "local-dns")]
 use tokio::sync::Mutex;
 #[cfg(feature = "local-fake-dns")]
 use tokio::sync::RwLock;
 
-use crate::{acl::AccessControl, config::SecurityConfig, net::FlowStat};
+use crate::{
+    acl::AccessControl,
+    config::{LocalClientAclConfig, SecurityConfig},
+    net::{FlowStat, RateLimiter},
+};
 
 #[cfg(feature = "local-fake-dns")]
 use super::fake_dns::manager::FakeDnsManager;
@@ -33,9 +37,21 @@ pub struct ServiceContext {
     // Access Control
     acl: Option<Arc<AccessControl>>,
 
+    // Source IP access control of listeners
+    client_acl: Option<Arc<LocalClientAclConfig>>,
+
     // Flow statistic report
     flow_stat: Arc<FlowStat>,
 
+    // Log summaries of completed TCP connections
+    access_log: bool,
+
+    // Aggregate throughput limit of TCP tunnels
+    rate_limiter: Option<Arc<RateLimiter>>,
+
+    // Buffer size for receiving UDP packets from targets
+    udp_max_payload: usize,
+
     // For DNS relay's ACL domain name reverse lookup -- whether the IP shall be forwarded
     #[cfg(feature = "local-dns")]
     reverse_lookup_cache: Arc<Mutex<LruCache<IpAddr, bool>>>,
@@ -58,6 +74,10 @@ impl ServiceContext {
             connect_opts: ConnectOpts::default(),
             accept_opts: AcceptOpts::default(),
             acl: None,
+            client_acl: None,
+            access_log: false,
+            rate_limiter: None,
+            udp_max_payload: MAXIMUM_UDP_PAYLOAD_SIZE,
             flow_stat: Arc::new(FlowStat::new()),
             #[cfg(feature = "local-dns")]
             reverse_lookup_cache: Arc::new(Mutex::new(LruCache::with_expiry_duration_and_capacity(
@@ -109,6 +129,51 @@ impl ServiceContext {
         self.acl.as_deref()
     }
 
+    /// Set whether summaries of completed TCP connections should be logged
+    pub fn set_access_log(&mut self, access_log: bool) {
+        self.access_log = access_log;
+    }
+
+    /// Check if summaries of completed TCP connections should be logged
+    pub fn access_log(&self) -> bool {
+        self.access_log
+    }
+
+    /// Set the throughput limiter shared by TCP tunnels
+    pub fn set_rate_limiter(&mut self, limiter: Arc<RateLimiter>) {
+        self.rate_limiter = Some(limiter);
+    }
+
+    /// Get the throughput limiter shared by TCP tunnels
+    pub fn rate_limiter(&self) -> Option<Arc<RateLimiter>> {
+        self.rate_limiter.clone()
+    }
+
+    /// Set buffer size for receiving UDP packets from targets, larger packets are truncated
+    ///
+    /// Defaults to `MAXIMUM_UDP_PAYLOAD_SIZE`, so this could only shrink buffers
+    pub fn set_udp_max_payload(&mut self, size: usize) {
+        self.udp_max_payload = size;
+    }
+
+    /// Buffer size for receiving UDP packets from targets
+    pub fn udp_max_payload(&self) -> usize {
+        self.udp_max_payload
+    }
+
+    /// Set source IP access control of listeners
+    pub fn set_client_acl(&mut self, client_acl: Arc<LocalClientAclConfig>) {
+        self.client_acl = Some(client_acl);
+    }
+
+    /// Check if listeners should accept client from `peer_addr`
+    pub fn check_client_allowed(&self, peer_addr: &SocketAddr) -> bool {
+        match self.client_acl {
+            None => true,
+            Some(ref acl) => acl.is_allowed(&peer_addr.ip()),
+        }
+    }
+
     /// Get cloned flow statistic
     pub fn flow_stat(&self) -> Arc<FlowStat> {
         self.flow_stat.clone()
@@ -185,6 +250,12 @@ impl ServiceContext {
         context.set_ipv6_first(ipv6_first);
     }
 
+    /// Never use IPv6 addresses resolved from hostnames
+    pub fn set_disable_ipv6(&mut self, disable_ipv6: bool) {
+        let context = Arc::get_mut(&mut self.context).expect("cannot set disable_ipv6 on a shared context");
+        context.set_disable_ipv6(disable_ipv6);
+    }
+
     /// Set security config
     pub fn set_security_config(&mut self, security: &SecurityConfig) {
         let context = Arc::get_mut(&mut self.context).expect("cannot set security on a shared context");
@@ -2,6 +2,7 @@
 
 use std::{net::SocketAddr, str::FromStr, sync::Arc};
 
+use base64::Engine as _;
 use bytes::Bytes;
 use http_body_util::{combinators::BoxBody, BodyExt};
 use hyper::{
@@ -31,6 +32,7 @@ pub struct HttpService {
     peer_addr: SocketAddr,
     http_client: HttpClient<body::Incoming>,
     balancer: PingBalancer,
+    auth: Option<Arc<(String, String)>>,
 }
 
 impl HttpService {
@@ -39,12 +41,14 @@ impl HttpService {
         peer_addr: SocketAddr,
         http_client: HttpClient<body::Incoming>,
         balancer: PingBalancer,
+        auth: Option<Arc<(String, String)>>,
     ) -> HttpService {
         HttpService {
             context,
             peer_addr,
             http_client,
             balancer,
+            auth,
         }
     }
 
@@ -52,6 +56,16 @@ impl HttpService {
         self,
         mut req: Request<body::Incoming>,
     ) -> hyper::Result<Response<BoxBody<Bytes, hyper::Error>>> {
+        if let Some(ref auth) = self.auth {
+            if !check_proxy_authorization(req.headers(), auth) {
+                error!("HTTP {} {} proxy authentication failed", self.peer_addr, req.method());
+                return make_proxy_authentication_required();
+            }
+        }
+
+        // Credentials shouldn't be logged or forwarded
+        req.headers_mut().remove(header::PROXY_AUTHORIZATION);
+
         trace!("request {} {:?}", self.peer_addr, req);
 
         // Parse URI
@@ -84,6 +98,8 @@ impl HttpService {
             // Connect to Shadowsocks' remote
             //
             // FIXME: What STATUS should I return for connection error?
+            let access_log = self.context.access_log();
+            let rate_limiter = self.context.rate_limiter();
             let (mut stream, server_opt) = match connect_host(self.context, &host, Some(&self.balancer)).await {
                 Ok(s) => s,
                 Err(err) => {
@@ -115,11 +131,21 @@ impl HttpService {
                                     &mut stream,
                                     client_addr,
                                     &host,
+                                    access_log,
+                                    rate_limiter,
                                 )
                                 .await
                             }
                             None => {
-                                establish_tcp_tunnel_bypassed(&mut upgraded_io, &mut stream, client_addr, &host).await
+                                establish_tcp_tunnel_bypassed(
+                                    &mut upgraded_io,
+                                    &mut stream,
+                                    client_addr,
+                                    &host,
+                                    access_log,
+                                    rate_limiter,
+                                )
+                                .await
                             }
                         };
                     }
@@ -205,6 +231,39 @@ fn make_bad_request() -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::E
         .unwrap())
 }
 
+fn make_proxy_authentication_required() -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+    Ok(Response::builder()
+        .status(StatusCode::PROXY_AUTHENTICATION_REQUIRED)
+        .header(header::PROXY_AUTHENTICATE, "Basic realm=\"shadowsocks\"")
+        .body(empty_body())
+        .unwrap())
+}
+
+/// Check `Proxy-Authorization: Basic <base64(username:password)>`
+fn check_proxy_authorization(headers: &HeaderMap<HeaderValue>, auth: &(String, String)) -> bool {
+    let Some(value) = headers.get(header::PROXY_AUTHORIZATION) else {
+        return false;
+    };
+    let Ok(value) = value.to_str() else {
+        return false;
+    };
+    let Some((scheme, credentials)) = value.trim().split_once(' ') else {
+        return false;
+    };
+    if !scheme.eq_ignore_ascii_case("Basic") {
+        return false;
+    }
+    let Ok(credentials) = base64::engine::general_purpose::STANDARD.decode(credentials.trim()) else {
+        return false;
+    };
+
+    let (ref username, ref password) = *auth;
+    match credentials.iter().position(|b| *b == b':') {
+        Some(pos) => &credentials[..pos] == username.as_bytes() && &credentials[pos + 1..] == password.as_bytes(),
+        None => false,
+    }
+}
+
 fn make_internal_server_error() -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
     Ok(Response::builder()
         .status(StatusCode::INTERNAL_SERVER_ERROR)
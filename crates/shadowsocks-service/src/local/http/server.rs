@@ -5,7 +5,7 @@
 use std::{io, net::SocketAddr, sync::Arc, time::Duration};
 
 use hyper::{body, server::conn::http1, service};
-use log::{error, info, trace};
+use log::{error, info, trace, warn};
 use shadowsocks::{config::ServerAddr, net::TcpListener};
 use tokio::{
     io::{AsyncRead, AsyncWrite},
@@ -23,6 +23,7 @@ pub struct HttpBuilder {
     context: Arc<ServiceContext>,
     client_config: ServerAddr,
     balancer: PingBalancer,
+    auth: Option<Arc<(String, String)>>,
     #[cfg(target_os = "macos")]
     launchd_tcp_socket_name: Option<String>,
 }
@@ -44,11 +45,17 @@ impl HttpBuilder {
             context,
             client_config,
             balancer,
+            auth: None,
             #[cfg(target_os = "macos")]
             launchd_tcp_socket_name: None,
         }
     }
 
+    /// Require clients to authenticate with HTTP Basic authentication
+    pub fn set_auth(&mut self, username: String, password: String) {
+        self.auth = Some(Arc::new((username, password)));
+    }
+
     #[cfg(target_os = "macos")]
     pub fn set_launchd_tcp_socket_name(&mut self, n: String) {
         self.launchd_tcp_socket_name = Some(n);
@@ -79,6 +86,7 @@ impl HttpBuilder {
             context: self.context,
             listener,
             balancer: self.balancer,
+            auth: self.auth,
         })
     }
 }
@@ -88,6 +96,7 @@ pub struct Http {
     context: Arc<ServiceContext>,
     listener: TcpListener,
     balancer: PingBalancer,
+    auth: Option<Arc<(String, String)>>,
 }
 
 impl Http {
@@ -106,7 +115,8 @@ impl Http {
             self.listener.local_addr().expect("http local_addr")
         );
 
-        let handler = HttpConnectionHandler::new(self.context, self.balancer);
+        let mut handler = HttpConnectionHandler::new(self.context, self.balancer);
+        handler.auth = self.auth;
 
         loop {
             let (stream, peer_addr) = match self.listener.accept().await {
@@ -118,6 +128,11 @@ impl Http {
                 }
             };
 
+            if !handler.context.check_client_allowed(&peer_addr) {
+                warn!("HTTP client {} rejected by local_client_acl", peer_addr);
+                continue;
+            }
+
             trace!("HTTP accepted client from {}", peer_addr);
             let handler = handler.clone();
             tokio::spawn(async move {
@@ -137,6 +152,7 @@ pub struct HttpConnectionHandler {
     context: Arc<ServiceContext>,
     balancer: PingBalancer,
     http_client: HttpClient<body::Incoming>,
+    auth: Option<Arc<(String, String)>>,
 }
 
 impl HttpConnectionHandler {
@@ -146,9 +162,15 @@ impl HttpConnectionHandler {
             context,
             balancer,
             http_client: HttpClient::new(),
+            auth: None,
         }
     }
 
+    /// Require clients to authenticate with HTTP Basic authentication
+    pub fn set_auth(&mut self, username: String, password: String) {
+        self.auth = Some(Arc::new((username, password)));
+    }
+
     /// Handle a TCP HTTP connection
     pub async fn serve_connection<S>(self, stream: S, peer_addr: SocketAddr) -> hyper::Result<()>
     where
@@ -158,6 +180,7 @@ impl HttpConnectionHandler {
             context,
             balancer,
             http_client,
+            auth,
         } = self;
 
         let io = TokioIo::new(stream);
@@ -171,8 +194,14 @@ impl HttpConnectionHandler {
             .serve_connection(
                 io,
                 service::service_fn(move |req| {
-                    HttpService::new(context.clone(), peer_addr, http_client.clone(), balancer.clone())
-                        .serve_connection(req)
+                    HttpService::new(
+                        context.clone(),
+                        peer_addr,
+                        http_client.clone(),
+                        balancer.clone(),
+                        auth.clone(),
+                    )
+                    .serve_connection(req)
                 }),
             )
             .with_upgrades()
@@ -10,7 +10,7 @@ use std::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
 
 use arc_swap::ArcSwap;
@@ -18,7 +18,7 @@ use byte_string::ByteStr;
 use futures::future;
 use log::{debug, error, info, trace, warn};
 use shadowsocks::{
-    config::{Mode, ServerSource},
+    config::{Mode, ServerAddr, ServerSource},
     plugin::{Plugin, PluginMode},
     relay::{
         socks5::Address,
@@ -35,7 +35,10 @@ use tokio::{
     time,
 };
 
-use crate::{config::ServerInstanceConfig, local::context::ServiceContext};
+use crate::{
+    config::{HealthCheckTarget, ServerInstanceConfig},
+    local::context::ServiceContext,
+};
 
 use super::{
     server_data::ServerIdent,
@@ -43,6 +46,8 @@ use super::{
 };
 
 const EXPECTED_CHECK_POINTS_IN_CHECK_WINDOW: u32 = 67;
+/// Preferred server will be replaced by the best server if its fail rate is higher than this
+const PREFERRED_SERVER_MAX_FAIL_RATE: f64 = 0.5;
 
 /// Remote Server Type
 #[derive(Debug, Clone, Copy)]
@@ -68,6 +73,8 @@ pub struct PingBalancerBuilder {
     max_server_rtt: Duration,
     check_interval: Duration,
     check_best_interval: Option<Duration>,
+    check_target: Option<Arc<HealthCheckTarget>>,
+    preferred_server: Option<ServerAddr>,
 }
 
 impl PingBalancerBuilder {
@@ -79,6 +86,8 @@ impl PingBalancerBuilder {
             max_server_rtt: Duration::from_secs(DEFAULT_CHECK_TIMEOUT_SEC),
             check_interval: Duration::from_secs(DEFAULT_CHECK_INTERVAL_SEC),
             check_best_interval: None,
+            check_target: None,
+            preferred_server: None,
         }
     }
 
@@ -104,7 +113,21 @@ impl PingBalancerBuilder {
         self.check_best_interval = Some(intv);
     }
 
-    fn find_best_idx(servers: &[Arc<ServerIdent>], mode: Mode) -> (usize, usize) {
+    /// Probe TCP connectivity of servers with `target`, instead of the builtin connectivity check
+    pub fn check_target(&mut self, target: HealthCheckTarget) {
+        self.check_target = Some(Arc::new(target));
+    }
+
+    /// Always choose server with `addr` while it is passing health checks
+    pub fn preferred_server(&mut self, addr: ServerAddr) {
+        self.preferred_server = Some(addr);
+    }
+
+    fn find_best_idx(
+        servers: &[Arc<ServerIdent>],
+        mode: Mode,
+        preferred_server: Option<&ServerAddr>,
+    ) -> (usize, usize) {
         if servers.is_empty() {
             trace!("init without any TCP and UDP servers");
             return (0, 0);
@@ -113,9 +136,17 @@ impl PingBalancerBuilder {
         let mut best_tcp_idx = 0;
         let mut best_udp_idx = 0;
 
+        // Try the preferred server first, there are no health check results yet
+        let preferred_idx =
+            preferred_server.and_then(|addr| servers.iter().position(|s| s.server_config().addr() == addr));
+
         if mode.enable_tcp() {
             let mut found_tcp_idx = false;
-            for (idx, server) in servers.iter().enumerate() {
+            for (idx, server) in preferred_idx
+                .iter()
+                .map(|&idx| (idx, &servers[idx]))
+                .chain(servers.iter().enumerate())
+            {
                 if PingBalancerContext::check_server_tcp_enabled(server.server_config()) {
                     best_tcp_idx = idx;
                     found_tcp_idx = true;
@@ -138,7 +169,11 @@ impl PingBalancerBuilder {
 
         if mode.enable_udp() {
             let mut found_udp_idx = false;
-            for (idx, server) in servers.iter().enumerate() {
+            for (idx, server) in preferred_idx
+                .iter()
+                .map(|&idx| (idx, &servers[idx]))
+                .chain(servers.iter().enumerate())
+            {
                 if PingBalancerContext::check_server_udp_enabled(server.server_config()) {
                     best_udp_idx = idx;
                     found_udp_idx = true;
@@ -179,6 +214,8 @@ impl PingBalancerBuilder {
             self.max_server_rtt,
             self.check_interval,
             self.check_best_interval,
+            self.check_target,
+            self.preferred_server,
         )
         .await?;
 
@@ -214,6 +251,8 @@ struct PingBalancerContext {
     max_server_rtt: Duration,
     check_interval: Duration,
     check_best_interval: Option<Duration>,
+    check_target: Option<Arc<HealthCheckTarget>>,
+    preferred_server: Option<ServerAddr>,
     best_task_notify: Notify,
 }
 
@@ -235,6 +274,7 @@ impl PingBalancerContext {
 }
 
 impl PingBalancerContext {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) async fn new(
         mut servers: Vec<Arc<ServerIdent>>,
         context: Arc<ServiceContext>,
@@ -242,6 +282,8 @@ impl PingBalancerContext {
         max_server_rtt: Duration,
         check_interval: Duration,
         check_best_interval: Option<Duration>,
+        check_target: Option<Arc<HealthCheckTarget>>,
+        preferred_server: Option<ServerAddr>,
     ) -> io::Result<(Arc<PingBalancerContext>, PingBalancerContextTask)> {
         let plugin_abortable = {
             // Start plugins for TCP proxies
@@ -304,7 +346,8 @@ impl PingBalancerContext {
             }
         };
 
-        let (best_tcp_idx, best_udp_idx) = PingBalancerBuilder::find_best_idx(&servers, mode);
+        let (best_tcp_idx, best_udp_idx) =
+            PingBalancerBuilder::find_best_idx(&servers, mode, preferred_server.as_ref());
 
         let balancer_context = PingBalancerContext {
             servers,
@@ -315,6 +358,8 @@ impl PingBalancerContext {
             max_server_rtt,
             check_interval,
             check_best_interval,
+            check_target,
+            preferred_server,
             best_task_notify: Notify::new(),
         };
 
@@ -343,12 +388,46 @@ impl PingBalancerContext {
         self.check_once(true).await;
     }
 
+    // Servers out of their validity windows are excluded since the next check
     fn check_server_tcp_enabled(svr_cfg: &ServerConfig) -> bool {
-        svr_cfg.mode().enable_tcp() && svr_cfg.weight().tcp_weight() > 0.0
+        svr_cfg.mode().enable_tcp()
+            && svr_cfg.weight().tcp_weight() > 0.0
+            && svr_cfg.is_currently_valid(SystemTime::now())
     }
 
     fn check_server_udp_enabled(svr_cfg: &ServerConfig) -> bool {
-        svr_cfg.mode().enable_udp() && svr_cfg.weight().udp_weight() > 0.0
+        svr_cfg.mode().enable_udp()
+            && svr_cfg.weight().udp_weight() > 0.0
+            && svr_cfg.is_currently_valid(SystemTime::now())
+    }
+
+    /// Index of the preferred server, if it is serving `server_type` and passing health checks
+    async fn preferred_server_idx(&self, server_type: ServerType) -> Option<usize> {
+        let addr = self.preferred_server.as_ref()?;
+        let idx = self.servers.iter().position(|s| s.server_config().addr() == addr)?;
+
+        let server = &self.servers[idx];
+        let stat = match server_type {
+            ServerType::Tcp if PingBalancerContext::check_server_tcp_enabled(server.server_config()) => {
+                server.tcp_score().stat_data().await
+            }
+            ServerType::Udp if PingBalancerContext::check_server_udp_enabled(server.server_config()) => {
+                server.udp_score().stat_data().await
+            }
+            _ => return None,
+        };
+
+        if stat.fail_rate > PREFERRED_SERVER_MAX_FAIL_RATE {
+            debug!(
+                "preferred {} server {} fail rate {:.2}, fallback to the best server",
+                server_type,
+                ServerConfigFormatter::new(server.server_config()),
+                stat.fail_rate
+            );
+            return None;
+        }
+
+        Some(idx)
     }
 
     fn probing_required(&self) -> bool {
@@ -404,6 +483,7 @@ impl PingBalancerContext {
                     server_type: ServerType::Tcp,
                     context: self.context.clone(),
                     max_server_rtt: self.max_server_rtt,
+                    check_target: self.check_target.clone(),
                 };
                 vfut_tcp.push(checker.check_update_score());
             }
@@ -414,6 +494,7 @@ impl PingBalancerContext {
                     server_type: ServerType::Udp,
                     context: self.context.clone(),
                     max_server_rtt: self.max_server_rtt,
+                    check_target: self.check_target.clone(),
                 };
                 vfut_udp.push(checker.check_update_score());
             }
@@ -443,12 +524,19 @@ impl PingBalancerContext {
             let mut best_idx = 0;
             let mut best_score = u32::MAX;
             for (idx, server) in servers.iter().enumerate() {
+                // Scores of expired servers are not updated anymore
+                if !PingBalancerContext::check_server_tcp_enabled(server.server_config()) {
+                    continue;
+                }
                 let score = server.tcp_score().score();
                 if score < best_score {
                     best_idx = idx;
                     best_score = score;
                 }
             }
+            if let Some(idx) = self.preferred_server_idx(ServerType::Tcp).await {
+                best_idx = idx;
+            }
             self.best_tcp_idx.store(best_idx, Ordering::Release);
 
             if first_run {
@@ -476,12 +564,19 @@ impl PingBalancerContext {
             let mut best_idx = 0;
             let mut best_score = u32::MAX;
             for (idx, server) in servers.iter().enumerate() {
+                // Scores of expired servers are not updated anymore
+                if !PingBalancerContext::check_server_udp_enabled(server.server_config()) {
+                    continue;
+                }
                 let score = server.udp_score().score();
                 if score < best_score {
                     best_idx = idx;
                     best_score = score;
                 }
             }
+            if let Some(idx) = self.preferred_server_idx(ServerType::Udp).await {
+                best_idx = idx;
+            }
             self.best_udp_idx.store(best_idx, Ordering::Release);
 
             if first_run {
@@ -530,6 +625,7 @@ impl PingBalancerContext {
                 server_type: ServerType::Tcp,
                 context: self.context.clone(),
                 max_server_rtt: self.max_server_rtt,
+                check_target: self.check_target.clone(),
             };
             vfut.push(checker.check_update_score());
             check_tcp = true;
@@ -541,6 +637,7 @@ impl PingBalancerContext {
                 server_type: ServerType::Udp,
                 context: self.context.clone(),
                 max_server_rtt: self.max_server_rtt,
+                check_target: self.check_target.clone(),
             };
             vfut.push(checker.check_update_score());
             check_udp = true;
@@ -554,12 +651,19 @@ impl PingBalancerContext {
             let mut best_idx = 0;
             let mut best_score = u32::MAX;
             for (idx, server) in servers.iter().enumerate() {
+                // Scores of expired servers are not updated anymore
+                if !PingBalancerContext::check_server_tcp_enabled(server.server_config()) {
+                    continue;
+                }
                 let score = server.tcp_score().score();
                 if score < best_score {
                     best_idx = idx;
                     best_score = score;
                 }
             }
+            if let Some(idx) = self.preferred_server_idx(ServerType::Tcp).await {
+                best_idx = idx;
+            }
             self.best_tcp_idx.store(best_idx, Ordering::Release);
 
             if best_idx != old_best_idx {
@@ -584,12 +688,19 @@ impl PingBalancerContext {
             let mut best_idx = 0;
             let mut best_score = u32::MAX;
             for (idx, server) in servers.iter().enumerate() {
+                // Scores of expired servers are not updated anymore
+                if !PingBalancerContext::check_server_udp_enabled(server.server_config()) {
+                    continue;
+                }
                 let score = server.udp_score().score();
                 if score < best_score {
                     best_idx = idx;
                     best_score = score;
                 }
             }
+            if let Some(idx) = self.preferred_server_idx(ServerType::Udp).await {
+                best_idx = idx;
+            }
             self.best_udp_idx.store(best_idx, Ordering::Release);
 
             if best_idx != old_best_idx {
@@ -780,6 +891,8 @@ impl PingBalancer {
             old_context.max_server_rtt,
             old_context.check_interval,
             old_context.check_best_interval,
+            old_context.check_target.clone(),
+            old_context.preferred_server.clone(),
         )
         .await?;
 
@@ -813,6 +926,7 @@ struct PingChecker {
     server_type: ServerType,
     context: Arc<ServiceContext>,
     max_server_rtt: Duration,
+    check_target: Option<Arc<HealthCheckTarget>>,
 }
 
 impl PingChecker {
@@ -927,6 +1041,52 @@ impl PingChecker {
         ))
     }
 
+    /// Detect TCP connectivity with a configured HTTP server, any valid HTTP response is accepted
+    async fn check_request_tcp_target(&self, target: &HealthCheckTarget) -> io::Result<()> {
+        use std::io::{Error, ErrorKind};
+
+        let host = match target.addr {
+            Address::SocketAddress(ref sa) => sa.to_string(),
+            Address::DomainNameAddress(ref dn, port) => {
+                if port == 80 {
+                    dn.clone()
+                } else {
+                    format!("{dn}:{port}")
+                }
+            }
+        };
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nAccept: */*\r\n\r\n",
+            target.path, host
+        );
+
+        let mut stream = ProxyClientStream::connect_with_opts(
+            self.context.context(),
+            self.server.server_config(),
+            &target.addr,
+            self.server.connect_opts_ref(),
+        )
+        .await?;
+        stream.write_all(request.as_bytes()).await?;
+
+        let mut reader = BufReader::new(stream);
+
+        let mut buf = Vec::new();
+        reader.read_until(b'\n', &mut buf).await?;
+
+        let mut headers = [httparse::EMPTY_HEADER; 1];
+        let mut response = httparse::Response::new(&mut headers);
+
+        if response.parse(&buf).is_ok() && response.code.is_some() {
+            return Ok(());
+        }
+
+        Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("unexpected response from {}, {:?}", target, ByteStr::new(&buf)),
+        ))
+    }
+
     async fn check_request_udp(&self) -> io::Result<()> {
         // TransactionID: 0x1234
         // Flags: 0x0100 RD
@@ -975,7 +1135,10 @@ impl PingChecker {
 
     async fn check_request(&self) -> io::Result<()> {
         match self.server_type {
-            ServerType::Tcp => self.check_request_tcp_firefox().await,
+            ServerType::Tcp => match self.check_target {
+                Some(ref target) => self.check_request_tcp_target(target).await,
+                None => self.check_request_tcp_firefox().await,
+            },
             ServerType::Udp => self.check_request_udp().await,
         }
     }
@@ -10,15 +10,16 @@ use std::{
 use futures::future;
 use log::trace;
 use shadowsocks::{
-    config::Mode,
+    config::{Mode, ServerAddr},
     net::{AcceptOpts, ConnectOpts},
 };
 
 #[cfg(feature = "local-flow-stat")]
 use crate::{config::LocalFlowStatAddress, net::FlowStat};
 use crate::{
-    config::{Config, ConfigType, ProtocolType},
+    config::{BalancerConfig, Config, ConfigType, HealthCheckConfig, ProtocolType},
     dns::build_dns_resolver,
+    net::RateLimiter,
     utils::ServerHandle,
 };
 
@@ -38,6 +39,8 @@ use self::online_config::{OnlineConfigService, OnlineConfigServiceBuilder};
 #[cfg(feature = "local-redir")]
 use self::redir::{Redir, RedirBuilder};
 use self::socks::{Socks, SocksBuilder};
+#[cfg(unix)]
+use self::socks::{SocksUnixServer, SocksUnixServerBuilder};
 #[cfg(feature = "local-tun")]
 use self::tun::{Tun, TunBuilder};
 #[cfg(feature = "local-tunnel")]
@@ -72,6 +75,8 @@ pub(crate) const LOCAL_DEFAULT_KEEPALIVE_TIMEOUT: Duration = Duration::from_secs
 pub struct Server {
     balancer: PingBalancer,
     socks_servers: Vec<Socks>,
+    #[cfg(unix)]
+    socks_unix_servers: Vec<SocksUnixServer>,
     #[cfg(feature = "local-tunnel")]
     tunnel_servers: Vec<Tunnel>,
     #[cfg(feature = "local-http")]
@@ -90,6 +95,7 @@ pub struct Server {
     flow_stat: Arc<FlowStat>,
     #[cfg(feature = "local-online-config")]
     online_config: Option<OnlineConfigService>,
+    bound_local_addrs: Vec<SocketAddr>,
 }
 
 impl Server {
@@ -131,6 +137,7 @@ impl Server {
             vpn_protect_path: config.outbound_vpn_protect_path,
 
             bind_interface: config.outbound_bind_interface,
+            ttl: config.outbound_ttl,
             bind_local_addr: config.outbound_bind_addr.map(|ip| SocketAddr::new(ip, 0)),
 
             ..Default::default()
@@ -147,6 +154,7 @@ impl Server {
 
         let mut accept_opts = AcceptOpts {
             ipv6_only: config.ipv6_only,
+            reuse_address: config.reuse_address,
             ..Default::default()
         };
         accept_opts.tcp.send_buffer_size = config.inbound_send_buffer_size;
@@ -157,9 +165,17 @@ impl Server {
         accept_opts.tcp.mptcp = config.mptcp;
         accept_opts.udp.mtu = config.udp_mtu;
         context.set_accept_opts(accept_opts);
+        context.set_access_log(config.access_log);
+        if let Some(rate) = config.max_rate_bytes_per_sec.filter(|&rate| rate > 0) {
+            context.set_rate_limiter(Arc::new(RateLimiter::new(rate)));
+        }
+        if let Some(size) = config.udp_max_payload {
+            context.set_udp_max_payload(size);
+        }
 
         if let Some(resolver) = build_dns_resolver(
             config.dns,
+            config.dns_in_order,
             config.ipv6_first,
             config.dns_cache_size,
             context.connect_opts_ref(),
@@ -173,6 +189,10 @@ impl Server {
             context.set_ipv6_first(config.ipv6_first);
         }
 
+        if config.disable_ipv6 {
+            context.set_disable_ipv6(config.disable_ipv6);
+        }
+
         if let Some(acl) = config.acl {
             context.set_acl(Arc::new(acl));
         }
@@ -181,6 +201,14 @@ impl Server {
 
         assert!(!config.local.is_empty(), "no valid local server configuration");
 
+        let preferred_server = match config.preferred_server {
+            None => None,
+            Some(idx) => match config.server.get(idx) {
+                Some(server) => Some(server.config.addr()),
+                None => return Err(io::Error::other(format!("preferred server {idx} is out of range"))),
+            },
+        };
+
         // Create a service balancer for choosing between multiple servers
         let balancer = {
             let mut mode: Option<Mode> = None;
@@ -195,23 +223,16 @@ impl Server {
             let mode = mode.unwrap_or(Mode::TcpOnly);
 
             // Load balancer will hold an individual ServiceContext
-            let mut balancer_builder = PingBalancerBuilder::new(Arc::new(context.clone()), mode);
-
-            // max_server_rtt have to be set before add_server
-            if let Some(rtt) = config.balancer.max_server_rtt {
-                balancer_builder.max_server_rtt(rtt);
-            }
-
-            if let Some(intv) = config.balancer.check_interval {
-                balancer_builder.check_interval(intv);
-            }
-
-            if let Some(intv) = config.balancer.check_best_interval {
-                balancer_builder.check_best_interval(intv);
-            }
-
-            for server in config.server {
-                balancer_builder.add_server(server);
+            let mut balancer_builder = new_balancer_builder(
+                &context,
+                mode,
+                &config.balancer,
+                config.health_check.as_ref(),
+                preferred_server,
+            );
+
+            for server in config.server.iter() {
+                balancer_builder.add_server(server.clone());
             }
 
             balancer_builder.build().await?
@@ -220,6 +241,8 @@ impl Server {
         let mut local_server = Server {
             balancer: balancer.clone(),
             socks_servers: Vec::new(),
+            #[cfg(unix)]
+            socks_unix_servers: Vec::new(),
             #[cfg(feature = "local-tunnel")]
             tunnel_servers: Vec::new(),
             #[cfg(feature = "local-http")]
@@ -251,6 +274,7 @@ impl Server {
                     Some(builder.build().await?)
                 }
             },
+            bound_local_addrs: Vec::new(),
         };
 
         for local_instance in config.local {
@@ -265,11 +289,48 @@ impl Server {
                 context.set_acl(Arc::new(acl))
             }
 
+            // Source IP access control of this local server's listener
+            if let Some(client_acl) = local_config.client_acl {
+                context.set_client_acl(Arc::new(client_acl));
+            }
+
+            // Private server list, only servers listed here could be chosen by this local server
+            let balancer = match local_instance.servers {
+                None => balancer.clone(),
+                Some(ref indices) => {
+                    let mut balancer_builder = new_balancer_builder(
+                        &context,
+                        local_config.mode,
+                        &config.balancer,
+                        config.health_check.as_ref(),
+                        preferred_server,
+                    );
+
+                    for &idx in indices {
+                        match config.server.get(idx) {
+                            Some(server) => balancer_builder.add_server(server.clone()),
+                            None => return Err(io::Error::other(format!("local server index {idx} is out of range"))),
+                        }
+                    }
+
+                    balancer_builder.build().await?
+                }
+            };
+
             let context = Arc::new(context);
-            let balancer = balancer.clone();
 
             match local_config.protocol {
                 ProtocolType::Socks => {
+                    #[cfg(unix)]
+                    if let Some(path) = local_config.unix_addr {
+                        let mut server_builder = SocksUnixServerBuilder::new(context.clone(), path, balancer);
+                        server_builder.set_socks5_auth(local_config.socks5_auth);
+
+                        let server = server_builder.build().await?;
+                        local_server.socks_unix_servers.push(server);
+                        continue;
+                    }
+
                     let client_addr = match local_config.addr {
                         Some(a) => a,
                         None => return Err(io::Error::new(ErrorKind::Other, "socks requires local address")),
@@ -278,6 +339,10 @@ impl Server {
                     let mut server_builder = SocksBuilder::with_context(context.clone(), client_addr, balancer);
                     server_builder.set_mode(local_config.mode);
                     server_builder.set_socks5_auth(local_config.socks5_auth);
+                    #[cfg(feature = "local-http")]
+                    if let Some((username, password)) = local_config.http_proxy_auth {
+                        server_builder.set_http_auth(username, password);
+                    }
 
                     if let Some(c) = config.udp_max_associations {
                         server_builder.set_udp_capacity(c);
@@ -290,6 +355,8 @@ impl Server {
                     }
                     if let Some(b) = local_config.udp_associate_addr {
                         server_builder.set_udp_associate_addr(b.clone());
+                    } else if let Some(b) = config.udp_bind_addr {
+                        server_builder.set_udp_associate_addr(ServerAddr::from(b));
                     }
 
                     #[cfg(target_os = "macos")]
@@ -302,6 +369,12 @@ impl Server {
                     }
 
                     let server = server_builder.build().await?;
+                    let bound_addr = server
+                        .tcp_server()
+                        .map(|s| s.local_addr())
+                        .or_else(|| server.udp_server().map(|s| s.local_addr()))
+                        .transpose()?;
+                    local_server.bound_local_addrs.extend(bound_addr);
                     local_server.socks_servers.push(server);
                 }
                 #[cfg(feature = "local-tunnel")]
@@ -337,6 +410,12 @@ impl Server {
                     }
 
                     let server = server_builder.build().await?;
+                    let bound_addr = server
+                        .tcp_server()
+                        .map(|s| s.local_addr())
+                        .or_else(|| server.udp_server().map(|s| s.local_addr()))
+                        .transpose()?;
+                    local_server.bound_local_addrs.extend(bound_addr);
                     local_server.tunnel_servers.push(server);
                 }
                 #[cfg(feature = "local-http")]
@@ -346,15 +425,19 @@ impl Server {
                         None => return Err(io::Error::new(ErrorKind::Other, "http requires local address")),
                     };
 
-                    #[allow(unused_mut)]
                     let mut builder = HttpBuilder::with_context(context.clone(), client_addr, balancer);
 
+                    if let Some((username, password)) = local_config.http_proxy_auth {
+                        builder.set_auth(username, password);
+                    }
+
                     #[cfg(target_os = "macos")]
                     if let Some(n) = local_config.launchd_tcp_socket_name {
                         builder.set_launchd_tcp_socket_name(n);
                     }
 
                     let server = builder.build().await?;
+                    local_server.bound_local_addrs.push(server.local_addr()?);
                     local_server.http_servers.push(server);
                 }
                 #[cfg(feature = "local-redir")]
@@ -532,6 +615,11 @@ impl Server {
             vfut.push(ServerHandle(tokio::spawn(svr.run())));
         }
 
+        #[cfg(unix)]
+        for svr in self.socks_unix_servers {
+            vfut.push(ServerHandle(tokio::spawn(svr.run())));
+        }
+
         #[cfg(feature = "local-tunnel")]
         for svr in self.tunnel_servers {
             vfut.push(ServerHandle(tokio::spawn(svr.run())));
@@ -579,6 +667,21 @@ impl Server {
         res
     }
 
+    /// Address of the first SOCKS, HTTP or tunnel local server, after binding
+    ///
+    /// Local servers with `local_port` 0 listen on ports assigned by the system, which are only known after
+    /// [`Server::new`] returns. Get them here before calling [`Server::run`].
+    pub fn bound_local_addr(&self) -> Option<SocketAddr> {
+        self.bound_local_addrs.first().copied()
+    }
+
+    /// Addresses of SOCKS, HTTP and tunnel local servers after binding, in the order of `locals`
+    ///
+    /// The TCP listener's address is used, or the UDP one if the local server is `udp_only`.
+    pub fn bound_local_addrs(&self) -> &[SocketAddr] {
+        &self.bound_local_addrs
+    }
+
     /// Get the internal server balancer
     pub fn server_balancer(&self) -> &PingBalancer {
         &self.balancer
@@ -589,6 +692,12 @@ impl Server {
         &self.socks_servers
     }
 
+    /// Get SOCKS server instances listening on Unix Domain Sockets
+    #[cfg(unix)]
+    pub fn socks_unix_servers(&self) -> &[SocksUnixServer] {
+        &self.socks_unix_servers
+    }
+
     /// Get Tunnel server instances
     #[cfg(feature = "local-tunnel")]
     pub fn tunnel_servers(&self) -> &[Tunnel] {
@@ -626,6 +735,48 @@ impl Server {
     }
 }
 
+fn new_balancer_builder(
+    context: &ServiceContext,
+    mode: Mode,
+    config: &BalancerConfig,
+    health_check: Option<&HealthCheckConfig>,
+    preferred_server: Option<&ServerAddr>,
+) -> PingBalancerBuilder {
+    let mut balancer_builder = PingBalancerBuilder::new(Arc::new(context.clone()), mode);
+
+    // max_server_rtt have to be set before add_server
+    if let Some(rtt) = config.max_server_rtt {
+        balancer_builder.max_server_rtt(rtt);
+    }
+
+    if let Some(intv) = config.check_interval {
+        balancer_builder.check_interval(intv);
+    }
+
+    if let Some(intv) = config.check_best_interval {
+        balancer_builder.check_best_interval(intv);
+    }
+
+    // health_check takes precedence over the balancer's own settings
+    if let Some(health_check) = health_check {
+        if let Some(timeout) = health_check.timeout {
+            balancer_builder.max_server_rtt(timeout);
+        }
+        if let Some(intv) = health_check.interval {
+            balancer_builder.check_interval(intv);
+        }
+        if let Some(ref target) = health_check.target {
+            balancer_builder.check_target(target.clone());
+        }
+    }
+
+    if let Some(addr) = preferred_server {
+        balancer_builder.preferred_server(addr.clone());
+    }
+
+    balancer_builder
+}
+
 #[cfg(feature = "local-flow-stat")]
 async fn flow_report_task(stat_addr: LocalFlowStatAddress, flow_stat: Arc<FlowStat>) -> io::Result<()> {
     use std::slice;
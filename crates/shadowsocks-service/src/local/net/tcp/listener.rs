@@ -7,15 +7,30 @@ use shadowsocks::{config::ServerAddr, lookup_then, net::TcpListener};
 use crate::local::context::ServiceContext;
 
 /// Create a standard TCP listener listening on `client_config`
+///
+/// Domain names in `client_config` are resolved here, the first address that could be listened on is used.
 pub async fn create_standard_tcp_listener(
     context: &ServiceContext,
     client_config: &ServerAddr,
 ) -> io::Result<TcpListener> {
     match client_config {
         ServerAddr::SocketAddr(saddr) => TcpListener::bind_with_opts(saddr, context.accept_opts()).await,
-        ServerAddr::DomainName(dname, port) => lookup_then!(context.context_ref(), dname, *port, |addr| {
-            TcpListener::bind_with_opts(&addr, context.accept_opts()).await
-        })
-        .map(|(_, l)| l),
+        ServerAddr::DomainName(dname, port) => {
+            // Wrapped in an async block for catching errors of resolving `dname`
+            let result: io::Result<_> = async {
+                lookup_then!(context.context_ref(), dname, *port, |addr| {
+                    TcpListener::bind_with_opts(&addr, context.accept_opts()).await
+                })
+            }
+            .await;
+
+            match result {
+                Ok((_, l)) => Ok(l),
+                Err(err) => Err(io::Error::new(
+                    err.kind(),
+                    format!("failed to listen on local address {dname}:{port}, error: {err}"),
+                )),
+            }
+        }
     }
 }
@@ -286,6 +286,7 @@ where
         let mut bypassed_ipv4_buffer = Vec::new();
         let mut bypassed_ipv6_buffer = Vec::new();
         let mut proxied_buffer = Vec::new();
+        let max_payload = self.context.udp_max_payload();
         let mut keepalive_interval = time::interval(Duration::from_secs(1));
 
         loop {
@@ -302,7 +303,7 @@ where
                     self.dispatch_received_packet(&target_addr, &data).await;
                 }
 
-                received_opt = receive_from_bypassed_opt(&self.bypassed_ipv4_socket, &mut bypassed_ipv4_buffer), if self.bypassed_ipv4_socket.is_some() => {
+                received_opt = receive_from_bypassed_opt(&self.bypassed_ipv4_socket, &mut bypassed_ipv4_buffer, max_payload), if self.bypassed_ipv4_socket.is_some() => {
                     let (n, addr) = match received_opt {
                         Ok(r) => r,
                         Err(err) => {
@@ -317,7 +318,7 @@ where
                     self.send_received_respond_packet(&addr, &bypassed_ipv4_buffer[..n], true).await;
                 }
 
-                received_opt = receive_from_bypassed_opt(&self.bypassed_ipv6_socket, &mut bypassed_ipv6_buffer), if self.bypassed_ipv6_socket.is_some() => {
+                received_opt = receive_from_bypassed_opt(&self.bypassed_ipv6_socket, &mut bypassed_ipv6_buffer, max_payload), if self.bypassed_ipv6_socket.is_some() => {
                     let (n, addr) = match received_opt {
                         Ok(r) => r,
                         Err(err) => {
@@ -391,12 +392,13 @@ where
         async fn receive_from_bypassed_opt(
             socket: &Option<ShadowUdpSocket>,
             buf: &mut Vec<u8>,
+            max_payload: usize,
         ) -> io::Result<(usize, SocketAddr)> {
             match *socket {
                 None => future::pending().await,
                 Some(ref s) => {
                     if buf.is_empty() {
-                        buf.resize(MAXIMUM_UDP_PAYLOAD_SIZE, 0);
+                        buf.resize(max_payload, 0);
                     }
                     s.recv_from(buf).await
                 }
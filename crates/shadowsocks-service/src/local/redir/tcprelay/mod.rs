@@ -39,9 +39,13 @@ async fn establish_client_tcp_redir(
     peer_addr: SocketAddr,
     addr: &Address,
 ) -> io::Result<()> {
+    let access_log = context.access_log();
+    let rate_limiter = context.rate_limiter();
+
     if balancer.is_empty() {
         let mut remote = AutoProxyClientStream::connect_bypassed(context, addr).await?;
-        return establish_tcp_tunnel_bypassed(&mut stream, &mut remote, peer_addr, addr).await;
+        return establish_tcp_tunnel_bypassed(&mut stream, &mut remote, peer_addr, addr, access_log, rate_limiter)
+            .await;
     }
 
     let server = balancer.best_tcp_server();
@@ -50,7 +54,16 @@ async fn establish_client_tcp_redir(
     let mut remote =
         AutoProxyClientStream::connect_with_opts(context, &server, addr, server.connect_opts_ref()).await?;
 
-    establish_tcp_tunnel(svr_cfg, &mut stream, &mut remote, peer_addr, addr).await
+    establish_tcp_tunnel(
+        svr_cfg,
+        &mut stream,
+        &mut remote,
+        peer_addr,
+        addr,
+        access_log,
+        rate_limiter,
+    )
+    .await
 }
 
 async fn handle_redir_client(
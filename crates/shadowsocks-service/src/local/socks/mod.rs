@@ -1,5 +1,7 @@
 //! Shadowsocks SOCKS (4/4a, 5) Local Server
 
+#[cfg(unix)]
+pub use self::server::{SocksUnixServer, SocksUnixServerBuilder};
 pub use self::server::{Socks, SocksBuilder};
 
 pub mod client;
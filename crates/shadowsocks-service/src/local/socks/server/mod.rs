@@ -8,6 +8,8 @@ use shadowsocks::{config::Mode, ServerAddr};
 use crate::local::{context::ServiceContext, loadbalancing::PingBalancer};
 
 pub use self::server::{SocksTcpServer, SocksTcpServerBuilder, SocksUdpServer};
+#[cfg(unix)]
+pub use self::unix::{SocksUnixServer, SocksUnixServerBuilder};
 use self::socks5::Socks5UdpServerBuilder;
 
 use super::config::Socks5AuthConfig;
@@ -17,6 +19,8 @@ mod server;
 #[cfg(feature = "local-socks4")]
 mod socks4;
 mod socks5;
+#[cfg(unix)]
+mod unix;
 
 /// SOCKS4/4a, SOCKS5 Local Server builder
 pub struct SocksBuilder {
@@ -27,6 +31,8 @@ pub struct SocksBuilder {
     udp_bind_addr: Option<ServerAddr>,
     udp_associate_addr: Option<ServerAddr>,
     socks5_auth: Socks5AuthConfig,
+    #[cfg(feature = "local-http")]
+    http_auth: Option<(String, String)>,
     client_config: ServerAddr,
     balancer: PingBalancer,
     #[cfg(target_os = "macos")]
@@ -56,6 +62,8 @@ impl SocksBuilder {
             udp_bind_addr: None,
             udp_associate_addr: None,
             socks5_auth: Socks5AuthConfig::default(),
+            #[cfg(feature = "local-http")]
+            http_auth: None,
             client_config,
             balancer,
             #[cfg(target_os = "macos")]
@@ -97,6 +105,12 @@ impl SocksBuilder {
         self.socks5_auth = p;
     }
 
+    /// Require HTTP clients sniffed on this port to authenticate with HTTP Basic authentication
+    #[cfg(feature = "local-http")]
+    pub fn set_http_auth(&mut self, username: String, password: String) {
+        self.http_auth = Some((username, password));
+    }
+
     /// macOS launchd activate socket
     #[cfg(target_os = "macos")]
     pub fn set_launchd_tcp_socket_name(&mut self, n: String) {
@@ -111,7 +125,7 @@ impl SocksBuilder {
 
     pub async fn build(self) -> io::Result<Socks> {
         let udp_bind_addr = self.udp_bind_addr.clone().unwrap_or_else(|| self.client_config.clone());
-        let udp_associate_addr: ServerAddr = self
+        let mut udp_associate_addr: ServerAddr = self
             .udp_associate_addr
             .as_ref()
             .or(self.udp_bind_addr.as_ref())
@@ -135,6 +149,14 @@ impl SocksBuilder {
             }
 
             let server = builder.build().await?;
+
+            // Port assigned by the system, clients have to know the actual one
+            if let ServerAddr::SocketAddr(ref mut sa) = udp_associate_addr {
+                if sa.port() == 0 {
+                    sa.set_port(server.local_addr()?.port());
+                }
+            }
+
             udp_server = Some(server);
         }
 
@@ -150,6 +172,11 @@ impl SocksBuilder {
                 self.socks5_auth,
             );
 
+            #[cfg(feature = "local-http")]
+            if let Some((username, password)) = self.http_auth {
+                builder.set_http_auth(username, password);
+            }
+
             #[cfg(target_os = "macos")]
             if let Some(s) = self.launchd_tcp_socket_name {
                 builder.set_launchd_socket_name(s);
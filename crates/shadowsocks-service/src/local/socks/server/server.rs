@@ -1,6 +1,6 @@
 use std::{io, net::SocketAddr, sync::Arc, time::Duration};
 
-use log::{error, info};
+use log::{error, info, warn};
 use shadowsocks::{config::Mode, net::TcpListener as ShadowTcpListener, ServerAddr};
 use tokio::{net::TcpStream, time};
 
@@ -22,6 +22,8 @@ pub struct SocksTcpServerBuilder {
     balancer: PingBalancer,
     mode: Mode,
     socks5_auth: Arc<Socks5AuthConfig>,
+    #[cfg(feature = "local-http")]
+    http_auth: Option<(String, String)>,
     #[cfg(target_os = "macos")]
     launchd_socket_name: Option<String>,
 }
@@ -42,11 +44,19 @@ impl SocksTcpServerBuilder {
             balancer,
             mode,
             socks5_auth: Arc::new(socks5_auth),
+            #[cfg(feature = "local-http")]
+            http_auth: None,
             #[cfg(target_os = "macos")]
             launchd_socket_name: None,
         }
     }
 
+    /// Require HTTP clients to authenticate with HTTP Basic authentication
+    #[cfg(feature = "local-http")]
+    pub fn set_http_auth(&mut self, username: String, password: String) {
+        self.http_auth = Some((username, password));
+    }
+
     /// macOS launchd activate socket
     #[cfg(target_os = "macos")]
     pub fn set_launchd_socket_name(&mut self, n: String) {
@@ -78,6 +88,8 @@ impl SocksTcpServerBuilder {
             balancer: self.balancer,
             mode: self.mode,
             socks5_auth: self.socks5_auth,
+            #[cfg(feature = "local-http")]
+            http_auth: self.http_auth,
         })
     }
 }
@@ -90,6 +102,8 @@ pub struct SocksTcpServer {
     balancer: PingBalancer,
     mode: Mode,
     socks5_auth: Arc<Socks5AuthConfig>,
+    #[cfg(feature = "local-http")]
+    http_auth: Option<(String, String)>,
 }
 
 impl SocksTcpServer {
@@ -105,7 +119,13 @@ impl SocksTcpServer {
         // If UDP is enabled, SOCK5 UDP_ASSOCIATE command will let client to send requests to this address
         let udp_associate_addr = Arc::new(self.udp_associate_addr);
         #[cfg(feature = "local-http")]
-        let http_handler = HttpConnectionHandler::new(self.context.clone(), self.balancer.clone());
+        let http_handler = {
+            let mut handler = HttpConnectionHandler::new(self.context.clone(), self.balancer.clone());
+            if let Some((username, password)) = self.http_auth {
+                handler.set_auth(username, password);
+            }
+            handler
+        };
 
         loop {
             let (stream, peer_addr) = match self.listener.accept().await {
@@ -117,6 +137,11 @@ impl SocksTcpServer {
                 }
             };
 
+            if !self.context.check_client_allowed(&peer_addr) {
+                warn!("socks client {} rejected by local_client_acl", peer_addr);
+                continue;
+            }
+
             let handler = SocksTcpHandler {
                 context: self.context.clone(),
                 udp_associate_addr: udp_associate_addr.clone(),
@@ -163,6 +188,7 @@ impl SocksTcpHandler {
         handler.handle_socks5_client(self.stream, self.peer_addr).await
     }
 
+    /// Dispatch by the first byte: `0x04` SOCKS4, `0x05` SOCKS5, ASCII letters of HTTP methods to HTTP
     #[cfg(any(feature = "local-socks4", feature = "local-http"))]
     async fn handle_tcp_client(self) -> io::Result<()> {
         use std::io::ErrorKind;
@@ -91,6 +91,8 @@ impl Socks4TcpHandler {
         }
 
         let target_addr = target_addr.into();
+        let access_log = self.context.access_log();
+        let rate_limiter = self.context.rate_limiter();
         let mut server_opt = None;
         let server_result = if self.balancer.is_empty() {
             AutoProxyClientStream::connect_bypassed(self.context, &target_addr).await
@@ -145,9 +147,28 @@ impl Socks4TcpHandler {
         match server_opt {
             Some(server) => {
                 let svr_cfg = server.server_config();
-                establish_tcp_tunnel(svr_cfg, &mut stream, &mut remote, peer_addr, &target_addr).await
+                establish_tcp_tunnel(
+                    svr_cfg,
+                    &mut stream,
+                    &mut remote,
+                    peer_addr,
+                    &target_addr,
+                    access_log,
+                    rate_limiter,
+                )
+                .await
+            }
+            None => {
+                establish_tcp_tunnel_bypassed(
+                    &mut stream,
+                    &mut remote,
+                    peer_addr,
+                    &target_addr,
+                    access_log,
+                    rate_limiter,
+                )
+                .await
             }
-            None => establish_tcp_tunnel_bypassed(&mut stream, &mut remote, peer_addr, &target_addr).await,
         }
     }
 }
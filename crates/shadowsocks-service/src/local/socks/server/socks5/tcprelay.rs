@@ -16,7 +16,7 @@ use shadowsocks::{
     },
     ServerAddr,
 };
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncWrite};
 
 use crate::{
     local::{
@@ -54,7 +54,10 @@ impl Socks5TcpHandler {
         }
     }
 
-    async fn check_auth(&self, stream: &mut TcpStream, handshake_req: &HandshakeRequest) -> io::Result<()> {
+    async fn check_auth<S>(&self, stream: &mut S, handshake_req: &HandshakeRequest) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
         use std::io::Error;
 
         let allow_none = !self.auth.auth_required();
@@ -96,7 +99,10 @@ impl Socks5TcpHandler {
         ))
     }
 
-    async fn check_auth_password(&self, stream: &mut TcpStream) -> io::Result<()> {
+    async fn check_auth_password<S>(&self, stream: &mut S) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
         use std::io::Error;
 
         const PASSWORD_AUTH_STATUS_FAILURE: u8 = 255;
@@ -169,7 +175,10 @@ impl Socks5TcpHandler {
         }
     }
 
-    pub async fn handle_socks5_client(self, mut stream: TcpStream, peer_addr: SocketAddr) -> io::Result<()> {
+    pub async fn handle_socks5_client<S>(self, mut stream: S, peer_addr: SocketAddr) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
         // 1. Handshake
 
         let handshake_req = match HandshakeRequest::read_from(&mut stream).await {
@@ -224,12 +233,10 @@ impl Socks5TcpHandler {
         }
     }
 
-    async fn handle_tcp_connect(
-        self,
-        mut stream: TcpStream,
-        peer_addr: SocketAddr,
-        target_addr: Address,
-    ) -> io::Result<()> {
+    async fn handle_tcp_connect<S>(self, mut stream: S, peer_addr: SocketAddr, target_addr: Address) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
         if !self.mode.enable_tcp() {
             warn!("TCP CONNECT is disabled");
 
@@ -239,6 +246,9 @@ impl Socks5TcpHandler {
             return Ok(());
         }
 
+        let access_log = self.context.access_log();
+
+        let rate_limiter = self.context.rate_limiter();
         let mut server_opt = None;
         let remote_result = if self.balancer.is_empty() {
             AutoProxyClientStream::connect_bypassed(self.context.clone(), &target_addr).await
@@ -286,13 +296,35 @@ impl Socks5TcpHandler {
         match server_opt {
             Some(server) => {
                 let svr_cfg = server.server_config();
-                establish_tcp_tunnel(svr_cfg, &mut stream, &mut remote, peer_addr, &target_addr).await
+                establish_tcp_tunnel(
+                    svr_cfg,
+                    &mut stream,
+                    &mut remote,
+                    peer_addr,
+                    &target_addr,
+                    access_log,
+                    rate_limiter,
+                )
+                .await
+            }
+            None => {
+                establish_tcp_tunnel_bypassed(
+                    &mut stream,
+                    &mut remote,
+                    peer_addr,
+                    &target_addr,
+                    access_log,
+                    rate_limiter,
+                )
+                .await
             }
-            None => establish_tcp_tunnel_bypassed(&mut stream, &mut remote, peer_addr, &target_addr).await,
         }
     }
 
-    async fn handle_udp_associate(self, mut stream: TcpStream, client_addr: Address) -> io::Result<()> {
+    async fn handle_udp_associate<S>(self, mut stream: S, client_addr: Address) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
         if !self.mode.enable_udp() {
             warn!("socks5 udp is disabled");
 
@@ -0,0 +1,135 @@
+//! SOCKS5 Local Server listening on a Unix Domain Socket
+
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    net::{Ipv4Addr, SocketAddr},
+    os::unix::fs::FileTypeExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use log::{error, info};
+use shadowsocks::{config::Mode, ServerAddr};
+use tokio::{net::UnixListener, time};
+
+use crate::local::{context::ServiceContext, loadbalancing::PingBalancer, socks::config::Socks5AuthConfig};
+
+use super::socks5::Socks5TcpHandler;
+
+/// SOCKS5 Unix Domain Socket server builder
+pub struct SocksUnixServerBuilder {
+    context: Arc<ServiceContext>,
+    path: PathBuf,
+    balancer: PingBalancer,
+    socks5_auth: Socks5AuthConfig,
+}
+
+impl SocksUnixServerBuilder {
+    /// Create a new SOCKS5 server listening on `path`
+    pub fn new(context: Arc<ServiceContext>, path: PathBuf, balancer: PingBalancer) -> SocksUnixServerBuilder {
+        SocksUnixServerBuilder {
+            context,
+            path,
+            balancer,
+            socks5_auth: Socks5AuthConfig::default(),
+        }
+    }
+
+    /// Set SOCKS5 Username/Password Authentication configuration
+    pub fn set_socks5_auth(&mut self, p: Socks5AuthConfig) {
+        self.socks5_auth = p;
+    }
+
+    pub async fn build(self) -> io::Result<SocksUnixServer> {
+        // Socket file left by the previous process, never remove the other kinds of files
+        match fs::symlink_metadata(&self.path) {
+            Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(&self.path)?,
+            Ok(..) => {
+                error!("uds path \"{}\" exists and is not a socket", self.path.display());
+                return Err(io::Error::new(
+                    ErrorKind::AddrInUse,
+                    format!("{} exists and is not a socket", self.path.display()),
+                ));
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+
+        let listener = match UnixListener::bind(&self.path) {
+            Ok(l) => l,
+            Err(err) => {
+                error!("failed to bind uds path \"{}\", error: {}", self.path.display(), err);
+                return Err(err);
+            }
+        };
+
+        Ok(SocksUnixServer {
+            context: self.context,
+            listener,
+            path: self.path,
+            balancer: self.balancer,
+            socks5_auth: Arc::new(self.socks5_auth),
+        })
+    }
+}
+
+/// SOCKS5 Unix Domain Socket server instance
+///
+/// Only TCP CONNECT is supported, there is no UDP relay for clients of Unix Domain Sockets.
+pub struct SocksUnixServer {
+    context: Arc<ServiceContext>,
+    listener: UnixListener,
+    path: PathBuf,
+    balancer: PingBalancer,
+    socks5_auth: Arc<Socks5AuthConfig>,
+}
+
+impl SocksUnixServer {
+    /// Get the listening socket path
+    pub fn local_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Start accept loop
+    pub async fn run(self) -> io::Result<()> {
+        info!("shadowsocks socks unix listening on {}", self.path.display());
+
+        // Clients of Unix Domain Sockets don't have IP addresses
+        let peer_addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0);
+        let udp_associate_addr = Arc::new(ServerAddr::from(peer_addr));
+
+        loop {
+            let (stream, _) = match self.listener.accept().await {
+                Ok(s) => s,
+                Err(err) => {
+                    error!("accept failed with error: {}", err);
+                    time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+
+            let handler = Socks5TcpHandler::new(
+                self.context.clone(),
+                udp_associate_addr.clone(),
+                self.balancer.clone(),
+                Mode::TcpOnly,
+                self.socks5_auth.clone(),
+            );
+
+            tokio::spawn(async move {
+                if let Err(err) = handler.handle_socks5_client(stream, peer_addr).await {
+                    error!("socks5 unix client handler error: {}", err);
+                }
+            });
+        }
+    }
+}
+
+impl Drop for SocksUnixServer {
+    fn drop(&mut self) {
+        // Remove the socket file created by `bind`
+        let _ = fs::remove_file(&self.path);
+    }
+}
@@ -2,7 +2,7 @@
 
 use std::{io, net::SocketAddr, sync::Arc, time::Duration};
 
-use log::{error, info, trace};
+use log::{error, info, trace, warn};
 use shadowsocks::{net::TcpListener as ShadowTcpListener, relay::socks5::Address, ServerAddr};
 use tokio::{net::TcpStream, time};
 
@@ -101,6 +101,11 @@ impl TunnelTcpServer {
                 }
             };
 
+            if !self.context.check_client_allowed(&peer_addr) {
+                warn!("tunnel client {} rejected by local_client_acl", peer_addr);
+                continue;
+            }
+
             tokio::spawn(handle_tcp_client(
                 self.context.clone(),
                 stream,
@@ -120,12 +125,22 @@ async fn handle_tcp_client(
     forward_addr: Arc<Address>,
 ) -> io::Result<()> {
     let forward_addr: &Address = &forward_addr;
+    let access_log = context.access_log();
+    let rate_limiter = context.rate_limiter();
 
     if balancer.is_empty() {
         trace!("establishing tcp tunnel {} <-> {} direct", peer_addr, forward_addr);
 
         let mut remote = AutoProxyClientStream::connect_bypassed(context, forward_addr).await?;
-        return establish_tcp_tunnel_bypassed(&mut stream, &mut remote, peer_addr, forward_addr).await;
+        return establish_tcp_tunnel_bypassed(
+            &mut stream,
+            &mut remote,
+            peer_addr,
+            forward_addr,
+            access_log,
+            rate_limiter,
+        )
+        .await;
     }
 
     let server = balancer.best_tcp_server();
@@ -141,5 +156,14 @@ async fn handle_tcp_client(
     let mut remote =
         AutoProxyClientStream::connect_proxied_with_opts(context, &server, forward_addr, server.connect_opts_ref())
             .await?;
-    establish_tcp_tunnel(svr_cfg, &mut stream, &mut remote, peer_addr, forward_addr).await
+    establish_tcp_tunnel(
+        svr_cfg,
+        &mut stream,
+        &mut remote,
+        peer_addr,
+        forward_addr,
+        access_log,
+        rate_limiter,
+    )
+    .await
 }
@@ -1,8 +1,14 @@
 //! Shadowsocks Local Utilities
 
-use std::{io, net::SocketAddr, time::Duration};
+use std::{
+    fmt::{self, Display},
+    io,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use log::{debug, trace};
+use log::{debug, info, trace};
 use shadowsocks::{
     config::ServerConfig,
     relay::{socks5::Address, tcprelay::utils::copy_encrypted_bidirectional},
@@ -12,7 +18,47 @@ use tokio::{
     time,
 };
 
-use crate::local::net::AutoProxyIo;
+use crate::{
+    local::net::AutoProxyIo,
+    net::{IdleTimeoutStream, RateLimitedStream, RateLimiter},
+};
+
+/// Summary of a completed TCP tunnel, logged as `key=value` pairs if `access_log` is enabled
+#[derive(Debug)]
+pub(crate) struct ConnectionStats<'a> {
+    pub peer_addr: SocketAddr,
+    pub target_addr: &'a Address,
+    /// Chosen server, `None` if bypassed
+    pub server: Option<&'a ServerConfig>,
+    /// Bytes sent to and received from `target_addr`, `None` if the tunnel was closed with an error
+    pub transferred: Option<(u64, u64)>,
+    pub duration: Duration,
+}
+
+impl ConnectionStats<'_> {
+    /// Log with target `shadowsocks_service::access_log`
+    pub fn log(&self) {
+        info!(target: "shadowsocks_service::access_log", "{}", self);
+    }
+}
+
+impl Display for ConnectionStats<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "peer={} target={}", self.peer_addr, self.target_addr)?;
+        match self.server {
+            None => f.write_str(" server=-")?,
+            Some(svr_cfg) => match svr_cfg.remarks() {
+                Some(remarks) => write!(f, " server={remarks:?}")?,
+                None => write!(f, " server={}", svr_cfg.addr())?,
+            },
+        }
+        match self.transferred {
+            Some((up, down)) => write!(f, " up={up} down={down}")?,
+            None => f.write_str(" up=- down=-")?,
+        }
+        write!(f, " duration_ms={}", self.duration.as_millis())
+    }
+}
 
 pub(crate) async fn establish_tcp_tunnel<P, S>(
     svr_cfg: &ServerConfig,
@@ -20,6 +66,8 @@ pub(crate) async fn establish_tcp_tunnel<P, S>(
     shadow: &mut S,
     peer_addr: SocketAddr,
     target_addr: &Address,
+    access_log: bool,
+    rate_limiter: Option<Arc<RateLimiter>>,
 ) -> io::Result<()>
 where
     P: AsyncRead + AsyncWrite + Unpin,
@@ -34,9 +82,15 @@ where
             svr_cfg.addr(),
         );
     } else {
-        return establish_tcp_tunnel_bypassed(plain, shadow, peer_addr, target_addr).await;
+        return establish_tcp_tunnel_bypassed(plain, shadow, peer_addr, target_addr, access_log, rate_limiter).await;
     }
 
+    // Both directions' traffic goes through `plain`
+    let plain = IdleTimeoutStream::new(plain, svr_cfg.idle_timeout());
+    let mut plain = RateLimitedStream::new(plain, rate_limiter);
+
+    let start = Instant::now();
+
     // https://github.com/shadowsocks/shadowsocks-rust/issues/232
     //
     // Protocols like FTP, clients will wait for servers to send Welcome Message without sending anything.
@@ -67,7 +121,7 @@ where
         }
     }
 
-    match copy_encrypted_bidirectional(svr_cfg.method(), shadow, plain).await {
+    let transferred = match copy_encrypted_bidirectional(svr_cfg.method(), shadow, &mut plain).await {
         Ok((wn, rn)) => {
             trace!(
                 "tcp tunnel {} <-> {} (proxied) closed, L2R {} bytes, R2L {} bytes",
@@ -76,6 +130,7 @@ where
                 rn,
                 wn
             );
+            Some((rn, wn))
         }
         Err(err) => {
             trace!(
@@ -84,7 +139,19 @@ where
                 target_addr,
                 err
             );
+            None
+        }
+    };
+
+    if access_log {
+        ConnectionStats {
+            peer_addr,
+            target_addr,
+            server: Some(svr_cfg),
+            transferred,
+            duration: start.elapsed(),
         }
+        .log();
     }
 
     Ok(())
@@ -95,6 +162,8 @@ pub(crate) async fn establish_tcp_tunnel_bypassed<P, S>(
     shadow: &mut S,
     peer_addr: SocketAddr,
     target_addr: &Address,
+    access_log: bool,
+    rate_limiter: Option<Arc<RateLimiter>>,
 ) -> io::Result<()>
 where
     P: AsyncRead + AsyncWrite + Unpin,
@@ -102,7 +171,10 @@ where
 {
     debug!("established tcp tunnel {} <-> {} bypassed", peer_addr, target_addr);
 
-    match copy_bidirectional(plain, shadow).await {
+    let mut plain = RateLimitedStream::new(plain, rate_limiter);
+
+    let start = Instant::now();
+    let transferred = match copy_bidirectional(&mut plain, shadow).await {
         Ok((rn, wn)) => {
             trace!(
                 "tcp tunnel {} <-> {} (bypassed) closed, L2R {} bytes, R2L {} bytes",
@@ -111,6 +183,7 @@ where
                 rn,
                 wn
             );
+            Some((rn, wn))
         }
         Err(err) => {
             trace!(
@@ -119,7 +192,19 @@ where
                 target_addr,
                 err
             );
+            None
+        }
+    };
+
+    if access_log {
+        ConnectionStats {
+            peer_addr,
+            target_addr,
+            server: None,
+            transferred,
+            duration: start.elapsed(),
         }
+        .log();
     }
 
     Ok(())
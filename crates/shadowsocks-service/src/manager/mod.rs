@@ -42,6 +42,7 @@ pub async fn run(config: Config) -> io::Result<()> {
 
         bind_local_addr: config.outbound_bind_addr.map(|ip| SocketAddr::new(ip, 0)),
         bind_interface: config.outbound_bind_interface,
+        ttl: config.outbound_ttl,
 
         ..Default::default()
     };
@@ -57,6 +58,8 @@ pub async fn run(config: Config) -> io::Result<()> {
 
     let mut accept_opts = AcceptOpts {
         ipv6_only: config.ipv6_only,
+        reuse_port: config.reuse_port,
+        reuse_address: config.reuse_address,
         ..Default::default()
     };
     accept_opts.tcp.send_buffer_size = config.inbound_send_buffer_size;
@@ -67,12 +70,19 @@ pub async fn run(config: Config) -> io::Result<()> {
     accept_opts.tcp.mptcp = config.mptcp;
     accept_opts.udp.mtu = config.udp_mtu;
 
-    if let Some(resolver) =
-        build_dns_resolver(config.dns, config.ipv6_first, config.dns_cache_size, &connect_opts).await
+    if let Some(resolver) = build_dns_resolver(
+        config.dns,
+        config.dns_in_order,
+        config.ipv6_first,
+        config.dns_cache_size,
+        &connect_opts,
+    )
+    .await
     {
         manager_builder.set_dns_resolver(Arc::new(resolver));
     }
     manager_builder.set_ipv6_first(config.ipv6_first);
+    manager_builder.set_disable_ipv6(config.disable_ipv6);
 
     manager_builder.set_connect_opts(connect_opts);
     manager_builder.set_accept_opts(accept_opts);
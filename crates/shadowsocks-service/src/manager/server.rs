@@ -6,7 +6,7 @@ use std::{collections::HashMap, io, net::SocketAddr, sync::Arc, time::Duration};
 
 use log::{error, info, trace};
 use shadowsocks::{
-    config::{Mode, ServerConfig, ServerType, ServerUser, ServerUserManager},
+    config::{parse_method, Mode, ServerConfig, ServerType, ServerUser, ServerUserManager},
     context::{Context, SharedContext},
     crypto::CipherKind,
     dns_resolver::DnsResolver,
@@ -74,6 +74,7 @@ pub struct ManagerBuilder {
     udp_capacity: Option<usize>,
     acl: Option<Arc<AccessControl>>,
     ipv6_first: bool,
+    disable_ipv6: bool,
     security: SecurityConfig,
 }
 
@@ -94,6 +95,7 @@ impl ManagerBuilder {
             udp_capacity: None,
             acl: None,
             ipv6_first: false,
+            disable_ipv6: false,
             security: SecurityConfig::default(),
         }
     }
@@ -139,6 +141,11 @@ impl ManagerBuilder {
         self.ipv6_first = ipv6_first;
     }
 
+    /// Never use IPv6 addresses resolved from hostnames
+    pub fn set_disable_ipv6(&mut self, disable_ipv6: bool) {
+        self.disable_ipv6 = disable_ipv6;
+    }
+
     /// Set security config
     pub fn set_security_config(&mut self, security: SecurityConfig) {
         self.security = security;
@@ -157,6 +164,7 @@ impl ManagerBuilder {
             udp_capacity: self.udp_capacity,
             acl: self.acl,
             ipv6_first: self.ipv6_first,
+            disable_ipv6: self.disable_ipv6,
             security: self.security,
             listener,
         })
@@ -174,6 +182,7 @@ pub struct Manager {
     udp_capacity: Option<usize>,
     acl: Option<Arc<AccessControl>>,
     ipv6_first: bool,
+    disable_ipv6: bool,
     security: SecurityConfig,
     listener: ManagerListener,
 }
@@ -269,6 +278,10 @@ impl Manager {
             server_builder.set_ipv6_first(self.ipv6_first);
         }
 
+        if self.disable_ipv6 {
+            server_builder.set_disable_ipv6(self.disable_ipv6);
+        }
+
         server_builder.set_security_config(&self.security);
 
         let server_port = server_builder.server_config().addr().port();
@@ -389,6 +402,7 @@ impl Manager {
             outbound_bind_addr: None,
             outbound_bind_interface: None,
             outbound_udp_allow_fragmentation: None,
+            outbound_proxy: None,
         };
 
         let mut config = Config::new(ConfigType::Server);
@@ -465,7 +479,7 @@ impl Manager {
         };
 
         let method = match req.method {
-            Some(ref m) => match m.parse::<CipherKind>() {
+            Some(ref m) => match parse_method(m) {
                 Ok(method) => method,
                 Err(..) => {
                     error!("unrecognized method \"{}\", req: {:?}", m, req);
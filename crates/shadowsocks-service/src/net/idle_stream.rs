@@ -0,0 +1,106 @@
+//! Stream with idle timeout
+
+use std::{
+    future::Future,
+    io::{self, ErrorKind},
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use pin_project::pin_project;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{self, Instant, Sleep},
+};
+
+/// Stream that fails with `TimedOut` if there is no successful read or write in `timeout`
+#[pin_project]
+pub struct IdleTimeoutStream<S> {
+    #[pin]
+    stream: S,
+    timeout: Option<Duration>,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> IdleTimeoutStream<S> {
+    /// Create a new stream, `None` means never timeout
+    pub fn new(stream: S, timeout: Option<Duration>) -> IdleTimeoutStream<S> {
+        IdleTimeoutStream {
+            stream,
+            timeout,
+            sleep: timeout.map(|d| Box::pin(time::sleep(d))),
+        }
+    }
+
+    #[inline]
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+fn poll_idle<R>(
+    timeout: Option<Duration>,
+    sleep: &mut Option<Pin<Box<Sleep>>>,
+    cx: &mut Context<'_>,
+    poll: Poll<io::Result<R>>,
+) -> Poll<io::Result<R>> {
+    match sleep {
+        None => poll,
+        Some(sleep) => match poll {
+            Poll::Ready(Ok(r)) => {
+                if let Some(d) = timeout {
+                    sleep.as_mut().reset(Instant::now() + d);
+                }
+                Poll::Ready(Ok(r))
+            }
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => match sleep.as_mut().poll(cx) {
+                Poll::Ready(..) => Poll::Ready(Err(io::Error::new(ErrorKind::TimedOut, "idle timeout"))),
+                Poll::Pending => Poll::Pending,
+            },
+        },
+    }
+}
+
+impl<S> AsyncRead for IdleTimeoutStream<S>
+where
+    S: AsyncRead,
+{
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.project();
+        let poll = this.stream.poll_read(cx, buf);
+        poll_idle(*this.timeout, this.sleep, cx, poll)
+    }
+}
+
+impl<S> AsyncWrite for IdleTimeoutStream<S>
+where
+    S: AsyncWrite,
+{
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let poll = this.stream.poll_write(cx, buf);
+        poll_idle(*this.timeout, this.sleep, cx, poll)
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().stream.poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().stream.poll_shutdown(cx)
+    }
+}
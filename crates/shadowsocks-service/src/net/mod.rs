@@ -1,13 +1,23 @@
 //! Shadowsocks Service Network Utilities
 
-pub use self::{flow::FlowStat, mon_socket::MonProxySocket, mon_stream::MonProxyStream};
+pub use self::{
+    flow::FlowStat,
+    idle_stream::IdleTimeoutStream,
+    mon_socket::MonProxySocket,
+    mon_stream::MonProxyStream,
+    rate_limit::{RateLimitedStream, RateLimiter},
+    tracker::ConnectionTracker,
+};
 
 pub mod flow;
+pub mod idle_stream;
 #[cfg(target_os = "macos")]
 pub mod launch_activate_socket;
 pub mod mon_socket;
 pub mod mon_stream;
 pub mod packet_window;
+pub mod rate_limit;
+pub mod tracker;
 pub mod utils;
 
 /// Packet size for all UDP associations' send queue
@@ -0,0 +1,248 @@
+//! Stream with throughput limited by a shared token bucket
+
+use std::{
+    future::Future,
+    io::{self, IoSlice},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use pin_project::pin_project;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{self, Instant, Sleep},
+};
+
+/// Token bucket shared by all streams limited together
+///
+/// Tokens are bytes, the bucket holds at most 1 second of traffic. Transfers are allowed while there are tokens
+/// left, and the bucket may go into debt by one buffer of each stream.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: u64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Create a limiter allowing `rate` bytes per second in total, reads and writes combined
+    ///
+    /// Panics if `rate` is `0`, use no limiter for unlimited streams.
+    pub fn new(rate: u64) -> RateLimiter {
+        assert!(rate > 0, "rate must be positive");
+
+        RateLimiter {
+            rate,
+            bucket: Mutex::new(Bucket {
+                tokens: rate as f64,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Allowed bytes per second
+    pub fn rate(&self) -> u64 {
+        self.rate
+    }
+
+    /// Duration to wait before transferring more data, `None` if it could be transferred now
+    fn wait_duration(&self) -> Option<Duration> {
+        let mut bucket = self.bucket.lock().unwrap();
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last_refill);
+        bucket.last_refill = now;
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * self.rate as f64).min(self.rate as f64);
+
+        if bucket.tokens > 0.0 {
+            None
+        } else {
+            // At least 1ms, or the timer may fire before any token is refilled
+            let secs = (1.0 - bucket.tokens) / self.rate as f64;
+            Some(Duration::from_secs_f64(secs).max(Duration::from_millis(1)))
+        }
+    }
+
+    fn consume(&self, n: usize) {
+        if n > 0 {
+            let mut bucket = self.bucket.lock().unwrap();
+            bucket.tokens -= n as f64;
+        }
+    }
+}
+
+/// Stream that reads and writes are limited by a `RateLimiter`
+#[pin_project]
+pub struct RateLimitedStream<S> {
+    #[pin]
+    stream: S,
+    limiter: Option<Arc<RateLimiter>>,
+    read_sleep: Option<Pin<Box<Sleep>>>,
+    write_sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> RateLimitedStream<S> {
+    /// Create a new stream, `None` means unlimited
+    pub fn new(stream: S, limiter: Option<Arc<RateLimiter>>) -> RateLimitedStream<S> {
+        RateLimitedStream {
+            stream,
+            limiter,
+            read_sleep: None,
+            write_sleep: None,
+        }
+    }
+
+    #[inline]
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+fn poll_limit(
+    limiter: &Option<Arc<RateLimiter>>,
+    sleep: &mut Option<Pin<Box<Sleep>>>,
+    cx: &mut Context<'_>,
+) -> Poll<()> {
+    let limiter = match limiter {
+        Some(l) => l,
+        None => return Poll::Ready(()),
+    };
+
+    loop {
+        let deadline = match limiter.wait_duration() {
+            None => return Poll::Ready(()),
+            Some(d) => Instant::now() + d,
+        };
+
+        let sleep = match sleep {
+            Some(s) => {
+                s.as_mut().reset(deadline);
+                s
+            }
+            None => sleep.insert(Box::pin(time::sleep_until(deadline))),
+        };
+        ready!(sleep.as_mut().poll(cx));
+    }
+}
+
+impl<S> AsyncRead for RateLimitedStream<S>
+where
+    S: AsyncRead,
+{
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.project();
+        ready!(poll_limit(this.limiter, this.read_sleep, cx));
+
+        let filled = buf.filled().len();
+        ready!(this.stream.poll_read(cx, buf))?;
+        if let Some(ref limiter) = this.limiter {
+            limiter.consume(buf.filled().len() - filled);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S> AsyncWrite for RateLimitedStream<S>
+where
+    S: AsyncWrite,
+{
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        ready!(poll_limit(this.limiter, this.write_sleep, cx));
+
+        let n = ready!(this.stream.poll_write(cx, buf))?;
+        if let Some(ref limiter) = this.limiter {
+            limiter.consume(n);
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().stream.poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().stream.poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        ready!(poll_limit(this.limiter, this.write_sleep, cx));
+
+        let n = ready!(this.stream.poll_write_vectored(cx, bufs))?;
+        if let Some(ref limiter) = this.limiter {
+            limiter.consume(n);
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    #[inline]
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn rate_limited_stream_shares_bucket() {
+        let limiter = Arc::new(RateLimiter::new(64 * 1024));
+
+        let (a, mut a_peer) = tokio::io::duplex(1024 * 1024);
+        let (b, mut b_peer) = tokio::io::duplex(1024 * 1024);
+        let mut a = RateLimitedStream::new(a, Some(limiter.clone()));
+        let mut b = RateLimitedStream::new(b, Some(limiter));
+
+        let start = std::time::Instant::now();
+
+        // 64K burst is sent immediately, the 3rd buffer goes into debt, the 4th waits for the debt to be repaid
+        let data = vec![0u8; 32 * 1024];
+        for _ in 0..2 {
+            a.write_all(&data).await.unwrap();
+            b.write_all(&data).await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(450));
+
+        let mut buf = vec![0u8; 64 * 1024];
+        a_peer.read_exact(&mut buf).await.unwrap();
+        b_peer.read_exact(&mut buf).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rate_limited_stream_unlimited() {
+        let (a, mut a_peer) = tokio::io::duplex(1024);
+        let mut a = RateLimitedStream::new(a, None);
+
+        a.write_all(b"hello").await.unwrap();
+        let mut buf = [0u8; 5];
+        a_peer.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+    }
+}
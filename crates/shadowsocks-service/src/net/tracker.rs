@@ -0,0 +1,113 @@
+//! Active connection tracker
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use tokio::sync::Notify;
+
+struct TrackerInner {
+    active: AtomicUsize,
+    notify: Notify,
+}
+
+/// Counts active connections, for waiting them to finish before shutting down
+///
+/// Cloned trackers share the same counter.
+#[derive(Clone)]
+pub struct ConnectionTracker {
+    inner: Arc<TrackerInner>,
+}
+
+impl Default for ConnectionTracker {
+    fn default() -> Self {
+        ConnectionTracker {
+            inner: Arc::new(TrackerInner {
+                active: AtomicUsize::new(0),
+                notify: Notify::new(),
+            }),
+        }
+    }
+}
+
+impl ConnectionTracker {
+    /// Create a tracker without active connections
+    pub fn new() -> ConnectionTracker {
+        ConnectionTracker::default()
+    }
+
+    /// Start tracking a connection, until the returned guard is dropped
+    pub fn track(&self) -> ConnectionGuard {
+        self.inner.active.fetch_add(1, Ordering::AcqRel);
+        ConnectionGuard {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Number of active connections
+    pub fn active(&self) -> usize {
+        self.inner.active.load(Ordering::Acquire)
+    }
+
+    /// Wait until there is no active connection
+    pub async fn wait_idle(&self) {
+        loop {
+            let notified = self.inner.notify.notified();
+            tokio::pin!(notified);
+            // Register before checking, so wakeups between the check and `.await` won't be missed
+            notified.as_mut().enable();
+
+            if self.active() == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Guard of an active connection, returned by [`ConnectionTracker::track`]
+pub struct ConnectionGuard {
+    inner: Arc<TrackerInner>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if self.inner.active.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.inner.notify.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_connection_tracker_wait_idle() {
+        let tracker = ConnectionTracker::new();
+        tracker.wait_idle().await;
+
+        let guard1 = tracker.track();
+        let guard2 = tracker.clone().track();
+        assert_eq!(tracker.active(), 2);
+
+        let waiter = tokio::spawn({
+            let tracker = tracker.clone();
+            async move { tracker.wait_idle().await }
+        });
+
+        drop(guard1);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!waiter.is_finished());
+
+        drop(guard2);
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("wait_idle returns after all guards dropped")
+            .unwrap();
+        assert_eq!(tracker.active(), 0);
+    }
+}
@@ -1,16 +1,27 @@
 //! Shadowsocks Local Server Context
 
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    io::{self, ErrorKind},
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
 
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use iprange::IpRange;
+use regex::Regex;
 use shadowsocks::{
     config::ServerType,
     context::{Context, SharedContext},
     dns_resolver::DnsResolver,
     net::ConnectOpts,
-    relay::Address,
+    relay::{udprelay::MAXIMUM_UDP_PAYLOAD_SIZE, Address},
 };
 
-use crate::{acl::AccessControl, config::SecurityConfig, net::FlowStat};
+use crate::{
+    acl::AccessControl,
+    config::{matches_forbidden_domain_regex, OutboundProxyConfig, SecurityConfig},
+    net::{ConnectionTracker, FlowStat, RateLimiter},
+};
 
 /// Server Service Context
 #[derive(Clone)]
@@ -23,6 +34,75 @@ pub struct ServiceContext {
 
     // Flow statistic report
     flow_stat: Arc<FlowStat>,
+
+    // Active TCP connections, for draining on shutdown
+    connection_tracker: ConnectionTracker,
+
+    // Outbound SOCKS5 proxy
+    outbound_proxy: Option<OutboundProxyConfig>,
+
+    // Forbidden outbound IPs
+    forbidden_ip: Option<Arc<ForbiddenIp>>,
+
+    // Forbidden outbound domain names
+    forbidden_domain_regex: Option<Arc<[Regex]>>,
+
+    // Allowed UDP destination ports
+    udp_allowed_ports: Option<Arc<[u16]>>,
+
+    // Buffer size for receiving UDP packets from targets
+    udp_max_payload: usize,
+
+    // Aggregate throughput limit of TCP relays
+    rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+/// Set of IP networks that are not allowed to be connected to
+#[derive(Debug, Clone)]
+pub struct ForbiddenIp {
+    ipv4: IpRange<Ipv4Net>,
+    ipv6: IpRange<Ipv6Net>,
+}
+
+impl ForbiddenIp {
+    /// Create from a list of networks
+    pub fn new(nets: &[IpNet]) -> ForbiddenIp {
+        let mut ipv4 = IpRange::new();
+        let mut ipv6 = IpRange::new();
+
+        for net in nets {
+            match *net {
+                IpNet::V4(n) => {
+                    ipv4.add(n);
+                }
+                IpNet::V6(n) => {
+                    ipv6.add(n);
+                }
+            }
+        }
+
+        ipv4.simplify();
+        ipv6.simplify();
+
+        ForbiddenIp { ipv4, ipv6 }
+    }
+
+    /// Check if `addr` is forbidden
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        match addr {
+            IpAddr::V4(v4) => self.ipv4.contains(v4) || self.ipv6.contains(&v4.to_ipv6_mapped()),
+            IpAddr::V6(v6) => {
+                if self.ipv6.contains(v6) {
+                    return true;
+                }
+
+                match v6.to_ipv4_mapped() {
+                    Some(v4) => self.ipv4.contains(&v4),
+                    None => false,
+                }
+            }
+        }
+    }
 }
 
 impl Default for ServiceContext {
@@ -32,6 +112,13 @@ impl Default for ServiceContext {
             connect_opts: ConnectOpts::default(),
             acl: None,
             flow_stat: Arc::new(FlowStat::new()),
+            connection_tracker: ConnectionTracker::new(),
+            outbound_proxy: None,
+            forbidden_ip: None,
+            forbidden_domain_regex: None,
+            udp_allowed_ports: None,
+            udp_max_payload: MAXIMUM_UDP_PAYLOAD_SIZE,
+            rate_limiter: None,
         }
     }
 }
@@ -62,6 +149,61 @@ impl ServiceContext {
         &self.connect_opts
     }
 
+    /// Set outbound SOCKS5 proxy for TCP connections
+    pub fn set_outbound_proxy(&mut self, proxy: OutboundProxyConfig) {
+        self.outbound_proxy = Some(proxy);
+    }
+
+    /// Get outbound SOCKS5 proxy reference
+    pub fn outbound_proxy(&self) -> Option<&OutboundProxyConfig> {
+        self.outbound_proxy.as_ref()
+    }
+
+    /// Set forbidden outbound IPs
+    pub fn set_forbidden_ip(&mut self, forbidden_ip: Arc<ForbiddenIp>) {
+        self.forbidden_ip = Some(forbidden_ip);
+    }
+
+    /// Set patterns of forbidden outbound domain names
+    pub fn set_forbidden_domain_regex(&mut self, regexes: Arc<[Regex]>) {
+        self.forbidden_domain_regex = Some(regexes);
+    }
+
+    /// Set allowed destination ports of UDP relay, should be sorted
+    pub fn set_udp_allowed_ports(&mut self, ports: Arc<[u16]>) {
+        self.udp_allowed_ports = Some(ports);
+    }
+
+    /// Check if UDP packets are allowed to be forwarded to `addr`
+    pub fn check_udp_port_allowed(&self, addr: &Address) -> bool {
+        match self.udp_allowed_ports {
+            Some(ref ports) => ports.binary_search(&addr.port()).is_ok(),
+            None => true,
+        }
+    }
+
+    /// Set buffer size for receiving UDP packets from targets, larger packets are truncated
+    ///
+    /// Defaults to `MAXIMUM_UDP_PAYLOAD_SIZE`, so this could only shrink buffers
+    pub fn set_udp_max_payload(&mut self, size: usize) {
+        self.udp_max_payload = size;
+    }
+
+    /// Buffer size for receiving UDP packets from targets
+    pub fn udp_max_payload(&self) -> usize {
+        self.udp_max_payload
+    }
+
+    /// Set the throughput limiter shared by TCP relays
+    pub fn set_rate_limiter(&mut self, limiter: Arc<RateLimiter>) {
+        self.rate_limiter = Some(limiter);
+    }
+
+    /// Get the throughput limiter shared by TCP relays
+    pub fn rate_limiter(&self) -> Option<Arc<RateLimiter>> {
+        self.rate_limiter.clone()
+    }
+
     /// Set Access Control List
     pub fn set_acl(&mut self, acl: Arc<AccessControl>) {
         self.acl = Some(acl);
@@ -82,6 +224,16 @@ impl ServiceContext {
         self.flow_stat.as_ref()
     }
 
+    /// Set tracker of active TCP connections
+    pub fn set_connection_tracker(&mut self, tracker: ConnectionTracker) {
+        self.connection_tracker = tracker;
+    }
+
+    /// Get tracker of active TCP connections
+    pub fn connection_tracker(&self) -> &ConnectionTracker {
+        &self.connection_tracker
+    }
+
     /// Set customized DNS resolver
     pub fn set_dns_resolver(&mut self, resolver: Arc<DnsResolver>) {
         let context = Arc::get_mut(&mut self.context).expect("cannot set DNS resolver on a shared context");
@@ -94,11 +246,60 @@ impl ServiceContext {
     }
 
     /// Check if target should be bypassed
+    ///
+    /// Domain names are checked against `forbidden_ip` by [`ServiceContext::resolve_outbound`]
     pub async fn check_outbound_blocked(&self, addr: &Address) -> bool {
-        match self.acl {
-            None => false,
-            Some(ref acl) => acl.check_outbound_blocked(&self.context, addr).await,
+        if let Some(ref acl) = self.acl {
+            if acl.check_outbound_blocked(&self.context, addr).await {
+                return true;
+            }
+        }
+
+        if let (Some(regexes), Address::DomainNameAddress(host, ..)) = (&self.forbidden_domain_regex, addr) {
+            if matches_forbidden_domain_regex(regexes, host) {
+                return true;
+            }
+        }
+
+        if let (Some(forbidden_ip), Address::SocketAddress(saddr)) = (&self.forbidden_ip, addr) {
+            return forbidden_ip.contains(&saddr.ip());
         }
+
+        false
+    }
+
+    /// Resolve target for connecting if `forbidden_ip` is set
+    ///
+    /// Domain names are resolved once and all the checked addresses are returned, in the order they should be tried,
+    /// so the connection goes to the addresses that were checked instead of resolving again. `None` if `addr` should
+    /// be connected as is. Fails with `PermissionDenied` if any of the resolved addresses is forbidden.
+    pub async fn resolve_outbound(&self, addr: &Address) -> io::Result<Option<Vec<SocketAddr>>> {
+        let (forbidden_ip, host, port) = match (&self.forbidden_ip, addr) {
+            (Some(forbidden_ip), Address::DomainNameAddress(host, port)) => (forbidden_ip, host, *port),
+            _ => return Ok(None),
+        };
+
+        let mut resolved = Vec::new();
+        for saddr in self.context.dns_resolve(host, port).await? {
+            if forbidden_ip.contains(&saddr.ip()) {
+                let err = io::Error::new(
+                    ErrorKind::PermissionDenied,
+                    format!("{host} resolved to forbidden ip {}", saddr.ip()),
+                );
+                return Err(err);
+            }
+            resolved.push(saddr);
+        }
+
+        if resolved.is_empty() {
+            return Err(io::Error::other(format!("dns resolve {host}:{port} got no addresses")));
+        }
+
+        // Same order as `lookup_then!`, IPv4 first unless `ipv6_first` is set
+        let ipv6_first = self.context.ipv6_first();
+        resolved.sort_by_key(|saddr| saddr.is_ipv4() == ipv6_first);
+
+        Ok(Some(resolved))
     }
 
     /// Check if client should be blocked
@@ -115,9 +316,37 @@ impl ServiceContext {
         context.set_ipv6_first(ipv6_first);
     }
 
+    /// Never use IPv6 addresses resolved from hostnames
+    pub fn set_disable_ipv6(&mut self, disable_ipv6: bool) {
+        let context = Arc::get_mut(&mut self.context).expect("cannot set disable_ipv6 on a shared context");
+        context.set_disable_ipv6(disable_ipv6);
+    }
+
     /// Set security config
     pub fn set_security_config(&mut self, security: &SecurityConfig) {
         let context = Arc::get_mut(&mut self.context).expect("cannot set security on a shared context");
         context.set_replay_attack_policy(security.replay_attack.policy);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn resolve_outbound_forbidden_ip() {
+        let mut context = ServiceContext::new();
+        let target = Address::DomainNameAddress("127.0.0.1".to_owned(), 80);
+        assert!(context.resolve_outbound(&target).await.unwrap().is_none());
+
+        context.set_forbidden_ip(Arc::new(ForbiddenIp::new(&["10.0.0.0/8".parse().unwrap()])));
+        assert_eq!(
+            context.resolve_outbound(&target).await.unwrap(),
+            Some(vec!["127.0.0.1:80".parse().unwrap()])
+        );
+
+        context.set_forbidden_ip(Arc::new(ForbiddenIp::new(&["127.0.0.0/8".parse().unwrap()])));
+        let err = context.resolve_outbound(&target).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    }
+}
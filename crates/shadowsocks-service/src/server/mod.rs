@@ -9,9 +9,12 @@ use shadowsocks::net::{AcceptOpts, ConnectOpts, UdpSocketOpts};
 use crate::{
     config::{Config, ConfigType},
     dns::build_dns_resolver,
+    net::{ConnectionTracker, RateLimiter},
     utils::ServerHandle,
 };
 
+use self::context::ForbiddenIp;
+
 pub use self::{
     server::{Server, ServerBuilder},
     tcprelay::TcpServer,
@@ -31,6 +34,14 @@ pub(crate) const SERVER_DEFAULT_KEEPALIVE_TIMEOUT: Duration = Duration::from_sec
 
 /// Starts a shadowsocks server
 pub async fn run(config: Config) -> io::Result<()> {
+    run_with_connection_tracker(config, ConnectionTracker::new()).await
+}
+
+/// Starts a shadowsocks server, active TCP connections of all servers are counted in `tracker`
+///
+/// Connections are served in their own tasks, they are not stopped when this future is dropped. Wait for them with
+/// [`ConnectionTracker::wait_idle`] for shutting down gracefully.
+pub async fn run_with_connection_tracker(config: Config, tracker: ConnectionTracker) -> io::Result<()> {
     assert_eq!(config.config_type, ConfigType::Server);
     assert!(!config.server.is_empty());
 
@@ -66,6 +77,7 @@ pub async fn run(config: Config) -> io::Result<()> {
 
         bind_local_addr: config.outbound_bind_addr.map(|ip| SocketAddr::new(ip, 0)),
         bind_interface: config.outbound_bind_interface,
+        ttl: config.outbound_ttl,
 
         udp: UdpSocketOpts {
             allow_fragmentation: config.outbound_udp_allow_fragmentation,
@@ -86,6 +98,8 @@ pub async fn run(config: Config) -> io::Result<()> {
 
     let mut accept_opts = AcceptOpts {
         ipv6_only: config.ipv6_only,
+        reuse_port: config.reuse_port,
+        reuse_address: config.reuse_address,
         ..Default::default()
     };
     accept_opts.tcp.send_buffer_size = config.inbound_send_buffer_size;
@@ -96,13 +110,50 @@ pub async fn run(config: Config) -> io::Result<()> {
     accept_opts.tcp.mptcp = config.mptcp;
     accept_opts.udp.mtu = config.udp_mtu;
 
-    let resolver = build_dns_resolver(config.dns, config.ipv6_first, config.dns_cache_size, &connect_opts)
-        .await
-        .map(Arc::new);
+    let resolver = build_dns_resolver(
+        config.dns,
+        config.dns_in_order,
+        config.ipv6_first,
+        config.dns_cache_size,
+        &connect_opts,
+    )
+    .await
+    .map(Arc::new);
 
     let acl = config.acl.map(Arc::new);
 
+    let forbidden_ip = if config.forbidden_ip.is_empty() {
+        None
+    } else {
+        Some(Arc::new(ForbiddenIp::new(&config.forbidden_ip)))
+    };
+
+    let forbidden_domain_regex = if config.forbidden_domain_regex.is_empty() {
+        None
+    } else {
+        Some(Arc::<[_]>::from(config.forbidden_domain_regex))
+    };
+
+    let udp_allowed_ports = config.udp_allowed_ports.map(Arc::<[u16]>::from);
+
+    // Shared by all servers, the limit is aggregated
+    let rate_limiter = config
+        .max_rate_bytes_per_sec
+        .filter(|&rate| rate > 0)
+        .map(|rate| Arc::new(RateLimiter::new(rate)));
+
     for inst in config.server {
+        let outbound_proxy = inst.effective_outbound_proxy(config.outbound_proxy.as_ref()).cloned();
+        if let Some(ref proxy) = outbound_proxy {
+            if inst.config.mode().enable_udp() {
+                log::warn!(
+                    "outbound_socks5 {} of server {} only applies to TCP relay, UDP relay will connect to targets directly",
+                    proxy.addr,
+                    inst.config.addr()
+                );
+            }
+        }
+
         let svr_cfg = inst.config;
         let mut server_builder = ServerBuilder::new(svr_cfg);
 
@@ -132,6 +183,7 @@ pub async fn run(config: Config) -> io::Result<()> {
 
         server_builder.set_connect_opts(connect_opts);
         server_builder.set_accept_opts(accept_opts);
+        server_builder.set_connection_tracker(tracker.clone());
 
         if let Some(c) = config.udp_max_associations {
             server_builder.set_udp_capacity(c);
@@ -139,6 +191,9 @@ pub async fn run(config: Config) -> io::Result<()> {
         if let Some(d) = config.udp_timeout {
             server_builder.set_udp_expiry_duration(d);
         }
+        if let Some(size) = config.udp_max_payload {
+            server_builder.set_udp_max_payload(size);
+        }
         if let Some(ref m) = config.manager {
             server_builder.set_manager_addr(m.addr.clone());
         }
@@ -152,12 +207,36 @@ pub async fn run(config: Config) -> io::Result<()> {
             }
         }
 
+        if let Some(ref forbidden_ip) = forbidden_ip {
+            server_builder.set_forbidden_ip(forbidden_ip.clone());
+        }
+
+        if let Some(ref regexes) = forbidden_domain_regex {
+            server_builder.set_forbidden_domain_regex(regexes.clone());
+        }
+
+        if let Some(ref ports) = udp_allowed_ports {
+            server_builder.set_udp_allowed_ports(ports.clone());
+        }
+
+        if let Some(ref limiter) = rate_limiter {
+            server_builder.set_rate_limiter(limiter.clone());
+        }
+
         if config.ipv6_first {
             server_builder.set_ipv6_first(config.ipv6_first);
         }
 
+        if config.disable_ipv6 {
+            server_builder.set_disable_ipv6(config.disable_ipv6);
+        }
+
         server_builder.set_security_config(&config.security);
 
+        if let Some(proxy) = outbound_proxy {
+            server_builder.set_outbound_proxy(proxy);
+        }
+
         let server = server_builder.build().await?;
         servers.push(server);
     }
@@ -9,6 +9,7 @@ use std::{
 
 use futures::future;
 use log::{error, trace};
+use regex::Regex;
 use shadowsocks::{
     config::{ManagerAddr, ServerConfig},
     dns_resolver::DnsResolver,
@@ -18,9 +19,18 @@ use shadowsocks::{
 };
 use tokio::time;
 
-use crate::{acl::AccessControl, config::SecurityConfig, net::FlowStat, utils::ServerHandle};
+use crate::{
+    acl::AccessControl,
+    config::{OutboundProxyConfig, SecurityConfig},
+    net::{ConnectionTracker, FlowStat, RateLimiter},
+    utils::ServerHandle,
+};
 
-use super::{context::ServiceContext, tcprelay::TcpServer, udprelay::UdpServer};
+use super::{
+    context::{ForbiddenIp, ServiceContext},
+    tcprelay::TcpServer,
+    udprelay::UdpServer,
+};
 
 /// Shadowsocks Server Builder
 pub struct ServerBuilder {
@@ -95,6 +105,41 @@ impl ServerBuilder {
         self.context.set_acl(acl);
     }
 
+    /// Set forbidden outbound IPs
+    pub fn set_forbidden_ip(&mut self, forbidden_ip: Arc<ForbiddenIp>) {
+        self.context.set_forbidden_ip(forbidden_ip);
+    }
+
+    /// Set patterns of forbidden outbound domain names
+    pub fn set_forbidden_domain_regex(&mut self, regexes: Arc<[Regex]>) {
+        self.context.set_forbidden_domain_regex(regexes);
+    }
+
+    /// Set allowed destination ports of UDP relay, should be sorted
+    pub fn set_udp_allowed_ports(&mut self, ports: Arc<[u16]>) {
+        self.context.set_udp_allowed_ports(ports);
+    }
+
+    /// Set the throughput limiter shared by TCP relays
+    pub fn set_rate_limiter(&mut self, limiter: Arc<RateLimiter>) {
+        self.context.set_rate_limiter(limiter);
+    }
+
+    /// Set buffer size for receiving UDP packets from targets
+    pub fn set_udp_max_payload(&mut self, size: usize) {
+        self.context.set_udp_max_payload(size);
+    }
+
+    /// Set tracker of active TCP connections, could be shared between servers
+    pub fn set_connection_tracker(&mut self, tracker: ConnectionTracker) {
+        self.context.set_connection_tracker(tracker);
+    }
+
+    /// Set outbound SOCKS5 proxy, outbound TCP connections will be chained through it
+    pub fn set_outbound_proxy(&mut self, proxy: OutboundProxyConfig) {
+        self.context.set_outbound_proxy(proxy);
+    }
+
     /// Set `AcceptOpts` for accepting new connections
     pub fn set_accept_opts(&mut self, opts: AcceptOpts) {
         self.accept_opts = opts;
@@ -105,6 +150,11 @@ impl ServerBuilder {
         self.context.set_ipv6_first(ipv6_first);
     }
 
+    /// Never use IPv6 addresses resolved from hostnames
+    pub fn set_disable_ipv6(&mut self, disable_ipv6: bool) {
+        self.context.set_disable_ipv6(disable_ipv6);
+    }
+
     /// Set security config
     pub fn set_security_config(&mut self, security: &SecurityConfig) {
         self.context.set_security_config(security)
@@ -3,6 +3,7 @@
 use std::{
     future::Future,
     io::{self, ErrorKind},
+    mem::MaybeUninit,
     net::SocketAddr,
     sync::Arc,
     time::Duration,
@@ -12,16 +13,31 @@ use log::{debug, error, info, trace, warn};
 use shadowsocks::{
     crypto::CipherKind,
     net::{AcceptOpts, TcpStream as OutboundTcpStream},
-    relay::tcprelay::{utils::copy_encrypted_bidirectional, ProxyServerStream},
+    relay::{
+        socks5::{
+            self, Command, HandshakeRequest, HandshakeResponse, PasswdAuthRequest, PasswdAuthResponse, Reply,
+            TcpRequestHeader, TcpResponseHeader,
+        },
+        tcprelay::{
+            utils::{copy_encrypted_bidirectional, probe_request_method, request_probe_len},
+            ProxyServerStream,
+        },
+        Address,
+    },
     ProxyListener, ServerConfig,
 };
+use socket2::SockRef;
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
+    io::{AsyncReadExt, AsyncWriteExt, Interest},
     net::TcpStream as TokioTcpStream,
+    sync::Semaphore,
     time,
 };
 
-use crate::net::{utils::ignore_until_end, MonProxyStream};
+use crate::{
+    config::OutboundProxyConfig,
+    net::{utils::ignore_until_end, IdleTimeoutStream, MonProxyStream, RateLimitedStream},
+};
 
 use super::context::ServiceContext;
 
@@ -64,6 +80,21 @@ impl TcpServer {
             self.svr_cfg.addr()
         );
 
+        // New connections are rejected while `max_connections` connections are being served
+        let connection_limit = self
+            .svr_cfg
+            .max_connections()
+            .map(|n| Arc::new(Semaphore::new(n.min(Semaphore::MAX_PERMITS))));
+
+        let fallback = match (self.svr_cfg.fallback_method(), self.svr_cfg.fallback_key()) {
+            (Some(method), Some(key)) => Some(Arc::new(FallbackMethod {
+                primary_key: self.svr_cfg.key().into(),
+                method,
+                key: key.into(),
+            })),
+            _ => None,
+        };
+
         loop {
             let flow_stat = self.context.flow_stat();
 
@@ -85,18 +116,38 @@ impl TcpServer {
                 continue;
             }
 
+            let permit = match connection_limit {
+                None => None,
+                Some(ref limit) => match limit.clone().try_acquire_owned() {
+                    Ok(permit) => Some(permit),
+                    Err(..) => {
+                        warn!(
+                            "tcp server {} reached max_connections, rejected client {}",
+                            self.svr_cfg.addr(),
+                            peer_addr
+                        );
+                        continue;
+                    }
+                },
+            };
+
             let client = TcpServerClient {
                 context: self.context.clone(),
                 method: self.svr_cfg.method(),
+                fallback: fallback.clone(),
                 peer_addr,
                 stream: local_stream,
-                timeout: self.svr_cfg.timeout(),
+                connect_timeout: self.svr_cfg.connect_timeout(),
+                idle_timeout: self.svr_cfg.idle_timeout(),
             };
 
+            let guard = self.context.connection_tracker().track();
             tokio::spawn(async move {
                 if let Err(err) = client.serve().await {
                     debug!("tcp server stream aborted with error: {}", err);
                 }
+                drop(permit);
+                drop(guard);
             });
         }
     }
@@ -116,18 +167,44 @@ where
     }
 }
 
+/// Deadline of receiving the leading bytes of a request for probing its method
+const FALLBACK_PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Fallback method of a server, with the key of the primary method for probing clients' requests
+struct FallbackMethod {
+    primary_key: Box<[u8]>,
+    method: CipherKind,
+    key: Box<[u8]>,
+}
+
 struct TcpServerClient {
     context: Arc<ServiceContext>,
     method: CipherKind,
+    fallback: Option<Arc<FallbackMethod>>,
     peer_addr: SocketAddr,
     stream: ProxyServerStream<MonProxyStream<TokioTcpStream>>,
-    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
 }
 
 impl TcpServerClient {
     async fn serve(mut self) -> io::Result<()> {
+        if let Some(fallback) = self.fallback.take() {
+            let peer_addr = self.peer_addr;
+            self = match timeout_fut(Some(FALLBACK_PROBE_TIMEOUT), self.select_method(&fallback)).await {
+                Ok(c) => c,
+                Err(err) => {
+                    debug!(
+                        "tcp handshake failed, couldn't probe method of request, peer: {}, {}",
+                        peer_addr, err
+                    );
+                    return Ok(());
+                }
+            };
+        }
+
         // let target_addr = match Address::read_from(&mut self.stream).await {
-        let target_addr = match timeout_fut(self.timeout, self.stream.handshake()).await {
+        let target_addr = match timeout_fut(self.idle_timeout, self.stream.handshake()).await {
             Ok(a) => a,
             // Err(Socks5Error::IoError(ref err)) if err.kind() == ErrorKind::UnexpectedEof => {
             //     debug!(
@@ -201,16 +278,19 @@ impl TcpServerClient {
             return Ok(());
         }
 
-        let mut remote_stream = match timeout_fut(
-            self.timeout,
-            OutboundTcpStream::connect_remote_with_opts(
-                self.context.context_ref(),
-                &target_addr,
-                self.context.connect_opts_ref(),
-            ),
-        )
-        .await
-        {
+        let resolved = match self.context.resolve_outbound(&target_addr).await {
+            Ok(resolved) => resolved,
+            Err(err) => {
+                error!(
+                    "tcp client {} outbound {} blocked, error: {}",
+                    self.peer_addr, target_addr, err
+                );
+                return Ok(());
+            }
+        };
+
+        let connect_fut = self.connect_remote_resolved(&target_addr, resolved);
+        let mut remote_stream = match timeout_fut(self.connect_timeout, connect_fut).await {
             Ok(s) => s,
             Err(err) => {
                 error!(
@@ -235,12 +315,12 @@ impl TcpServerClient {
                 }
                 Ok(Ok(n)) => {
                     // Send the first packet.
-                    timeout_fut(self.timeout, remote_stream.write_all(&buffer[..n])).await?;
+                    timeout_fut(self.idle_timeout, remote_stream.write_all(&buffer[..n])).await?;
                 }
                 Ok(Err(err)) => return Err(err),
                 Err(..) => {
                     // Timeout. Send handshake to server.
-                    timeout_fut(self.timeout, remote_stream.write(&[])).await?;
+                    timeout_fut(self.idle_timeout, remote_stream.write(&[])).await?;
 
                     trace!(
                         "tcp tunnel {} -> {} sent TFO connect without data",
@@ -258,6 +338,10 @@ impl TcpServerClient {
             self.context.connect_opts_ref()
         );
 
+        // Both directions' traffic goes through `remote_stream`
+        let remote_stream = IdleTimeoutStream::new(remote_stream, self.idle_timeout);
+        let mut remote_stream = RateLimitedStream::new(remote_stream, self.context.rate_limiter());
+
         match copy_encrypted_bidirectional(self.method, &mut self.stream, &mut remote_stream).await {
             Ok((rn, wn)) => {
                 trace!(
@@ -280,4 +364,172 @@ impl TcpServerClient {
 
         Ok(())
     }
+
+    /// Switch to the fallback method if the request couldn't be decrypted with the primary method
+    ///
+    /// The leading bytes are peeked, so nothing is consumed from the stream.
+    async fn select_method(mut self, fallback: &FallbackMethod) -> io::Result<TcpServerClient> {
+        let probe_len = request_probe_len(self.method).max(request_probe_len(fallback.method));
+        let mut buffer = vec![0u8; probe_len];
+
+        let stream = self.stream.get_ref().get_ref();
+        let n = loop {
+            stream.readable().await?;
+
+            // Readiness is cleared if only part of the request has been received, so the next `readable()` waits
+            // for the rest instead of waking up immediately
+            let res = stream.try_io(Interest::READABLE, || {
+                // SAFETY: `buffer` is initialized, `peek` only writes initialized bytes into it
+                let buf = unsafe { &mut *(buffer.as_mut_slice() as *mut [u8] as *mut [MaybeUninit<u8>]) };
+                match SockRef::from(stream).peek(buf)? {
+                    n if n > 0 && n < probe_len => Err(ErrorKind::WouldBlock.into()),
+                    n => Ok(n),
+                }
+            });
+
+            match res {
+                Ok(n) => break n,
+                Err(ref err) if err.kind() == ErrorKind::WouldBlock => continue,
+                Err(err) => return Err(err),
+            }
+        };
+
+        if probe_request_method(&buffer[..n], self.method, &fallback.primary_key)
+            || !probe_request_method(&buffer[..n], fallback.method, &fallback.key)
+        {
+            return Ok(self);
+        }
+
+        debug!(
+            "tcp client {} request decrypted with fallback method {}",
+            self.peer_addr, fallback.method
+        );
+
+        let context = self.context.context();
+        let stream = self.stream.into_inner();
+        self.stream = ProxyServerStream::from_stream(context, stream, fallback.method, &fallback.key);
+        self.method = fallback.method;
+
+        Ok(self)
+    }
+
+    /// Connect to `resolved` addresses in order if `target_addr` was resolved by `resolve_outbound`
+    async fn connect_remote_resolved(
+        &self,
+        target_addr: &Address,
+        resolved: Option<Vec<SocketAddr>>,
+    ) -> io::Result<OutboundTcpStream> {
+        let resolved = match resolved {
+            None => return self.connect_remote(target_addr).await,
+            Some(resolved) => resolved,
+        };
+
+        let mut last_err = None;
+        for saddr in resolved {
+            match self.connect_remote(&Address::SocketAddress(saddr)).await {
+                Ok(s) => return Ok(s),
+                Err(err) => {
+                    trace!(
+                        "tcp tunnel {} -> {} ({}) connect failed, error: {}",
+                        self.peer_addr,
+                        target_addr,
+                        saddr,
+                        err
+                    );
+                    last_err = Some(err);
+                }
+            }
+        }
+
+        Err(last_err.expect("resolved empty address"))
+    }
+
+    async fn connect_remote(&self, target_addr: &Address) -> io::Result<OutboundTcpStream> {
+        match self.context.outbound_proxy() {
+            None => {
+                OutboundTcpStream::connect_remote_with_opts(
+                    self.context.context_ref(),
+                    target_addr,
+                    self.context.connect_opts_ref(),
+                )
+                .await
+            }
+            Some(proxy) => {
+                let mut stream = OutboundTcpStream::connect_server_with_opts(
+                    self.context.context_ref(),
+                    &proxy.addr,
+                    self.context.connect_opts_ref(),
+                )
+                .await?;
+
+                socks5_connect(&mut stream, proxy, target_addr).await?;
+
+                trace!(
+                    "tcp tunnel {} -> {} chained through socks5 proxy {}",
+                    self.peer_addr,
+                    target_addr,
+                    proxy.addr
+                );
+
+                Ok(stream)
+            }
+        }
+    }
+}
+
+/// Establish a SOCKS5 CONNECT tunnel to `target_addr` on `stream`
+async fn socks5_connect(
+    stream: &mut OutboundTcpStream,
+    proxy: &OutboundProxyConfig,
+    target_addr: &Address,
+) -> io::Result<()> {
+    // 1. Handshake
+    let auth = match (&proxy.username, &proxy.password) {
+        (Some(username), Some(password)) => Some((username, password)),
+        _ => None,
+    };
+
+    let method = if auth.is_some() {
+        socks5::SOCKS5_AUTH_METHOD_PASSWORD
+    } else {
+        socks5::SOCKS5_AUTH_METHOD_NONE
+    };
+
+    let hs = HandshakeRequest::new(vec![method]);
+    hs.write_to(stream).await?;
+
+    let hsp = HandshakeResponse::read_from(stream).await?;
+    if hsp.chosen_method != method {
+        return Err(io::Error::other(format!(
+            "socks5 proxy {} chose unsupported authentication method {:#x}",
+            proxy.addr, hsp.chosen_method
+        )));
+    }
+
+    // 2. Username/Password Authentication (RFC1929)
+    if let Some((username, password)) = auth {
+        let req = PasswdAuthRequest::new(username.as_bytes(), password.as_bytes());
+        req.write_to(stream).await?;
+
+        let resp = PasswdAuthResponse::read_from(stream).await?;
+        if resp.status != 0x00 {
+            return Err(io::Error::new(
+                ErrorKind::PermissionDenied,
+                format!("socks5 proxy {} authentication failed", proxy.addr),
+            ));
+        }
+    }
+
+    // 3. CONNECT
+    let h = TcpRequestHeader::new(Command::TcpConnect, target_addr.clone());
+    h.write_to(stream).await?;
+
+    let hp = TcpResponseHeader::read_from(stream).await?;
+    match hp.reply {
+        Reply::Succeeded => Ok(()),
+        r => Err(io::Error::other(format!(
+            "socks5 proxy {} CONNECT {} failed, reply: {}",
+            proxy.addr, target_addr, r
+        ))),
+    }
 }
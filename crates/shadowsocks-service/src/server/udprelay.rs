@@ -312,6 +312,14 @@ impl UdpServer {
             return None;
         }
 
+        if !context.check_udp_port_allowed(&target_addr) {
+            warn!(
+                "udp client {} outbound {} blocked by udp_allowed_ports",
+                peer_addr, target_addr
+            );
+            return None;
+        }
+
         Some((n, peer_addr, target_addr, control))
     }
 
@@ -504,6 +512,7 @@ impl UdpAssociationContext {
     async fn dispatch_packet(&mut self, mut receiver: mpsc::Receiver<UdpAssociationSendMessage>) {
         let mut outbound_ipv4_buffer = Vec::new();
         let mut outbound_ipv6_buffer = Vec::new();
+        let max_payload = self.context.udp_max_payload();
         let mut keepalive_interval = time::interval(Duration::from_secs(1));
 
         loop {
@@ -520,7 +529,7 @@ impl UdpAssociationContext {
                     self.dispatch_received_packet(peer_addr, &target_addr, &data, &control).await;
                 }
 
-                received_opt = receive_from_outbound_opt(&self.outbound_ipv4_socket, &mut outbound_ipv4_buffer), if self.outbound_ipv4_socket.is_some() => {
+                received_opt = receive_from_outbound_opt(&self.outbound_ipv4_socket, &mut outbound_ipv4_buffer, max_payload), if self.outbound_ipv4_socket.is_some() => {
                     let (n, addr) = match received_opt {
                         Ok(r) => r,
                         Err(err) => {
@@ -535,7 +544,7 @@ impl UdpAssociationContext {
                     self.send_received_respond_packet(addr, &outbound_ipv4_buffer[..n]).await;
                 }
 
-                received_opt = receive_from_outbound_opt(&self.outbound_ipv6_socket, &mut outbound_ipv6_buffer), if self.outbound_ipv6_socket.is_some() => {
+                received_opt = receive_from_outbound_opt(&self.outbound_ipv6_socket, &mut outbound_ipv6_buffer, max_payload), if self.outbound_ipv6_socket.is_some() => {
                     let (n, addr) = match received_opt {
                         Ok(r) => r,
                         Err(err) => {
@@ -574,12 +583,13 @@ impl UdpAssociationContext {
         async fn receive_from_outbound_opt(
             socket: &Option<OutboundUdpSocket>,
             buf: &mut Vec<u8>,
+            max_payload: usize,
         ) -> io::Result<(usize, SocketAddr)> {
             match *socket {
                 None => future::pending().await,
                 Some(ref s) => {
                     if buf.is_empty() {
-                        buf.resize(MAXIMUM_UDP_PAYLOAD_SIZE, 0);
+                        buf.resize(max_payload, 0);
                     }
                     s.recv_from(buf).await
                 }
@@ -653,12 +663,22 @@ impl UdpAssociationContext {
     async fn dispatch_received_outbound_packet(&mut self, target_addr: &Address, data: &[u8]) -> io::Result<()> {
         match *target_addr {
             Address::SocketAddress(sa) => self.send_received_outbound_packet(sa, data).await,
-            Address::DomainNameAddress(ref dname, port) => {
-                lookup_then!(self.context.context_ref(), dname, port, |sa| {
+            Address::DomainNameAddress(ref dname, port) => match self.context.resolve_outbound(target_addr).await? {
+                Some(resolved) => {
+                    let mut last_err = None;
+                    for sa in resolved {
+                        match self.send_received_outbound_packet(sa, data).await {
+                            Ok(()) => return Ok(()),
+                            Err(err) => last_err = Some(err),
+                        }
+                    }
+                    Err(last_err.expect("resolved empty address"))
+                }
+                None => lookup_then!(self.context.context_ref(), dname, port, |sa| {
                     self.send_received_outbound_packet(sa, data).await
                 })
-                .map(|_| ())
-            }
+                .map(|_| ()),
+            },
         }
     }
 
@@ -3,25 +3,32 @@
 #[cfg(unix)]
 use std::path::PathBuf;
 use std::{
+    cmp::Ordering,
     collections::HashMap,
     fmt::{self, Debug, Display},
-    net::SocketAddr,
+    hash::{Hash, Hasher},
+    io,
+    net::{SocketAddr, SocketAddrV6, ToSocketAddrs},
     str::{self, FromStr},
     sync::Arc,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
 use base64::Engine as _;
 use byte_string::ByteStr;
 use bytes::Bytes;
 use cfg_if::cfg_if;
-use log::{error, warn};
+use log::{debug, error, warn};
 use thiserror::Error;
 use url::{self, Url};
 
 #[cfg(any(feature = "stream-cipher", feature = "aead-cipher"))]
 use crate::crypto::v1::openssl_bytes_to_key;
-use crate::{crypto::CipherKind, plugin::PluginConfig, relay::socks5::Address};
+use crate::{
+    crypto::{kind::ParseCipherKindError, CipherCategory, CipherKind},
+    plugin::PluginConfig,
+    relay::socks5::Address,
+};
 
 const USER_KEY_BASE64_ENGINE: base64::engine::GeneralPurpose = base64::engine::GeneralPurpose::new(
     &base64::alphabet::STANDARD,
@@ -68,7 +75,7 @@ impl ServerType {
 }
 
 /// Server mode
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Mode {
     TcpOnly = 0x01,
     TcpAndUdp = 0x03,
@@ -127,6 +134,58 @@ impl FromStr for Mode {
     }
 }
 
+/// Address family preference of configurations
+///
+/// Unlike [`net::AddrFamily`](crate::net::AddrFamily) of sockets, it could be `Any`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AddrFamily {
+    /// IPv4 only
+    V4,
+    /// IPv6 only
+    V6,
+    /// Both IPv4 and IPv6
+    Any,
+}
+
+impl AddrFamily {
+    /// String representation of AddrFamily
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            AddrFamily::V4 => "ipv4",
+            AddrFamily::V6 => "ipv6",
+            AddrFamily::Any => "any",
+        }
+    }
+}
+
+impl fmt::Display for AddrFamily {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AddrFamily {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ipv4" => Ok(AddrFamily::V4),
+            "ipv6" => Ok(AddrFamily::V6),
+            "any" => Ok(AddrFamily::Any),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<crate::net::AddrFamily> for AddrFamily {
+    fn from(af: crate::net::AddrFamily) -> AddrFamily {
+        match af {
+            crate::net::AddrFamily::Ipv4 => AddrFamily::V4,
+            crate::net::AddrFamily::Ipv6 => AddrFamily::V6,
+        }
+    }
+}
+
 struct ModeVisitor;
 
 impl serde::de::Visitor<'_> for ModeVisitor {
@@ -394,8 +453,34 @@ pub enum ServerConfigError {
     /// Key length mismatch
     #[error("invalid key length for {0}, expecting {1} bytes, but found {2} bytes")]
     InvalidKeyLength(CipherKind, usize, usize),
+
+    /// Key derivation parameters are not supported by the method
+    #[error("unsupported key derivation parameters for {0}, {1}")]
+    UnsupportedKdf(CipherKind, &'static str),
+}
+
+/// Key derivation parameters of the password
+///
+/// Stream and AEAD ciphers derive their keys from the password with OpenSSL's `EVP_BytesToKey` (MD5),
+/// which is fixed to `iterations = 1` by all the other shadowsocks implementations.
+/// AEAD-2022 ciphers use base64 encoded keys directly, so they don't accept any parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KdfConfig {
+    /// Iterations of `EVP_BytesToKey`
+    pub iterations: u32,
+}
+
+impl Default for KdfConfig {
+    fn default() -> KdfConfig {
+        KdfConfig { iterations: 1 }
+    }
 }
 
+/// Default `connect_timeout` and `idle_timeout` of servers that don't set any timeout
+///
+/// Set `timeout` to `0` explicitly for no timeout.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
 /// Configuration for a server
 #[derive(Clone, Debug)]
 pub struct ServerConfig {
@@ -407,8 +492,28 @@ pub struct ServerConfig {
     method: CipherKind,
     /// Encryption key
     enc_key: Box<[u8]>,
+    /// Fallback encryption type (method) and its key, derived from the same password
+    fallback_method: Option<(CipherKind, Box<[u8]>)>,
+    /// Key derivation parameters, validated against `method`
+    kdf: Option<KdfConfig>,
     /// Handshake timeout (connect)
+    ///
+    /// Default value of `connect_timeout` and `idle_timeout`
     timeout: Option<Duration>,
+    /// Timeout for establishing connections
+    connect_timeout: Option<Duration>,
+    /// Timeout for inactive streams
+    idle_timeout: Option<Duration>,
+    /// Maximum number of concurrent TCP connections
+    max_connections: Option<usize>,
+    /// Retries of failed connections to this server
+    max_retries: Option<u32>,
+    /// Delay before the first retry, doubled for each of the following retries
+    retry_backoff: Option<Duration>,
+    /// Server is valid since this time
+    valid_from: Option<SystemTime>,
+    /// Server is valid until this time
+    valid_until: Option<SystemTime>,
 
     /// Extensible Identity Headers (AEAD-2022)
     ///
@@ -422,13 +527,15 @@ pub struct ServerConfig {
 
     /// Plugin config
     plugin: Option<PluginConfig>,
-    /// Plugin address
+    /// Plugin address, the local address of this server's own plugin process, filled in by the launcher
     plugin_addr: Option<ServerAddr>,
 
     /// Remark (Profile Name), normally used as an identifier of this erver
     remarks: Option<String>,
     /// ID (SIP008) is a random generated UUID
     id: Option<String>,
+    /// Group tag, for selecting a subset of servers
+    group: Option<String>,
 
     /// Mode
     mode: Mode,
@@ -487,6 +594,48 @@ pub fn method_support_eih(method: CipherKind) -> bool {
     )
 }
 
+/// Alternate spellings of methods, in compacted form (lowercase, only alphanumeric characters)
+const METHOD_ALIASES: &[(&str, &str)] = &[
+    ("chacha20poly1305", "chacha20-ietf-poly1305"),
+    ("xchacha20poly1305", "xchacha20-ietf-poly1305"),
+    ("plaintext", "none"),
+];
+
+/// Parse method name, accepts common aliases and alternate spellings of the canonical names
+///
+/// For example, `aes256gcm`, `AES_256_GCM`, `AEAD_AES_256_GCM` (go-shadowsocks2) are all `aes-256-gcm`,
+/// and `chacha20-poly1305` is `chacha20-ietf-poly1305`. [`CipherKind`] is always formatted with its canonical name.
+pub fn parse_method(name: &str) -> Result<CipherKind, ParseCipherKindError> {
+    if let Ok(method) = name.parse::<CipherKind>() {
+        return Ok(method);
+    }
+
+    fn compact(name: &str) -> String {
+        name.chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_lowercase())
+            .collect()
+    }
+
+    let compacted = compact(name);
+    let compacted = compacted.strip_prefix("aead").unwrap_or(&compacted);
+    let compacted = METHOD_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == compacted)
+        .map(|(_, canonical)| compact(canonical))
+        .unwrap_or_else(|| compacted.to_owned());
+
+    for canonical in crate::crypto::available_ciphers() {
+        if compact(canonical) == compacted {
+            let method = canonical.parse::<CipherKind>()?;
+            debug!("method {} normalized to {}", name, method);
+            return Ok(method);
+        }
+    }
+
+    Err(ParseCipherKindError)
+}
+
 #[allow(clippy::type_complexity)]
 fn password_to_keys<P>(method: CipherKind, password: P) -> Result<(String, Box<[u8]>, Vec<Bytes>), ServerConfigError>
 where
@@ -568,19 +717,71 @@ impl ServerConfig {
             password,
             method,
             enc_key,
+            fallback_method: None,
+            kdf: None,
             identity_keys: Arc::new(identity_keys),
             user_manager: None,
             timeout: None,
+            connect_timeout: None,
+            idle_timeout: None,
+            max_connections: None,
+            max_retries: None,
+            retry_backoff: None,
+            valid_from: None,
+            valid_until: None,
             plugin: None,
             plugin_addr: None,
             remarks: None,
             id: None,
+            group: None,
             mode: Mode::TcpAndUdp, // Server serves TCP & UDP by default
             weight: ServerWeight::new(),
             source: ServerSource::Default,
         })
     }
 
+    /// Create a new `ServerConfig` with a SIP003 plugin
+    ///
+    /// Plugin runs in `tcp_only` mode, just like plugins in SIP002 URLs.
+    pub fn with_plugin<A, P, S>(
+        addr: A,
+        password: P,
+        method: CipherKind,
+        plugin: S,
+        plugin_opts: Option<String>,
+    ) -> Result<ServerConfig, ServerConfigError>
+    where
+        A: Into<ServerAddr>,
+        P: Into<String>,
+        S: Into<String>,
+    {
+        let mut svr_cfg = ServerConfig::new(addr, password, method)?;
+        svr_cfg.set_plugin(PluginConfig {
+            plugin: plugin.into(),
+            plugin_opts,
+            plugin_args: Vec::new(),
+            plugin_mode: Mode::TcpOnly,
+        });
+        Ok(svr_cfg)
+    }
+
+    /// Generate a random password for `method`
+    ///
+    /// AEAD-2022 methods get a base64 encoded key with exactly [`CipherKind::key_len`] bytes,
+    /// the others get a base64 encoded random password with the same strength.
+    pub fn generate_key(method: CipherKind) -> String {
+        let key_len = match method.key_len() {
+            0 if method.is_none() => return String::new(),
+            // Methods without keys (like `table`) use passwords directly
+            0 => 16,
+            n => n,
+        };
+
+        let mut key = vec![0u8; key_len];
+        crate::crypto::utils::random_iv_or_salt(&mut key);
+        USER_KEY_BASE64_ENGINE.encode(&key)
+    }
+
     /// Set encryption method
     pub fn set_method<P>(&mut self, method: CipherKind, password: P) -> Result<(), ServerConfigError>
     where
@@ -594,9 +795,70 @@ impl ServerConfig {
         self.enc_key = enc_key;
         self.identity_keys = Arc::new(identity_keys);
 
+        if let Some((fallback_method, ..)) = self.fallback_method.take() {
+            self.set_fallback_method(fallback_method)?;
+        }
+
+        if let Some(kdf) = self.kdf.take() {
+            self.set_kdf(kdf)?;
+        }
+
         Ok(())
     }
 
+    /// Set fallback encryption method, its key is derived from the same password of the primary `method`
+    ///
+    /// Server tries the fallback method if a client's request couldn't be decrypted with the primary one,
+    /// which is normally used while migrating clients to a new method.
+    pub fn set_fallback_method(&mut self, method: CipherKind) -> Result<(), ServerConfigError> {
+        let (_, enc_key, _) = password_to_keys(method, self.password.as_str())?;
+        self.fallback_method = Some((method, enc_key));
+        Ok(())
+    }
+
+    /// Fallback encryption method
+    pub fn fallback_method(&self) -> Option<CipherKind> {
+        self.fallback_method.as_ref().map(|(m, ..)| *m)
+    }
+
+    /// Key of the fallback encryption method
+    pub fn fallback_key(&self) -> Option<&[u8]> {
+        self.fallback_method.as_ref().map(|(.., k)| k.as_ref())
+    }
+
+    /// Set key derivation parameters
+    ///
+    /// Only methods deriving their keys from passwords (stream and AEAD ciphers) accept parameters,
+    /// and only the values used by key derivation are allowed, so both ends never diverge silently.
+    pub fn set_kdf(&mut self, kdf: KdfConfig) -> Result<(), ServerConfigError> {
+        let derives_key = match self.method.category() {
+            CipherCategory::None => false,
+            #[cfg(feature = "aead-cipher-2022")]
+            CipherCategory::Aead2022 => false,
+            #[allow(unreachable_patterns)]
+            _ => true,
+        };
+
+        if !derives_key {
+            return Err(ServerConfigError::UnsupportedKdf(
+                self.method,
+                "method doesn't derive key from password",
+            ));
+        }
+
+        if kdf.iterations != KdfConfig::default().iterations {
+            return Err(ServerConfigError::UnsupportedKdf(self.method, "iterations must be 1"));
+        }
+
+        self.kdf = Some(kdf);
+        Ok(())
+    }
+
+    /// Key derivation parameters
+    pub fn kdf(&self) -> Option<&KdfConfig> {
+        self.kdf.as_ref()
+    }
+
     /// Set plugin
     pub fn set_plugin(&mut self, p: PluginConfig) {
         self.plugin = Some(p);
@@ -620,6 +882,13 @@ impl ServerConfig {
         self.enc_key.as_ref()
     }
 
+    /// Get a copy of the encryption key derived from `password` with `method`'s KDF
+    ///
+    /// This exposes secret key material, it is intended for tools that verify keys with peers.
+    pub fn derived_key(&self) -> Vec<u8> {
+        self.enc_key.to_vec()
+    }
+
     /// Get password
     pub fn password(&self) -> &str {
         self.password.as_str()
@@ -655,17 +924,45 @@ impl ServerConfig {
         self.method
     }
 
+    /// Check if method is an AEAD cipher (not including AEAD-2022)
+    pub fn is_aead(&self) -> bool {
+        cfg_if! {
+            if #[cfg(feature = "aead-cipher")] {
+                self.method.is_aead()
+            } else {
+                false
+            }
+        }
+    }
+
+    /// Check if method is an AEAD-2022 cipher
+    pub fn is_aead_2022(&self) -> bool {
+        cfg_if! {
+            if #[cfg(feature = "aead-cipher-2022")] {
+                self.method.is_aead_2022()
+            } else {
+                false
+            }
+        }
+    }
+
     /// Get plugin
     pub fn plugin(&self) -> Option<&PluginConfig> {
         self.plugin.as_ref()
     }
 
     /// Set plugin address
+    ///
+    /// Every server with a plugin gets its own plugin process, started by
+    /// [`Plugin::start`](crate::plugin::Plugin::start) listening on an unused local port, and that address should be set here
+    /// before the server is used. The process is killed when its [`Plugin`](crate::plugin::Plugin) is dropped.
+    /// `sslocal`'s balancer restarts plugins of all servers when servers are reloaded,
+    /// plugins of the old servers (including the removed ones) are killed after the new ones are started.
     pub fn set_plugin_addr(&mut self, a: ServerAddr) {
         self.plugin_addr = Some(a);
     }
 
-    /// Get plugin address
+    /// Get plugin address, `None` if the plugin hasn't been started
     pub fn plugin_addr(&self) -> Option<&ServerAddr> {
         self.plugin_addr.as_ref()
     }
@@ -690,14 +987,86 @@ impl ServerConfig {
         &self.addr
     }
 
-    /// Set timeout
+    /// Set timeout, `0` means no timeout
     pub fn set_timeout(&mut self, timeout: Duration) {
         self.timeout = Some(timeout);
     }
 
     /// Timeout
     pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.filter(|t| !t.is_zero())
+    }
+
+    /// Timeout of this server with a `global` (top-level) timeout, then [`DEFAULT_TIMEOUT`] if neither is set
+    ///
+    /// Server's own timeout always takes precedence over `global`, even if it is `0` (no timeout).
+    pub fn effective_timeout(&self, global: Option<Duration>) -> Option<Duration> {
         self.timeout
+            .or(global)
+            .or(Some(DEFAULT_TIMEOUT))
+            .filter(|t| !t.is_zero())
+    }
+
+    /// Set connect timeout, which takes precedence over `timeout`, `0` means no timeout
+    pub fn set_connect_timeout(&mut self, timeout: Duration) {
+        self.connect_timeout = Some(timeout);
+    }
+
+    /// Connect timeout, fallback to `timeout`, then [`DEFAULT_TIMEOUT`] if not set
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout
+            .or(self.timeout)
+            .or(Some(DEFAULT_TIMEOUT))
+            .filter(|t| !t.is_zero())
+    }
+
+    /// Set idle timeout, which takes precedence over `timeout`, `0` means no timeout
+    pub fn set_idle_timeout(&mut self, timeout: Duration) {
+        self.idle_timeout = Some(timeout);
+    }
+
+    /// Idle timeout, fallback to `timeout`, then [`DEFAULT_TIMEOUT`] if not set
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+            .or(self.timeout)
+            .or(Some(DEFAULT_TIMEOUT))
+            .filter(|t| !t.is_zero())
+    }
+
+    /// Set maximum number of concurrent TCP connections
+    pub fn set_max_connections(&mut self, max_connections: usize) {
+        self.max_connections = Some(max_connections);
+    }
+
+    /// Maximum number of concurrent TCP connections
+    ///
+    /// Server rejects (closes) new connections immediately while the limit is reached, they are not queued.
+    pub fn max_connections(&self) -> Option<usize> {
+        self.max_connections
+    }
+
+    /// Set retries of failed connections to this server
+    pub fn set_max_retries(&mut self, max_retries: u32) {
+        self.max_retries = Some(max_retries);
+    }
+
+    /// Retries of failed connections to this server
+    ///
+    /// Clients connect only once if not set, the same as `0`.
+    pub fn max_retries(&self) -> Option<u32> {
+        self.max_retries
+    }
+
+    /// Set delay before the first retry
+    pub fn set_retry_backoff(&mut self, backoff: Duration) {
+        self.retry_backoff = Some(backoff);
+    }
+
+    /// Delay before the first retry, doubled for each of the following retries
+    ///
+    /// Clients wait 100ms if not set and `max_retries` is set.
+    pub fn retry_backoff(&self) -> Option<Duration> {
+        self.retry_backoff
     }
 
     /// Get server's remark
@@ -726,6 +1095,44 @@ impl ServerConfig {
         self.id = Some(id.into())
     }
 
+    /// Get server's group tag
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    /// Set server's group tag
+    pub fn set_group<S>(&mut self, group: S)
+    where
+        S: Into<String>,
+    {
+        self.group = Some(group.into())
+    }
+
+    /// Set the time that server is valid since
+    pub fn set_valid_from(&mut self, t: SystemTime) {
+        self.valid_from = Some(t);
+    }
+
+    /// The time that server is valid since
+    pub fn valid_from(&self) -> Option<SystemTime> {
+        self.valid_from
+    }
+
+    /// Set the time that server is valid until
+    pub fn set_valid_until(&mut self, t: SystemTime) {
+        self.valid_until = Some(t);
+    }
+
+    /// The time that server is valid until
+    pub fn valid_until(&self) -> Option<SystemTime> {
+        self.valid_until
+    }
+
+    /// Check if `now` is in server's validity window, both ends are inclusive
+    pub fn is_currently_valid(&self, now: SystemTime) -> bool {
+        self.valid_from.map_or(true, |t| t <= now) && self.valid_until.map_or(true, |t| now <= t)
+    }
+
     /// Get server's `Mode`
     pub fn mode(&self) -> Mode {
         self.mode
@@ -919,7 +1326,7 @@ impl ServerConfig {
             }
         };
 
-        let method = match method.parse::<CipherKind>() {
+        let method = match parse_method(&method) {
             Ok(m) => m,
             Err(err) => {
                 error!("failed to parse \"{}\" to CipherKind, err: {:?}", method, err);
@@ -970,7 +1377,7 @@ impl ServerConfig {
 
     /// Check if it is a basic format server
     pub fn is_basic(&self) -> bool {
-        self.remarks.is_none() && self.id.is_none()
+        self.remarks.is_none() && self.id.is_none() && self.group.is_none()
     }
 }
 
@@ -1006,7 +1413,10 @@ impl FromStr for ServerConfig {
 }
 
 /// Server address
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+///
+/// Comparing and hashing ignore `flowinfo` of IPv6 addresses, which is not a part of the address,
+/// but `scope_id` is respected. So `[fe80::1%2]:8388` with different `flowinfo` are equal.
+#[derive(Clone, Debug)]
 pub enum ServerAddr {
     /// IP Address
     SocketAddr(SocketAddr),
@@ -1015,6 +1425,11 @@ pub enum ServerAddr {
 }
 
 impl ServerAddr {
+    /// Check if the address is a domain name
+    pub fn is_domain(&self) -> bool {
+        matches!(*self, ServerAddr::DomainName(..))
+    }
+
     /// Get string representation of domain
     pub fn host(&self) -> String {
         match *self {
@@ -1030,6 +1445,83 @@ impl ServerAddr {
             ServerAddr::DomainName(_, p) => p,
         }
     }
+
+    /// Get the socket address, `None` for domain names which have to be resolved first
+    pub fn as_socket_addr(&self) -> Option<&SocketAddr> {
+        match *self {
+            ServerAddr::SocketAddr(ref s) => Some(s),
+            ServerAddr::DomainName(..) => None,
+        }
+    }
+
+    /// Resolve to the first socket address with the system's resolver
+    ///
+    /// This will block the current thread, it is for checking configurations before starting servers.
+    /// Running servers resolve domain names with `Context::dns_resolve`.
+    pub fn resolve(&self) -> io::Result<SocketAddr> {
+        match *self {
+            ServerAddr::SocketAddr(ref s) => Ok(*s),
+            ServerAddr::DomainName(ref dm, port) => match (dm.as_str(), port).to_socket_addrs()?.next() {
+                Some(addr) => Ok(addr),
+                None => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{dm} doesn't have any addresses"),
+                )),
+            },
+        }
+    }
+
+    /// Address family of socket addresses, `None` for domain names
+    pub fn family(&self) -> Option<AddrFamily> {
+        match *self {
+            ServerAddr::SocketAddr(SocketAddr::V4(..)) => Some(AddrFamily::V4),
+            ServerAddr::SocketAddr(SocketAddr::V6(..)) => Some(AddrFamily::V6),
+            ServerAddr::DomainName(..) => None,
+        }
+    }
+
+    fn cmp_key(&self) -> ServerAddrKey<'_> {
+        match *self {
+            ServerAddr::SocketAddr(SocketAddr::V6(ref a)) => {
+                ServerAddrKey::SocketAddr(SocketAddr::V6(SocketAddrV6::new(*a.ip(), a.port(), 0, a.scope_id())))
+            }
+            ServerAddr::SocketAddr(ref a) => ServerAddrKey::SocketAddr(*a),
+            ServerAddr::DomainName(ref dm, port) => ServerAddrKey::DomainName(dm, port),
+        }
+    }
+}
+
+/// Key for comparing and hashing `ServerAddr`
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum ServerAddrKey<'a> {
+    SocketAddr(SocketAddr),
+    DomainName(&'a str, u16),
+}
+
+impl PartialEq for ServerAddr {
+    fn eq(&self, other: &ServerAddr) -> bool {
+        self.cmp_key() == other.cmp_key()
+    }
+}
+
+impl Eq for ServerAddr {}
+
+impl PartialOrd for ServerAddr {
+    fn partial_cmp(&self, other: &ServerAddr) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ServerAddr {
+    fn cmp(&self, other: &ServerAddr) -> Ordering {
+        self.cmp_key().cmp(&other.cmp_key())
+    }
+}
+
+impl Hash for ServerAddr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.cmp_key().hash(state)
+    }
 }
 
 /// Parse `ServerAddr` error
@@ -1412,4 +1904,237 @@ mod test {
         let server_config = ServerConfig::from_url("ss://foo:bar@127.0.0.1:9999");
         assert!(matches!(server_config, Err(UrlParseError::InvalidMethod)));
     }
+
+    #[test]
+    fn test_available_ciphers_round_trip() {
+        for name in crate::crypto::available_ciphers() {
+            // Aliases, like `plain`, are formatted with their canonical names
+            let kind = name.parse::<CipherKind>().unwrap();
+            assert_eq!(kind.to_string().parse::<CipherKind>().unwrap(), kind);
+        }
+    }
+
+    #[test]
+    fn test_server_addr_ignore_flowinfo() {
+        use std::{collections::HashSet, net::Ipv6Addr};
+
+        let ip = "fe80::1".parse::<Ipv6Addr>().unwrap();
+        let a = ServerAddr::from(SocketAddr::V6(SocketAddrV6::new(ip, 8388, 0, 2)));
+        let b = ServerAddr::from(SocketAddr::V6(SocketAddrV6::new(ip, 8388, 12345, 2)));
+        let c = ServerAddr::from(SocketAddr::V6(SocketAddrV6::new(ip, 8388, 0, 3)));
+
+        assert_eq!(a, b);
+        assert_eq!(a.cmp(&b), Ordering::Equal);
+        assert_ne!(a, c);
+        assert_eq!(HashSet::from([a.clone(), b, c]).len(), 2);
+
+        assert!(a < ServerAddr::from(("example.com", 8388)));
+    }
+
+    #[test]
+    fn test_server_config_cipher_category() {
+        let addr = "127.0.0.1:8388".parse::<ServerAddr>().unwrap();
+
+        let svr_cfg = ServerConfig::new(addr.clone(), "", CipherKind::NONE).unwrap();
+        assert!(!svr_cfg.is_aead() && !svr_cfg.is_aead_2022());
+
+        #[cfg(feature = "stream-cipher")]
+        {
+            let svr_cfg = ServerConfig::new(addr.clone(), "password", CipherKind::AES_256_CTR).unwrap();
+            assert!(!svr_cfg.is_aead() && !svr_cfg.is_aead_2022());
+        }
+
+        #[cfg(feature = "aead-cipher")]
+        {
+            let svr_cfg = ServerConfig::new(addr.clone(), "password", CipherKind::AES_256_GCM).unwrap();
+            assert!(svr_cfg.is_aead() && !svr_cfg.is_aead_2022());
+        }
+
+        #[cfg(feature = "aead-cipher-2022")]
+        {
+            let password = "AQEBAQEBAQEBAQEBAQEBAQ==";
+            let svr_cfg = ServerConfig::new(addr, password, CipherKind::AEAD2022_BLAKE3_AES_128_GCM).unwrap();
+            assert!(!svr_cfg.is_aead() && svr_cfg.is_aead_2022());
+        }
+    }
+
+    #[test]
+    fn test_server_config_plugin_addr() {
+        let addr = "127.0.0.1:8388".parse::<ServerAddr>().unwrap();
+        let mut svr_cfg = ServerConfig::with_plugin(
+            addr.clone(),
+            "",
+            CipherKind::NONE,
+            "obfs-local",
+            Some("obfs=http".to_owned()),
+        )
+        .unwrap();
+        assert!(svr_cfg.timeout().is_none());
+
+        let plugin = svr_cfg.plugin().unwrap();
+        assert_eq!(plugin.plugin, "obfs-local");
+        assert_eq!(plugin.plugin_opts.as_deref(), Some("obfs=http"));
+        assert!(plugin.plugin_args.is_empty());
+        assert_eq!(plugin.plugin_mode, Mode::TcpOnly);
+
+        // Plugin hasn't been started
+        assert!(svr_cfg.plugin_addr().is_none());
+        assert_eq!(svr_cfg.tcp_external_addr(), &addr);
+
+        let plugin_addr = "127.0.0.1:50000".parse::<ServerAddr>().unwrap();
+        svr_cfg.set_plugin_addr(plugin_addr.clone());
+        assert_eq!(svr_cfg.tcp_external_addr(), &plugin_addr);
+        assert_eq!(svr_cfg.udp_external_addr(), &addr);
+    }
+
+    #[test]
+    fn test_server_config_effective_timeout() {
+        let addr = "127.0.0.1:8388".parse::<ServerAddr>().unwrap();
+        let mut svr_cfg = ServerConfig::new(addr, "", CipherKind::NONE).unwrap();
+
+        assert_eq!(svr_cfg.effective_timeout(None), Some(DEFAULT_TIMEOUT));
+        assert_eq!(
+            svr_cfg.effective_timeout(Some(Duration::from_secs(10))),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(svr_cfg.effective_timeout(Some(Duration::ZERO)), None);
+
+        svr_cfg.set_timeout(Duration::from_secs(20));
+        assert_eq!(
+            svr_cfg.effective_timeout(Some(Duration::from_secs(10))),
+            Some(Duration::from_secs(20))
+        );
+
+        svr_cfg.set_timeout(Duration::ZERO);
+        assert_eq!(svr_cfg.effective_timeout(Some(Duration::from_secs(10))), None);
+    }
+
+    #[test]
+    fn test_server_config_generate_key() {
+        let addr = "127.0.0.1:8388".parse::<ServerAddr>().unwrap();
+
+        assert_eq!(ServerConfig::generate_key(CipherKind::NONE), "");
+
+        let password = ServerConfig::generate_key(CipherKind::AES_256_GCM);
+        assert_eq!(USER_KEY_BASE64_ENGINE.decode(&password).unwrap().len(), 32);
+        assert_ne!(password, ServerConfig::generate_key(CipherKind::AES_256_GCM));
+
+        #[cfg(feature = "aead-cipher-2022")]
+        for method in [
+            CipherKind::AEAD2022_BLAKE3_AES_128_GCM,
+            CipherKind::AEAD2022_BLAKE3_AES_256_GCM,
+            CipherKind::AEAD2022_BLAKE3_CHACHA20_POLY1305,
+        ] {
+            let key = ServerConfig::generate_key(method);
+            assert_eq!(USER_KEY_BASE64_ENGINE.decode(&key).unwrap().len(), method.key_len());
+            ServerConfig::new(addr.clone(), key, method).unwrap();
+        }
+
+        ServerConfig::new(addr, password, CipherKind::AES_256_GCM).unwrap();
+    }
+
+    #[test]
+    fn test_addr_family() {
+        for (s, af) in [
+            ("ipv4", AddrFamily::V4),
+            ("ipv6", AddrFamily::V6),
+            ("any", AddrFamily::Any),
+        ] {
+            assert_eq!(s.parse::<AddrFamily>(), Ok(af));
+            assert_eq!(af.to_string(), s);
+        }
+        assert!("IPv4".parse::<AddrFamily>().is_err());
+        assert!("".parse::<AddrFamily>().is_err());
+
+        let addr = "127.0.0.1:8388".parse::<ServerAddr>().unwrap();
+        assert_eq!(addr.family(), Some(AddrFamily::V4));
+        let addr = "[::1]:8388".parse::<ServerAddr>().unwrap();
+        assert_eq!(addr.family(), Some(AddrFamily::V6));
+        let addr = "example.com:8388".parse::<ServerAddr>().unwrap();
+        assert_eq!(addr.family(), None);
+    }
+
+    #[test]
+    fn test_server_addr_as_socket_addr() {
+        let addr = "127.0.0.1:8388".parse::<SocketAddr>().unwrap();
+        assert_eq!(ServerAddr::from(addr).as_socket_addr(), Some(&addr));
+        assert_eq!(ServerAddr::from(("example.com", 8388)).as_socket_addr(), None);
+    }
+
+    #[test]
+    fn test_parse_method_aliases() {
+        for (name, method) in [
+            ("aes-256-gcm", CipherKind::AES_256_GCM),
+            ("aes256gcm", CipherKind::AES_256_GCM),
+            ("AES_128_GCM", CipherKind::AES_128_GCM),
+            ("AEAD_AES_256_GCM", CipherKind::AES_256_GCM),
+            ("chacha20-poly1305", CipherKind::CHACHA20_POLY1305),
+            ("AEAD_CHACHA20_POLY1305", CipherKind::CHACHA20_POLY1305),
+            ("chacha20ietfpoly1305", CipherKind::CHACHA20_POLY1305),
+        ] {
+            let parsed = parse_method(name).unwrap();
+            assert_eq!(parsed, method, "{name}");
+            assert_eq!(parsed.to_string(), method.to_string());
+        }
+
+        assert!(parse_method("aes-256-foo").is_err());
+    }
+
+    #[test]
+    fn test_server_config_derived_key() {
+        let addr = "127.0.0.1:8388".parse::<ServerAddr>().unwrap();
+
+        // EVP_BytesToKey, MD5("foobar")
+        let svr_cfg = ServerConfig::new(addr, "foobar", CipherKind::AES_128_GCM).unwrap();
+        assert_eq!(
+            svr_cfg.derived_key(),
+            [0x38, 0x58, 0xf6, 0x22, 0x30, 0xac, 0x3c, 0x91, 0x5f, 0x30, 0x0c, 0x66, 0x43, 0x12, 0xc6, 0x3f]
+        );
+        assert_eq!(svr_cfg.derived_key(), svr_cfg.key());
+    }
+
+    #[test]
+    fn test_server_addr_accessors() {
+        let addr = "127.0.0.1:8388".parse::<ServerAddr>().unwrap();
+        assert!(!addr.is_domain());
+        assert_eq!(addr.host(), "127.0.0.1");
+        assert_eq!(addr.port(), 8388);
+
+        let addr = "example.com:443".parse::<ServerAddr>().unwrap();
+        assert!(addr.is_domain());
+        assert_eq!(addr.host(), "example.com");
+        assert_eq!(addr.port(), 443);
+    }
+
+    #[test]
+    fn test_server_config_url_special_password() {
+        let addr = "127.0.0.1:8388".parse::<ServerAddr>().unwrap();
+
+        for password in ["p@ss:word#1", "a/b?c=d&e", "100% sure :@/#"] {
+            let svr_cfg = ServerConfig::new(addr.clone(), password, CipherKind::AES_256_GCM).unwrap();
+            let decoded = ServerConfig::from_url(&svr_cfg.to_url()).unwrap();
+            assert_eq!(decoded.password(), password);
+            assert_eq!(decoded.method(), CipherKind::AES_256_GCM);
+            assert_eq!(decoded.addr(), &addr);
+        }
+
+        // Plain percent-encoded userinfo
+        let decoded = ServerConfig::from_url("ss://aes-256-gcm:p%40ss%3Aword%231@127.0.0.1:8388").unwrap();
+        assert_eq!(decoded.password(), "p@ss:word#1");
+        assert_eq!(decoded.addr(), &addr);
+    }
+
+    #[test]
+    fn test_server_addr_serde_round_trip() {
+        for s in ["127.0.0.1:8388", "[::1]:8388", "example.com:443"] {
+            let addr = s.parse::<ServerAddr>().unwrap();
+            assert_eq!(addr.to_string(), s);
+
+            let json = serde_json::to_string(&addr).unwrap();
+            assert_eq!(json, format!("\"{s}\""));
+            assert_eq!(serde_json::from_str::<ServerAddr>(&json).unwrap(), addr);
+        }
+
+        assert!(serde_json::from_str::<ServerAddr>("\"example.com\"").is_err());
+    }
 }
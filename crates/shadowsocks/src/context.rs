@@ -26,6 +26,9 @@ pub struct Context {
 
     // Connect IPv6 address first
     ipv6_first: bool,
+
+    // Drop IPv6 addresses from DNS results
+    disable_ipv6: bool,
 }
 
 /// `Context` for sharing between services
@@ -39,6 +42,7 @@ impl Context {
             replay_policy: ReplayAttackPolicy::Default,
             dns_resolver: Arc::new(DnsResolver::system_resolver()),
             ipv6_first: false,
+            disable_ipv6: false,
         }
     }
 
@@ -133,8 +137,26 @@ impl Context {
     }
 
     /// Resolves DNS address to `SocketAddr`s
+    ///
+    /// IPv6 addresses are filtered out if `disable_ipv6` is set,
+    /// returns an error if there are no IPv4 addresses in that case.
     pub async fn dns_resolve<'a>(&self, addr: &'a str, port: u16) -> io::Result<impl Iterator<Item = SocketAddr> + 'a> {
-        self.dns_resolver.resolve(addr, port).await
+        let disable_ipv6 = self.disable_ipv6;
+        let mut addrs = self
+            .dns_resolver
+            .resolve(addr, port)
+            .await?
+            .filter(move |addr| !disable_ipv6 || addr.is_ipv4())
+            .peekable();
+
+        if disable_ipv6 && addrs.peek().is_none() {
+            let err = io::Error::other(format!(
+                "dns resolve {addr}:{port} got no IPv4 addresses, IPv6 is disabled"
+            ));
+            return Err(err);
+        }
+
+        Ok(addrs)
     }
 
     /// Try to connect IPv6 addresses first if hostname could be resolved to both IPv4 and IPv6
@@ -147,6 +169,16 @@ impl Context {
         self.ipv6_first
     }
 
+    /// Never use IPv6 addresses resolved from hostnames
+    pub fn set_disable_ipv6(&mut self, disable_ipv6: bool) {
+        self.disable_ipv6 = disable_ipv6;
+    }
+
+    /// Never use IPv6 addresses resolved from hostnames
+    pub fn disable_ipv6(&self) -> bool {
+        self.disable_ipv6
+    }
+
     /// Set policy against replay attack
     pub fn set_replay_attack_policy(&mut self, replay_policy: ReplayAttackPolicy) {
         self.replay_policy = replay_policy;
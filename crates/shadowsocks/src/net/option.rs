@@ -68,6 +68,11 @@ pub struct ConnectOpts {
     /// Outbound socket binds to interface
     pub bind_interface: Option<String>,
 
+    /// Outbound socket's IP TTL (IPv4) or unicast hop limit (IPv6)
+    ///
+    /// Platforms that don't support the option will ignore it with a warning
+    pub ttl: Option<u32>,
+
     /// TCP options
     pub tcp: TcpSocketOpts,
 
@@ -76,7 +81,7 @@ pub struct ConnectOpts {
 }
 
 /// Inbound connection options
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug)]
 pub struct AcceptOpts {
     /// TCP options
     pub tcp: TcpSocketOpts,
@@ -86,4 +91,24 @@ pub struct AcceptOpts {
 
     /// Enable IPV6_V6ONLY option for socket
     pub ipv6_only: bool,
+
+    /// Enable SO_REUSEPORT option for listening sockets
+    pub reuse_port: bool,
+
+    /// Enable SO_REUSEADDR option for TCP listening sockets, enabled by default
+    ///
+    /// It is never set on Windows, where it allows hijacking sockets that are in use
+    pub reuse_address: bool,
+}
+
+impl Default for AcceptOpts {
+    fn default() -> AcceptOpts {
+        AcceptOpts {
+            tcp: TcpSocketOpts::default(),
+            udp: UdpSocketOpts::default(),
+            ipv6_only: false,
+            reuse_port: false,
+            reuse_address: true,
+        }
+    }
 }
@@ -6,7 +6,7 @@ use std::{
 use cfg_if::cfg_if;
 use log::{debug, warn};
 use once_cell::sync::Lazy;
-use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use socket2::{Domain, Protocol, SockAddr, SockRef, Socket, Type};
 use tokio::net::TcpSocket;
 
 use super::ConnectOpts;
@@ -45,9 +45,33 @@ fn set_common_sockopt_for_connect(addr: SocketAddr, socket: &TcpSocket, opts: &C
         socket.set_recv_buffer_size(buf_size)?;
     }
 
+    // Set `IP_TTL` / `IPV6_UNICAST_HOPS`
+    if let Some(ttl) = opts.ttl {
+        set_outbound_ttl(socket, addr.is_ipv6(), ttl);
+    }
+
     Ok(())
 }
 
+/// Set `IP_TTL` for IPv4 sockets, or `IPV6_UNICAST_HOPS` for IPv6 sockets
+///
+/// Failures are logged and ignored, the socket is still usable with the system default
+pub(crate) fn set_outbound_ttl<S>(socket: &S, is_ipv6: bool, ttl: u32)
+where
+    for<'a> SockRef<'a>: From<&'a S>,
+{
+    let sock = SockRef::from(socket);
+    let result = if is_ipv6 {
+        sock.set_unicast_hops_v6(ttl)
+    } else {
+        sock.set_ttl(ttl)
+    };
+
+    if let Err(err) = result {
+        warn!("failed to set outbound socket ttl {}, error: {}, ignored", ttl, err);
+    }
+}
+
 #[cfg(all(not(windows), not(unix)))]
 #[inline]
 fn set_common_sockopt_after_connect_sys(_: &tokio::net::TcpStream, _: &ConnectOpts) -> io::Result<()> {
@@ -438,14 +438,18 @@ pub fn set_disable_ip_fragmentation<S: AsRawSocket>(af: AddrFamily, socket: &S)
 /// Create a `UdpSocket` binded to `addr`
 ///
 /// It also disables `WSAECONNRESET` for UDP socket
-pub async fn create_inbound_udp_socket(addr: &SocketAddr, ipv6_only: bool) -> io::Result<UdpSocket> {
+pub async fn create_inbound_udp_socket(addr: &SocketAddr, opts: &AcceptOpts) -> io::Result<UdpSocket> {
+    if opts.reuse_port {
+        warn!("SO_REUSEPORT is not supported on this platform, listening {} without it", addr);
+    }
+
     let set_dual_stack = is_dual_stack_addr(addr);
 
     let socket = if !set_dual_stack {
         UdpSocket::bind(addr).await?
     } else {
         let socket = Socket::new(Domain::for_address(*addr), Type::DGRAM, Some(Protocol::UDP))?;
-        socket_bind_dual_stack(&socket, addr, ipv6_only)?;
+        socket_bind_dual_stack(&socket, addr, opts.ipv6_only)?;
 
         // UdpSocket::from_std requires socket to be non-blocked
         socket.set_nonblocking(true)?;
@@ -13,6 +13,7 @@ use std::{
 };
 
 use futures::{future, ready};
+use log::warn;
 use pin_project::pin_project;
 use tokio::{
     io::{AsyncRead, AsyncWrite, ReadBuf},
@@ -148,7 +149,22 @@ impl TcpListener {
         // which allows “socket hijacking”, so we explicitly don't set it here.
         // https://docs.microsoft.com/en-us/windows/win32/winsock/using-so-reuseaddr-and-so-exclusiveaddruse
         #[cfg(not(windows))]
-        socket.set_reuseaddr(true)?;
+        if accept_opts.reuse_address {
+            socket.set_reuseaddr(true)?;
+        }
+
+        if accept_opts.reuse_port {
+            #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))))]
+            if let Err(err) = socket.set_reuseport(true) {
+                warn!("failed to set SO_REUSEPORT for {}, error: {}", addr, err);
+            }
+
+            #[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin")))))]
+            warn!(
+                "SO_REUSEPORT is not supported on this platform, listening {} without it",
+                addr
+            );
+        }
 
         let set_dual_stack = is_dual_stack_addr(addr);
 
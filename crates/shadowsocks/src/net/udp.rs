@@ -38,7 +38,7 @@ use tokio::{io::ReadBuf, net::ToSocketAddrs};
 use crate::{context::Context, relay::socks5::Address, ServerAddr};
 
 use super::{
-    sys::{bind_outbound_udp_socket, create_inbound_udp_socket, create_outbound_udp_socket},
+    sys::{bind_outbound_udp_socket, create_inbound_udp_socket, create_outbound_udp_socket, set_outbound_ttl},
     AcceptOpts, AddrFamily, ConnectOpts,
 };
 
@@ -84,6 +84,24 @@ fn make_mtu_error(packet_size: usize, mtu: usize) -> io::Error {
     )
 }
 
+/// Create an outbound socket with `ConnectOpts::ttl` applied
+async fn create_outbound_socket(af: AddrFamily, opts: &ConnectOpts) -> io::Result<tokio::net::UdpSocket> {
+    let socket = create_outbound_udp_socket(af, opts).await?;
+    if let Some(ttl) = opts.ttl {
+        set_outbound_ttl(&socket, af == AddrFamily::Ipv6, ttl);
+    }
+    Ok(socket)
+}
+
+/// Bind an outbound socket with `ConnectOpts::ttl` applied
+async fn bind_outbound_socket(addr: &SocketAddr, opts: &ConnectOpts) -> io::Result<tokio::net::UdpSocket> {
+    let socket = bind_outbound_udp_socket(addr, opts).await?;
+    if let Some(ttl) = opts.ttl {
+        set_outbound_ttl(&socket, addr.is_ipv6(), ttl);
+    }
+    Ok(socket)
+}
+
 /// Wrappers for outbound `UdpSocket`
 #[derive(Debug)]
 pub struct UdpSocket {
@@ -100,13 +118,13 @@ impl UdpSocket {
     ) -> io::Result<UdpSocket> {
         let socket = match *addr {
             ServerAddr::SocketAddr(ref remote_addr) => {
-                let socket = create_outbound_udp_socket(From::from(remote_addr), opts).await?;
+                let socket = create_outbound_socket(From::from(remote_addr), opts).await?;
                 socket.connect(remote_addr).await?;
                 socket
             }
             ServerAddr::DomainName(ref dname, port) => {
                 lookup_then!(context, dname, port, |remote_addr| {
-                    let s = create_outbound_udp_socket(From::from(&remote_addr), opts).await?;
+                    let s = create_outbound_socket(From::from(&remote_addr), opts).await?;
                     s.connect(remote_addr).await.map(|_| s)
                 })?
                 .1
@@ -127,13 +145,13 @@ impl UdpSocket {
     ) -> io::Result<UdpSocket> {
         let socket = match *addr {
             Address::SocketAddress(ref remote_addr) => {
-                let socket = create_outbound_udp_socket(From::from(remote_addr), opts).await?;
+                let socket = create_outbound_socket(From::from(remote_addr), opts).await?;
                 socket.connect(remote_addr).await?;
                 socket
             }
             Address::DomainNameAddress(ref dname, port) => {
                 lookup_then!(context, dname, port, |remote_addr| {
-                    let s = create_outbound_udp_socket(From::from(&remote_addr), opts).await?;
+                    let s = create_outbound_socket(From::from(&remote_addr), opts).await?;
                     s.connect(remote_addr).await.map(|_| s)
                 })?
                 .1
@@ -148,7 +166,7 @@ impl UdpSocket {
 
     /// Connects to shadowsocks server
     pub async fn connect_with_opts(addr: &SocketAddr, opts: &ConnectOpts) -> io::Result<UdpSocket> {
-        let socket = create_outbound_udp_socket(From::from(addr), opts).await?;
+        let socket = create_outbound_socket(From::from(addr), opts).await?;
         socket.connect(addr).await?;
         Ok(UdpSocket {
             socket,
@@ -158,12 +176,10 @@ impl UdpSocket {
 
     /// Binds to a specific address with opts
     pub async fn connect_any_with_opts<AF: Into<AddrFamily>>(af: AF, opts: &ConnectOpts) -> io::Result<UdpSocket> {
-        create_outbound_udp_socket(af.into(), opts)
-            .await
-            .map(|socket| UdpSocket {
-                socket,
-                mtu: opts.udp.mtu,
-            })
+        create_outbound_socket(af.into(), opts).await.map(|socket| UdpSocket {
+            socket,
+            mtu: opts.udp.mtu,
+        })
     }
 
     /// Binds to a specific address as an outbound socket
@@ -174,7 +190,7 @@ impl UdpSocket {
 
     /// Binds to a specific address with opts as an outbound socket
     pub async fn bind_with_opts(addr: &SocketAddr, opts: &ConnectOpts) -> io::Result<UdpSocket> {
-        bind_outbound_udp_socket(addr, opts).await.map(|socket| UdpSocket {
+        bind_outbound_socket(addr, opts).await.map(|socket| UdpSocket {
             socket,
             mtu: opts.udp.mtu,
         })
@@ -188,7 +204,7 @@ impl UdpSocket {
 
     /// Binds to a specific address (inbound)
     pub async fn listen_with_opts(addr: &SocketAddr, opts: AcceptOpts) -> io::Result<UdpSocket> {
-        let socket = create_inbound_udp_socket(addr, opts.ipv6_only).await?;
+        let socket = create_inbound_udp_socket(addr, &opts).await?;
         Ok(UdpSocket {
             socket,
             mtu: opts.udp.mtu,
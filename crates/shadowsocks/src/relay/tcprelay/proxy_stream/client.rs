@@ -4,12 +4,13 @@ use std::{
     io::{self, ErrorKind},
     pin::Pin,
     task::{self, Poll},
+    time::{Duration, SystemTime},
 };
 
 use bytes::{BufMut, BytesMut};
 use cfg_if::cfg_if;
 use futures::ready;
-use log::trace;
+use log::{debug, trace};
 use once_cell::sync::Lazy;
 use pin_project::pin_project;
 use tokio::{
@@ -57,6 +58,34 @@ pub struct ProxyClientStream<S> {
 
 static DEFAULT_CONNECT_OPTS: Lazy<ConnectOpts> = Lazy::new(Default::default);
 
+/// Delay before the first retry if `retry_backoff` is not set
+const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Connect to the shadowsocks' server, with `connect_timeout` for each attempt
+async fn connect_server(
+    context: &SharedContext,
+    svr_cfg: &ServerConfig,
+    opts: &ConnectOpts,
+) -> io::Result<OutboundTcpStream> {
+    match svr_cfg.connect_timeout() {
+        Some(d) => {
+            match time::timeout(
+                d,
+                OutboundTcpStream::connect_server_with_opts(context, svr_cfg.tcp_external_addr(), opts),
+            )
+            .await
+            {
+                Ok(r) => r,
+                Err(..) => Err(io::Error::new(
+                    ErrorKind::TimedOut,
+                    format!("connect {} timeout", svr_cfg.addr()),
+                )),
+            }
+        }
+        None => OutboundTcpStream::connect_server_with_opts(context, svr_cfg.tcp_external_addr(), opts).await,
+    }
+}
+
 impl ProxyClientStream<OutboundTcpStream> {
     /// Connect to target `addr` via shadowsocks' server configured by `svr_cfg`
     pub async fn connect<A>(
@@ -114,25 +143,36 @@ where
         A: Into<Address>,
         F: FnOnce(OutboundTcpStream) -> S,
     {
-        let stream = match svr_cfg.timeout() {
-            Some(d) => {
-                match time::timeout(
-                    d,
-                    OutboundTcpStream::connect_server_with_opts(&context, svr_cfg.tcp_external_addr(), opts),
-                )
-                .await
-                {
-                    Ok(Ok(s)) => s,
-                    Ok(Err(e)) => return Err(e),
-                    Err(..) => {
-                        return Err(io::Error::new(
-                            ErrorKind::TimedOut,
-                            format!("connect {} timeout", svr_cfg.addr()),
-                        ))
-                    }
+        // Expired servers must not be used, even if there is no other server
+        if !svr_cfg.is_currently_valid(SystemTime::now()) {
+            return Err(io::Error::other(format!(
+                "server {} is out of its validity window",
+                svr_cfg.addr()
+            )));
+        }
+
+        let max_retries = svr_cfg.max_retries().unwrap_or(0);
+        let mut backoff = svr_cfg.retry_backoff().unwrap_or(DEFAULT_RETRY_BACKOFF);
+        let mut retries = 0;
+
+        let stream = loop {
+            match connect_server(&context, svr_cfg, opts).await {
+                Ok(s) => break s,
+                Err(err) if retries < max_retries => {
+                    retries += 1;
+                    debug!(
+                        "connect {} failed with error: {}, retry {}/{} after {:?}",
+                        svr_cfg.addr(),
+                        err,
+                        retries,
+                        max_retries,
+                        backoff
+                    );
+                    time::sleep(backoff).await;
+                    backoff = backoff.saturating_mul(2);
                 }
+                Err(err) => return Err(err),
             }
-            None => OutboundTcpStream::connect_server_with_opts(&context, svr_cfg.tcp_external_addr(), opts).await?,
         };
 
         trace!(
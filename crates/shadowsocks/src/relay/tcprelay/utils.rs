@@ -171,6 +171,55 @@ pub fn alloc_plain_read_buffer(method: CipherKind) -> Box<[u8]> {
     vec![0u8; plain_read_buffer_size(method)].into_boxed_slice()
 }
 
+/// Length of the leading bytes of a client's request that [`probe_request_method`] requires
+///
+/// Only AEAD and AEAD-2022 methods could be probed, `0` is returned for the others.
+pub fn request_probe_len(method: CipherKind) -> usize {
+    match method.category() {
+        // SALT + *HeaderLen* + HeaderLen_TAG
+        #[cfg(feature = "aead-cipher")]
+        CipherCategory::Aead => method.salt_len() + 2 + method.tag_len(),
+        // SALT + *Type* + *Timestamp* + *HeaderLen* + HeaderLen_TAG
+        #[cfg(feature = "aead-cipher-2022")]
+        CipherCategory::Aead2022 => method.salt_len() + 1 + 8 + 2 + method.tag_len(),
+        _ => 0,
+    }
+}
+
+/// Check if a client's request, starts with `buf`, is encrypted with `method` and `key`
+///
+/// This decrypts the first chunk of the request, so `buf` must have at least [`request_probe_len`] bytes.
+/// Requests with Extensible Identity Headers (AEAD-2022 multi-users) are not supported.
+#[allow(unused_variables)]
+pub fn probe_request_method(buf: &[u8], method: CipherKind, key: &[u8]) -> bool {
+    let probe_len = request_probe_len(method);
+    if probe_len == 0 || buf.len() < probe_len {
+        return false;
+    }
+
+    match method.category() {
+        #[cfg(feature = "aead-cipher")]
+        CipherCategory::Aead => {
+            use crate::crypto::v1::Cipher;
+
+            let (salt, chunk) = buf[..probe_len].split_at(method.salt_len());
+            let mut chunk = chunk.to_vec();
+            let mut cipher = Cipher::new(method, key, salt);
+            cipher.decrypt_packet(&mut chunk)
+        }
+        #[cfg(feature = "aead-cipher-2022")]
+        CipherCategory::Aead2022 => {
+            use crate::crypto::v2::tcp::TcpCipher;
+
+            let (salt, chunk) = buf[..probe_len].split_at(method.salt_len());
+            let mut chunk = chunk.to_vec();
+            let mut cipher = TcpCipher::new(method, key, salt);
+            cipher.decrypt_packet(&mut chunk)
+        }
+        _ => false,
+    }
+}
+
 #[derive(Debug)]
 enum TransferState {
     Running(CopyBuffer),
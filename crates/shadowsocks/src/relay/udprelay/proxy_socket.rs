@@ -9,7 +9,7 @@ use std::{
     net::SocketAddr,
     sync::Arc,
     task::{ready, Context, Poll},
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
 use byte_string::ByteStr;
@@ -105,6 +105,11 @@ impl ProxySocket<ShadowUdpSocket> {
     ) -> ProxySocketResult<ProxySocket<ShadowUdpSocket>> {
         // Note: Plugins doesn't support UDP relay
 
+        // Expired servers must not be used, even if there is no other server
+        if !svr_cfg.is_currently_valid(SystemTime::now()) {
+            return Err(io::Error::other(format!("server {} is out of its validity window", svr_cfg.addr())).into());
+        }
+
         let socket = ShadowUdpSocket::connect_server_with_opts(&context, svr_cfg.udp_external_addr(), opts).await?;
 
         trace!(
@@ -0,0 +1,18 @@
+use std::net::SocketAddr;
+
+use shadowsocks::{config::ServerType, context::Context};
+
+#[tokio::test]
+async fn dns_resolve_disable_ipv6() {
+    let mut context = Context::new(ServerType::Local);
+
+    let addrs: Vec<SocketAddr> = context.dns_resolve("::1", 8388).await.unwrap().collect();
+    assert_eq!(addrs, ["[::1]:8388".parse::<SocketAddr>().unwrap()]);
+
+    context.set_disable_ipv6(true);
+
+    let addrs: Vec<SocketAddr> = context.dns_resolve("127.0.0.1", 8388).await.unwrap().collect();
+    assert_eq!(addrs, ["127.0.0.1:8388".parse::<SocketAddr>().unwrap()]);
+
+    assert!(context.dns_resolve("::1", 8388).await.is_err());
+}
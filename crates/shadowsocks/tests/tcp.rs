@@ -2,6 +2,7 @@ use std::{
     io::{self},
     net::SocketAddr,
     sync::Arc,
+    time::{Duration, SystemTime},
 };
 
 use byte_string::ByteStr;
@@ -219,3 +220,20 @@ async fn tcp_tunnel_aead_2022_chacha20() {
     .await
     .unwrap();
 }
+
+#[tokio::test]
+async fn tcp_tunnel_expired_server() {
+    let _ = env_logger::try_init();
+
+    let server_addr = "127.0.0.1:36001".parse::<SocketAddr>().unwrap();
+    let mut svr_cfg = ServerConfig::new(server_addr, "", CipherKind::NONE).unwrap();
+    svr_cfg.set_valid_until(SystemTime::now() - Duration::from_secs(1));
+
+    let context = Context::new_shared(ServerType::Local);
+    let target_addr = Address::from(("www.example.com".to_owned(), 80));
+    let err = ProxyClientStream::connect(context, &svr_cfg, target_addr)
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::Other);
+}
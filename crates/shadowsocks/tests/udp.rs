@@ -1,4 +1,9 @@
-use std::{io, net::SocketAddr, sync::Arc};
+use std::{
+    io,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 use byte_string::ByteStr;
 use log::info;
@@ -222,3 +227,15 @@ async fn udp_tunnel_aead_2022_chacha20() {
     .await
     .unwrap();
 }
+
+#[tokio::test]
+async fn udp_tunnel_expired_server() {
+    let _ = env_logger::try_init();
+
+    let server_addr = "127.0.0.1:26001".parse::<SocketAddr>().unwrap();
+    let mut svr_cfg = ServerConfig::new(server_addr, "", CipherKind::NONE).unwrap();
+    svr_cfg.set_valid_from(SystemTime::now() + Duration::from_secs(3600));
+
+    let context = Context::new_shared(ServerType::Local);
+    assert!(ProxySocket::connect(context, &svr_cfg).await.is_err());
+}
@@ -1,9 +1,7 @@
 //! Common configuration utilities
 
 use std::{
-    env,
-    fs::OpenOptions,
-    io::{self, Read},
+    env, io,
     path::{Path, PathBuf},
     str::FromStr,
 };
@@ -107,11 +105,9 @@ pub struct Config {
 impl Config {
     /// Load `Config` from file
     pub fn load_from_file<P: AsRef<Path>>(filename: &P) -> Result<Config, ConfigError> {
-        let filename = filename.as_ref();
-
-        let mut reader = OpenOptions::new().read(true).open(filename)?;
-        let mut content = String::new();
-        reader.read_to_string(&mut content)?;
+        // YAML files are converted to JSON in the same way as the service configuration
+        let content = shadowsocks_service::config::read_config_content(filename.as_ref())
+            .map_err(|err| ConfigError::InvalidValue(err.to_string()))?;
 
         Config::load_from_str(&content)
     }
@@ -147,11 +143,32 @@ impl Config {
             config.log = nlog;
         }
 
+        #[cfg(feature = "logging")]
+        if let Some(level) = ssconfig.log_level {
+            match level.parse::<log::LevelFilter>() {
+                Ok(l) => config.log.log_level = Some(l),
+                Err(..) => return Err(ConfigError::InvalidValue(level)),
+            }
+        }
+
+        #[cfg(feature = "logging")]
+        if let Some(log_file) = ssconfig.log_file {
+            if log_file.is_empty() {
+                return Err(ConfigError::InvalidValue("log_file shouldn't be an empty string".to_owned()));
+            }
+            config.log.file = Some(PathBuf::from(log_file));
+        }
+
         if let Some(runtime) = ssconfig.runtime {
             let mut nruntime = RuntimeConfig::default();
 
             #[cfg(feature = "multi-threaded")]
             if let Some(worker_count) = runtime.worker_count {
+                if worker_count == 0 {
+                    return Err(ConfigError::InvalidValue(
+                        "runtime.worker_count must be a positive integer".to_owned(),
+                    ));
+                }
                 nruntime.worker_count = Some(worker_count);
             }
 
@@ -175,6 +192,8 @@ impl Config {
             let debug_level = matches.get_count("VERBOSE");
             if debug_level > 0 {
                 self.log.level = debug_level as u32;
+                // `-v` takes precedence over `log_level` in configuration file
+                self.log.log_level = None;
             }
 
             if matches.get_flag("LOG_WITHOUT_TIME") {
@@ -210,6 +229,10 @@ pub struct LogConfig {
     pub format: LogFormatConfig,
     /// Logging configuration file path
     pub config_path: Option<PathBuf>,
+    /// Log level of shadowsocks' crates, overrides the one derived from `level`
+    pub log_level: Option<log::LevelFilter>,
+    /// Write logs to this file instead of stdout
+    pub file: Option<PathBuf>,
 }
 
 /// Logger format configuration
@@ -262,6 +285,10 @@ pub struct RuntimeConfig {
 struct SSConfig {
     #[cfg(feature = "logging")]
     log: Option<SSLogConfig>,
+    #[cfg(feature = "logging")]
+    log_level: Option<String>,
+    #[cfg(feature = "logging")]
+    log_file: Option<String>,
     runtime: Option<SSRuntimeConfig>,
 }
 
@@ -282,6 +309,7 @@ struct SSLogFormat {
 #[derive(Deserialize)]
 struct SSRuntimeConfig {
     #[cfg(feature = "multi-threaded")]
+    #[serde(alias = "worker_threads")]
     worker_count: Option<usize>,
     mode: Option<String>,
 }
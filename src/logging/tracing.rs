@@ -1,10 +1,14 @@
 //! Logging facilities with tracing
 
-use std::io::IsTerminal;
+use std::{fs::OpenOptions, io::IsTerminal, sync::Mutex};
 
 use time::UtcOffset;
 use tracing::level_filters::LevelFilter;
-use tracing_subscriber::{fmt::time::OffsetTime, EnvFilter, FmtSubscriber};
+use tracing_subscriber::{
+    fmt::{time::OffsetTime, writer::BoxMakeWriter},
+    EnvFilter,
+    FmtSubscriber,
+};
 
 use crate::config::LogConfig;
 
@@ -13,7 +17,20 @@ pub fn init_with_config(bin_name: &str, config: &LogConfig) {
     let debug_level = config.level;
     let without_time = config.format.without_time;
 
+    let writer = match config.file {
+        Some(ref path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .unwrap_or_else(|err| panic!("failed to open log file {}, error: {}", path.display(), err));
+            BoxMakeWriter::new(Mutex::new(file))
+        }
+        None => BoxMakeWriter::new(std::io::stdout),
+    };
+
     let mut builder = FmtSubscriber::builder()
+        .with_writer(writer)
         .with_level(true)
         .with_timer(match OffsetTime::local_rfc_3339() {
             Ok(t) => t,
@@ -26,7 +43,7 @@ pub fn init_with_config(bin_name: &str, config: &LogConfig) {
     // NOTE: ansi is enabled by default.
     // Could be disabled by `NO_COLOR` environment variable.
     // https://no-color.org/
-    if !std::io::stdout().is_terminal() {
+    if config.file.is_some() || !std::io::stdout().is_terminal() {
         builder = builder.with_ansi(false);
     }
 
@@ -45,22 +62,31 @@ pub fn init_with_config(bin_name: &str, config: &LogConfig) {
 
     let filter = match EnvFilter::try_from_default_env() {
         Ok(f) => f,
-        Err(..) => match debug_level {
-            0 => EnvFilter::builder()
+        Err(..) => match (config.log_level, debug_level) {
+            (Some(level), _) => {
+                let level = level.as_str().to_ascii_lowercase();
+                EnvFilter::builder()
+                    .with_regex(true)
+                    .with_default_directive(LevelFilter::ERROR.into())
+                    .parse_lossy(format!(
+                        "warn,{bin_name}={level},shadowsocks_rust={level},shadowsocks_service={level},shadowsocks={level}"
+                    ))
+            }
+            (None, 0) => EnvFilter::builder()
                 .with_regex(true)
                 .with_default_directive(LevelFilter::ERROR.into())
                 .parse_lossy(format!(
                     "warn,{}=info,shadowsocks_rust=info,shadowsocks_service=info,shadowsocks=info",
                     bin_name
                 )),
-            1 => EnvFilter::builder()
+            (None, 1) => EnvFilter::builder()
                 .with_regex(true)
                 .with_default_directive(LevelFilter::ERROR.into())
                 .parse_lossy(format!(
                     "warn,{}=debug,shadowsocks_rust=debug,shadowsocks_service=debug,shadowsocks=debug",
                     bin_name
                 )),
-            2 => EnvFilter::builder()
+            (None, 2) => EnvFilter::builder()
                 .with_regex(true)
                 .with_default_directive(LevelFilter::ERROR.into())
                 .parse_lossy(format!(
@@ -534,7 +534,7 @@ pub fn define_command_line_options(mut app: Command) -> Command {
                     .long("worker-threads")
                     .num_args(1)
                     .action(ArgAction::Set)
-                    .value_parser(clap::value_parser!(usize))
+                    .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
                     .help("Sets the number of worker threads the `Runtime` will use"),
             );
     }
@@ -961,6 +961,7 @@ pub fn create(matches: &ArgMatches) -> ShadowsocksResult<(Runtime, impl Future<O
         }
 
         info!("shadowsocks local {} build {}", crate::VERSION, crate::BUILD_TIME);
+        info!("{}", config.summary());
 
         let mut builder = match service_config.runtime.mode {
             RuntimeMode::SingleThread => Builder::new_current_thread(),
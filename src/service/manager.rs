@@ -246,7 +246,7 @@ pub fn define_command_line_options(mut app: Command) -> Command {
                     .long("worker-threads")
                     .num_args(1)
                     .action(ArgAction::Set)
-                    .value_parser(clap::value_parser!(usize))
+                    .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
                     .help("Sets the number of worker threads the `Runtime` will use"),
             );
     }
@@ -4,16 +4,20 @@ use std::{future::Future, net::IpAddr, path::PathBuf, process::ExitCode, time::D
 
 use clap::{builder::PossibleValuesParser, Arg, ArgAction, ArgGroup, ArgMatches, Command, ValueHint};
 use futures::future::{self, Either};
-use log::{info, trace};
+use log::{info, trace, warn};
 use tokio::{
     self,
     runtime::{Builder, Runtime},
+    time,
 };
 
 use shadowsocks_service::{
     acl::AccessControl,
-    config::{read_variable_field_value, Config, ConfigType, ManagerConfig, ServerInstanceConfig},
-    run_server,
+    config::{
+        read_variable_field_value, Config, ConfigType, ManagerConfig, ServerInstanceConfig, DEFAULT_SHUTDOWN_TIMEOUT,
+    },
+    net::ConnectionTracker,
+    server::run_with_connection_tracker,
     shadowsocks::{
         config::{ManagerAddr, Mode, ServerAddr, ServerConfig},
         crypto::{available_ciphers, CipherKind},
@@ -258,7 +262,7 @@ pub fn define_command_line_options(mut app: Command) -> Command {
                     .long("worker-threads")
                     .num_args(1)
                     .action(ArgAction::Set)
-                    .value_parser(clap::value_parser!(usize))
+                    .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
                     .help("Sets the number of worker threads the `Runtime` will use"),
             );
     }
@@ -504,6 +508,7 @@ pub fn create(matches: &ArgMatches) -> ShadowsocksResult<(Runtime, impl Future<O
         }
 
         info!("shadowsocks server {} build {}", crate::VERSION, crate::BUILD_TIME);
+        info!("{}", config.summary());
 
         let mut builder = match service_config.runtime.mode {
             RuntimeMode::SingleThread => Builder::new_current_thread(),
@@ -524,11 +529,14 @@ pub fn create(matches: &ArgMatches) -> ShadowsocksResult<(Runtime, impl Future<O
     };
 
     let main_fut = async move {
+        let shutdown_timeout = config.shutdown_timeout.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT);
+        let tracker = ConnectionTracker::new();
+
         let abort_signal = monitor::create_signal_monitor();
-        let server = run_server(config);
+        // Boxed for dropping it (stopping all listeners) before waiting for active connections
+        let server = Box::pin(run_with_connection_tracker(config, tracker.clone()));
 
         tokio::pin!(abort_signal);
-        tokio::pin!(server);
 
         match future::select(server, abort_signal).await {
             // Server future resolved without an error. This should never happen.
@@ -537,8 +545,26 @@ pub fn create(matches: &ArgMatches) -> ShadowsocksResult<(Runtime, impl Future<O
             )),
             // Server future resolved with error, which are listener errors in most cases
             Either::Left((Err(err), ..)) => Err(ShadowsocksError::ServerAborted(format!("server aborted with {err}"))),
-            // The abort signal future resolved. Means we should just exit.
-            Either::Right(_) => Ok(()),
+            // The abort signal future resolved. Stop accepting and wait for active connections to finish.
+            Either::Right((_, server)) => {
+                drop(server);
+
+                let active = tracker.active();
+                if active > 0 && !shutdown_timeout.is_zero() {
+                    info!(
+                        "waiting at most {:?} for {} active connections to finish",
+                        shutdown_timeout, active
+                    );
+                    if time::timeout(shutdown_timeout, tracker.wait_idle()).await.is_err() {
+                        warn!(
+                            "shutdown_timeout reached, closing {} active connections",
+                            tracker.active()
+                        );
+                    }
+                }
+
+                Ok(())
+            }
         }
     };
 
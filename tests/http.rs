@@ -85,3 +85,45 @@ async fn http_proxy() {
         assert!(buf.starts_with(b"HTTP/1.0 200 OK\r\n"));
     }
 }
+
+#[tokio::test]
+async fn http_proxy_auth_on_auto_port() {
+    let _ = env_logger::try_init();
+
+    let local_config = Config::load_from_str(
+        r#"{
+            "locals": [
+                {
+                    "local_port": 5130,
+                    "local_address": "127.0.0.1",
+                    "protocol": "auto",
+                    "local_http_username": "user",
+                    "local_http_password": "password"
+                }
+            ],
+            "server": "127.0.0.1",
+            "server_port": 5140,
+            "password": "password",
+            "method": "aes-256-gcm"
+        }"#,
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    tokio::spawn(run_local(local_config));
+
+    time::sleep(Duration::from_secs(1)).await;
+
+    let mut c = TcpStream::connect("127.0.0.1:5130").await.unwrap();
+    c.write_all(b"GET http://www.example.com/ HTTP/1.0\r\nHost: www.example.com\r\n\r\n")
+        .await
+        .unwrap();
+    c.flush().await.unwrap();
+
+    let mut r = BufReader::new(c);
+
+    let mut buf = Vec::new();
+    r.read_until(b'\n', &mut buf).await.unwrap();
+
+    assert!(buf.starts_with(b"HTTP/1.0 407 "), "{:?}", String::from_utf8_lossy(&buf));
+}
@@ -6,13 +6,14 @@ use std::{
 };
 
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
     time::{self, Duration},
 };
 
 use shadowsocks_service::{
     config::{Config, ConfigType, LocalConfig, LocalInstanceConfig, ProtocolType, ServerInstanceConfig},
-    local::socks::client::socks5::Socks5TcpClient,
+    local::{socks::client::socks5::Socks5TcpClient, Server as LocalServer},
     run_local, run_server,
     shadowsocks::{
         config::{Mode, ServerAddr, ServerConfig},
@@ -144,3 +145,85 @@ async fn socks5_relay_aead() {
     let http_status = b"HTTP/1.0 200 OK\r\n";
     assert!(buf.starts_with(http_status));
 }
+
+#[tokio::test]
+async fn socks5_bound_local_addr() {
+    let _ = env_logger::try_init();
+
+    const SERVER_ADDR: &str = "127.0.0.1:8120";
+    const LOCAL_ADDR: &str = "127.0.0.1:0";
+
+    const PASSWORD: &str = "test-password";
+    const METHOD: CipherKind = CipherKind::AES_256_GCM;
+
+    let svr = Socks5TestServer::new(SERVER_ADDR, LOCAL_ADDR, PASSWORD, METHOD, false);
+    tokio::spawn(run_server(svr.svr_config.clone()));
+
+    // Port is assigned by the system while building the local server
+    let local = LocalServer::new(svr.cli_config.clone()).await.unwrap();
+    let local_addr = local.bound_local_addr().unwrap();
+    assert_ne!(local_addr.port(), 0);
+    assert_eq!(local.bound_local_addrs(), [local_addr]);
+    tokio::spawn(local.run());
+
+    let target = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let target_addr = target.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = target.accept().await.unwrap();
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        stream.write_all(&buf).await.unwrap();
+    });
+
+    time::sleep(Duration::from_secs(1)).await;
+
+    let mut c = Socks5TcpClient::connect(Address::SocketAddress(target_addr), local_addr)
+        .await
+        .unwrap();
+    c.write_all(b"hello").await.unwrap();
+    c.flush().await.unwrap();
+
+    let mut buf = [0u8; 5];
+    c.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"hello");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn socks5_unix_socket_path() {
+    use std::{fs, io::ErrorKind};
+
+    let _ = env_logger::try_init();
+
+    let path = std::env::temp_dir().join(format!("ss-socks5-unix-{}.sock", std::process::id()));
+    let config = || {
+        Config::load_from_str(
+            &format!(
+                r#"{{"server": "127.0.0.1", "server_port": 8130, "method": "none", "local_address": "unix:{}"}}"#,
+                path.display()
+            ),
+            ConfigType::Local,
+        )
+        .unwrap()
+    };
+
+    // Files other than sockets are never removed
+    fs::write(&path, b"not a socket").unwrap();
+    let err = LocalServer::new(config()).await.err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::AddrInUse);
+    assert_eq!(fs::read(&path).unwrap(), b"not a socket");
+    fs::remove_file(&path).unwrap();
+
+    // Socket file is removed with the server
+    let local = LocalServer::new(config()).await.unwrap();
+    assert!(path.exists());
+    drop(local);
+    assert!(!path.exists());
+
+    // Stale socket file is replaced
+    let stale = std::os::unix::net::UnixListener::bind(&path).unwrap();
+    drop(stale);
+    let local = LocalServer::new(config()).await.unwrap();
+    assert!(path.exists());
+    drop(local);
+}
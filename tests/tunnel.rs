@@ -5,7 +5,7 @@ use log::debug;
 use tokio::{
     self,
     io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
-    net::{TcpStream, UdpSocket},
+    net::{TcpListener, TcpStream, UdpSocket},
     time::{self, Duration},
 };
 
@@ -85,6 +85,81 @@ async fn tcp_tunnel() {
     assert!(buf.starts_with(http_status));
 }
 
+#[tokio::test]
+async fn tcp_tunnel_fallback_method() {
+    let _ = env_logger::try_init();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let echo_server_port = listener.local_addr().unwrap().port();
+
+    // A TCP echo server
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let (mut r, mut w) = stream.split();
+                let _ = tokio::io::copy(&mut r, &mut w).await;
+            });
+        }
+    });
+
+    let (local_port, server_port) = random_local_tcp_port_pair();
+    let local_config = Config::load_from_str(
+        &format!(
+            r#"{{
+            "locals": [
+                {{
+                    "local_port": {local_port},
+                    "local_address": "127.0.0.1",
+                    "protocol": "tunnel",
+                    "forward_address": "127.0.0.1",
+                    "forward_port": {echo_server_port}
+                }}
+            ],
+            "server": "127.0.0.1",
+            "server_port": {server_port},
+            "password": "password",
+            "method": "chacha20-ietf-poly1305"
+        }}"#
+        ),
+        ConfigType::Local,
+    )
+    .unwrap();
+
+    // Server migrated to aes-256-gcm, clients with the old method are still accepted
+    let server_config = Config::load_from_str(
+        &format!(
+            r#"{{
+            "server": "127.0.0.1",
+            "server_port": {server_port},
+            "password": "password",
+            "method": "aes-256-gcm",
+            "fallback_method": "chacha20-ietf-poly1305"
+        }}"#
+        ),
+        ConfigType::Server,
+    )
+    .unwrap();
+
+    tokio::spawn(run_local(local_config));
+    tokio::spawn(run_server(server_config));
+
+    time::sleep(Duration::from_secs(5)).await;
+
+    const MESSAGE: &[u8] = b"hello shadowsocks\n";
+
+    let mut stream = TcpStream::connect(("127.0.0.1", local_port)).await.unwrap();
+    stream.write_all(MESSAGE).await.unwrap();
+    stream.flush().await.unwrap();
+
+    let mut r = BufReader::new(stream);
+
+    let mut buf = Vec::new();
+    r.read_until(b'\n', &mut buf).await.unwrap();
+
+    assert_eq!(MESSAGE, buf);
+}
+
 #[tokio::test]
 async fn udp_tunnel() {
     let _ = env_logger::try_init();